@@ -804,6 +804,43 @@ where
                 self.make_executed_block_canonical(block);
                 tx.send(()).unwrap();
             }
+            PipeExecLayerEvent::BlockExecuted {
+                block_number,
+                block_hash,
+                receipts,
+                fork_features,
+            } => {
+                trace!(target: "on_pipe_exec_event",
+                    block_number=%block_number,
+                    block_hash=%block_hash,
+                    receipts=%receipts.len(),
+                    fork_features=?fork_features,
+                    "Received block executed event");
+            }
+            PipeExecLayerEvent::ExecutionFailed { block_id, error } => {
+                error!(target: "on_pipe_exec_event",
+                    block_id=%block_id,
+                    %error,
+                    "Pipe execution failed");
+            }
+            PipeExecLayerEvent::Halted { reason } => {
+                error!(target: "on_pipe_exec_event",
+                    %reason,
+                    "Pipe execution halted, no more blocks are accepted");
+            }
+            PipeExecLayerEvent::StateDiff { block_number, accounts, storage } => {
+                trace!(target: "on_pipe_exec_event",
+                    block_number=%block_number,
+                    accounts=%accounts.len(),
+                    storage=%storage.len(),
+                    "Received state diff event");
+            }
+            PipeExecLayerEvent::Reorg { from, to } => {
+                warn!(target: "on_pipe_exec_event",
+                    from=%from,
+                    to=%to,
+                    "Pipe execution rolled back canonical blocks for a reorg");
+            }
         }
     }
 
@@ -1,6 +1,9 @@
+use reth_ethereum_primitives::Block;
+use reth_primitives_traits::RecoveredBlock;
 use reth_revm::database::StateProviderDatabase;
 use reth_storage_api::{
-    errors::provider::ProviderError, StateProviderBox, StateProviderFactory, STATE_PROVIDER_OPTS,
+    errors::provider::ProviderError, BlockReader, StateProviderBox, StateProviderFactory,
+    TransactionVariant, STATE_PROVIDER_OPTS,
 };
 use reth_trie::{updates::TrieUpdates, HashedPostState, KeccakKeyHasher};
 use revm::{
@@ -18,9 +21,14 @@ use std::{
 
 use crate::{GravityStorage, GravityStorageError};
 
+type CanonicalBlockReader = Box<dyn Fn(u64) -> Option<RecoveredBlock<Block>> + Send + Sync>;
+
 pub struct BlockViewStorage<Client> {
     client: Client,
     inner: Mutex<BlockViewStorageInner>,
+    // Reader of the canonical blocks persisted by the client, only set by
+    // with_canonical_block_reader since it requires the client to be a BlockReader
+    canonical_block_reader: Option<CanonicalBlockReader>,
 }
 
 struct BlockViewStorageInner {
@@ -61,8 +69,25 @@ impl<Client: StateProviderFactory + 'static> BlockViewStorage<Client> {
                 latest_block_hash,
                 block_number_to_id,
             )),
+            canonical_block_reader: None,
         }
     }
+
+    /// Read the canonical blocks from the client, so that `get_canonical_block` provides the
+    /// blocks replayed on startup.
+    pub fn with_canonical_block_reader(mut self) -> Self
+    where
+        Client: BlockReader<Block = Block> + Clone,
+    {
+        let client = self.client.clone();
+        self.canonical_block_reader = Some(Box::new(move |block_number| {
+            client
+                .sealed_block_with_senders(block_number.into(), TransactionVariant::WithHash)
+                .ok()
+                .flatten()
+        }));
+        self
+    }
 }
 
 impl BlockViewStorageInner {
@@ -76,6 +101,15 @@ impl BlockViewStorageInner {
     }
 }
 
+fn get_history_provider<Client: StateProviderFactory + 'static>(
+    client: &Client,
+    block_number: u64,
+) -> Result<StateProviderBox, GravityStorageError> {
+    client
+        .history_by_block_number(block_number)
+        .map_err(|err| GravityStorageError::HistoryUnavailable { block_number, error: Some(err) })
+}
+
 impl<Client: StateProviderFactory + 'static> GravityStorage for BlockViewStorage<Client> {
     type StateView = BlockViewProvider;
 
@@ -91,6 +125,13 @@ impl<Client: StateProviderFactory + 'static> GravityStorage for BlockViewStorage
         if target_block_number > latest_block_number {
             return Err(GravityStorageError::TooNew(target_block_number));
         }
+        // The views of the canonical blocks below the base have been reclaimed
+        if target_block_number < base_block_number {
+            return Err(GravityStorageError::HistoryUnavailable {
+                block_number: target_block_number,
+                error: None,
+            });
+        }
 
         let block_id = *storage.block_number_to_id.get(&target_block_number).unwrap();
         let block_number_to_id = storage.block_number_to_id.clone();
@@ -115,6 +156,10 @@ impl<Client: StateProviderFactory + 'static> GravityStorage for BlockViewStorage
         ))
     }
 
+    fn get_block_id(&self, block_number: u64) -> Option<B256> {
+        self.inner.lock().unwrap().block_number_to_id.get(&block_number).copied()
+    }
+
     fn insert_block_id(&self, block_number: u64, block_id: B256) {
         let mut storage = self.inner.lock().unwrap();
         storage.block_number_to_id.insert(block_number, block_id);
@@ -146,10 +191,77 @@ impl<Client: StateProviderFactory + 'static> GravityStorage for BlockViewStorage
     fn update_canonical(&self, block_number: u64, block_hash: B256) {
         let mut storage = self.inner.lock().unwrap();
         assert!(block_number > storage.state_provider_info.1);
-        let gc_block_number = storage.state_provider_info.1;
         storage.state_provider_info = (block_hash, block_number);
-        storage.block_number_to_view.remove(&gc_block_number);
-        storage.block_number_to_trie_updates.remove(&gc_block_number);
+        // The views up to the new canonical block are read from the state provider from now on
+        let retained = storage.block_number_to_view.split_off(&(block_number + 1));
+        storage.block_number_to_view = retained;
+        let retained = storage.block_number_to_trie_updates.split_off(&(block_number + 1));
+        storage.block_number_to_trie_updates = retained;
+    }
+
+    fn rollback_to(&self, block_number: u64) -> Result<(), GravityStorageError> {
+        let mut storage = self.inner.lock().unwrap();
+        let canonical_block_number = storage.state_provider_info.1;
+        if block_number < canonical_block_number {
+            return Err(GravityStorageError::InvalidRollbackTarget {
+                block_number,
+                canonical_block_number,
+            });
+        }
+        storage.block_number_to_view.retain(|number, _| *number <= block_number);
+        storage.block_number_to_trie_updates.retain(|number, _| *number <= block_number);
+        storage.block_number_to_id.retain(|number, _| *number <= block_number);
+        Ok(())
+    }
+
+    fn unwind_canonical(
+        &self,
+        block_number: u64,
+        block_hash: B256,
+    ) -> Result<(), GravityStorageError> {
+        let mut storage = self.inner.lock().unwrap();
+        let canonical_block_number = storage.state_provider_info.1;
+        if block_number > canonical_block_number {
+            return Err(GravityStorageError::InvalidRollbackTarget {
+                block_number,
+                canonical_block_number,
+            });
+        }
+        storage.state_provider_info = (block_hash, block_number);
+        // The views of the canonical blocks have been reclaimed, and the remaining ones are all
+        // above block_number
+        storage.block_number_to_view.clear();
+        storage.block_number_to_trie_updates.clear();
+        storage.block_number_to_id.retain(|number, _| *number <= block_number);
+        Ok(())
+    }
+
+    fn get_canonical_block(&self, block_number: u64) -> Option<RecoveredBlock<Block>> {
+        self.canonical_block_reader.as_ref().and_then(|read| read(block_number))
+    }
+
+    fn get_historical_state_view(
+        &self,
+        block_number: u64,
+    ) -> Result<Self::StateView, GravityStorageError> {
+        let block_number_to_id = self.inner.lock().unwrap().block_number_to_id.clone();
+        Ok(BlockViewProvider::new(
+            vec![],
+            block_number_to_id,
+            get_history_provider(&self.client, block_number)?,
+        ))
+    }
+
+    fn historical_state_root(
+        &self,
+        block_number: u64,
+        bundle_state: &BundleState,
+    ) -> Result<B256, GravityStorageError> {
+        let hashed_state =
+            HashedPostState::from_bundle_state::<KeccakKeyHasher>(&bundle_state.state);
+        get_history_provider(&self.client, block_number)?.state_root(hashed_state).map_err(|err| {
+            GravityStorageError::HistoryUnavailable { block_number, error: Some(err) }
+        })
     }
 
     fn state_root_with_updates(
@@ -193,6 +305,29 @@ impl<Client: StateProviderFactory + 'static> GravityStorage for BlockViewStorage
 
         Ok((state_root, hashed_state, trie_updates))
     }
+
+    fn state_root(&self, block_number: u64) -> Result<B256, GravityStorageError> {
+        let storage = self.inner.lock().unwrap();
+        let (base_block_hash, base_block_number) = storage.state_provider_info;
+        let hashed_state_vec: Vec<_> = storage
+            .block_number_to_view
+            .range(base_block_number + 1..block_number + 1)
+            .map(|(_, view)| view.1.clone())
+            .collect();
+        drop(storage);
+
+        // Block number should be continuous
+        assert_eq!(hashed_state_vec.len() as u64, block_number - base_block_number);
+
+        let mut hashed_state = HashedPostState::default();
+        for block_hashed_state in hashed_state_vec {
+            hashed_state.extend_ref(&block_hashed_state);
+        }
+        let state_provider = get_state_provider(&self.client, base_block_hash, false)?;
+        state_provider
+            .state_root(hashed_state)
+            .map_err(|err| GravityStorageError::StateProviderError((base_block_hash, err)))
+    }
 }
 
 struct BlockView {
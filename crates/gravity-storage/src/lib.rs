@@ -3,7 +3,10 @@ pub mod block_view_storage;
 use std::sync::Arc;
 
 use alloy_primitives::B256;
+use async_trait::async_trait;
+use reth_ethereum_primitives::Block;
 use reth_evm::ParallelDatabase;
+use reth_primitives_traits::RecoveredBlock;
 use reth_storage_api::errors::provider::ProviderError;
 use reth_trie::{updates::TrieUpdates, HashedPostState};
 use revm::db::BundleState;
@@ -15,6 +18,14 @@ pub enum GravityStorageError {
     // block number too new
     TooNew(u64),
     StateProviderError((B256, ProviderError)),
+    // the block at the number is on another fork
+    BlockIdMismatch { block_number: u64, block_id: B256, stored_block_id: B256 },
+    // the state of the canonical block at the number is not available from the provider
+    HistoryUnavailable { block_number: u64, error: Option<ProviderError> },
+    // the operation is not supported by the storage
+    Unsupported(&'static str),
+    // the block number to roll back or unwind to is on the wrong side of the canonical block
+    InvalidRollbackTarget { block_number: u64, canonical_block_number: u64 },
 }
 
 // 实现错误显示
@@ -31,10 +42,50 @@ impl std::fmt::Display for GravityStorageError {
                     block_hash, error
                 )
             }
+            GravityStorageError::BlockIdMismatch { block_number, block_id, stored_block_id } => {
+                write!(
+                    f,
+                    "Block id mismatch. block_number={}, block_id={}, stored_block_id={}",
+                    block_number, block_id, stored_block_id
+                )
+            }
+            GravityStorageError::HistoryUnavailable { block_number, error: Some(error) } => {
+                write!(
+                    f,
+                    "Historical state unavailable. block_number={}, error={}",
+                    block_number, error
+                )
+            }
+            GravityStorageError::HistoryUnavailable { block_number, error: None } => {
+                write!(f, "Historical state unavailable. block_number={}", block_number)
+            }
+            GravityStorageError::Unsupported(operation) => {
+                write!(f, "Unsupported operation. operation={}", operation)
+            }
+            GravityStorageError::InvalidRollbackTarget { block_number, canonical_block_number } => {
+                write!(
+                    f,
+                    "Invalid rollback target. block_number={}, canonical_block_number={}",
+                    block_number, canonical_block_number
+                )
+            }
         }
     }
 }
 
+/// Storage of the states of the blocks in the pipeline.
+///
+/// The calls on the critical path of each block are `get_state_view` before execution,
+/// `insert_block_id` and `insert_bundle_state` after execution, `state_root_with_updates` for
+/// merklization, and `update_canonical` once the block is made canonical. `insert_block_id` and
+/// `insert_bundle_state` are called while the pipeline holds its epoch lock, so they must be local
+/// and fast. `state_root_with_updates` is called on the blocking thread pool and may block.
+/// `get_state_view` and `update_canonical` are called on the async runtime through their async
+/// variants, which a storage backed by a remote store should override instead of blocking.
+/// The other calls are off the critical path, e.g. on startup or rollback. The canonical blocks
+/// and the historical states are only read for replaying the latest canonical blocks on startup.
+/// They are unavailable by default.
+#[async_trait]
 pub trait GravityStorage: Send + Sync + 'static {
     type StateView: ParallelDatabase;
 
@@ -44,6 +95,44 @@ pub trait GravityStorage: Send + Sync + 'static {
         block_number: u64,
     ) -> Result<(B256, Self::StateView), GravityStorageError>;
 
+    // Async variant of get_state_view called by the pipeline, which calls get_state_view in place
+    // by default
+    async fn get_state_view_async(
+        &self,
+        block_number: u64,
+    ) -> Result<(B256, Self::StateView), GravityStorageError> {
+        self.get_state_view(block_number)
+    }
+
+    // Get the state view of block_number only if its block_id is block_id, so that the state of
+    // another fork sharing the number is never used
+    fn get_state_view_by_id(
+        &self,
+        block_number: u64,
+        block_id: B256,
+    ) -> Result<Self::StateView, GravityStorageError> {
+        let (stored_block_id, state_view) = self.get_state_view(block_number)?;
+        check_block_id(block_number, block_id, stored_block_id)?;
+        Ok(state_view)
+    }
+
+    // Async variant of get_state_view_by_id called by the pipeline, which calls
+    // get_state_view_async by default
+    async fn get_state_view_by_id_async(
+        &self,
+        block_number: u64,
+        block_id: B256,
+    ) -> Result<Self::StateView, GravityStorageError> {
+        let (stored_block_id, state_view) = self.get_state_view_async(block_number).await?;
+        check_block_id(block_number, block_id, stored_block_id)?;
+        Ok(state_view)
+    }
+
+    // Get the block_id of block_number, if the mapping is known. Unknown by default
+    fn get_block_id(&self, _block_number: u64) -> Option<B256> {
+        None
+    }
+
     // Insert the mapping from block_number to block_id
     fn insert_block_id(&self, block_number: u64, block_id: B256);
 
@@ -53,9 +142,86 @@ pub trait GravityStorage: Send + Sync + 'static {
     // Update canonical to block_number and reclaim the intermediate result cache
     fn update_canonical(&self, block_number: u64, block_hash: B256);
 
+    // Remove the bundle states of the canonical blocks below block number below, which are never
+    // rolled back to. Returns the number of removed bundle states. Nothing is removed by default,
+    // for a storage which reclaims them in update_canonical
+    fn prune_bundle_states(&self, _below: u64) -> usize {
+        0
+    }
+
+    // Async variant of update_canonical called by the pipeline, which calls update_canonical in
+    // place by default
+    async fn update_canonical_async(&self, block_number: u64, block_hash: B256) {
+        self.update_canonical(block_number, block_hash)
+    }
+
+    // Discard the bundle states, trie updates and block ids above block_number, which must not be
+    // lower than the canonical block number. Unsupported by default
+    fn rollback_to(&self, _block_number: u64) -> Result<(), GravityStorageError> {
+        Err(GravityStorageError::Unsupported("rollback_to"))
+    }
+
+    // Move canonical back to block_number on a reorg, discarding the bundle states, trie updates
+    // and block ids above it. The state of block_number is read from the provider by block_hash
+    // afterwards, so block_number must still be known to the provider. Unsupported by default
+    fn unwind_canonical(
+        &self,
+        _block_number: u64,
+        _block_hash: B256,
+    ) -> Result<(), GravityStorageError> {
+        Err(GravityStorageError::Unsupported("unwind_canonical"))
+    }
+
+    // Get the canonical block of block_number persisted by the provider along with its senders,
+    // even below the latest canonical block
+    fn get_canonical_block(&self, _block_number: u64) -> Option<RecoveredBlock<Block>> {
+        None
+    }
+
+    // Get the state view of the canonical block of block_number persisted by the provider, even
+    // below the latest canonical block
+    fn get_historical_state_view(
+        &self,
+        block_number: u64,
+    ) -> Result<Self::StateView, GravityStorageError> {
+        Err(GravityStorageError::HistoryUnavailable { block_number, error: None })
+    }
+
+    // Calculate the state root of the state of the canonical block of block_number with
+    // bundle_state applied on top, i.e. the state root of its child executed to bundle_state
+    fn historical_state_root(
+        &self,
+        block_number: u64,
+        _bundle_state: &BundleState,
+    ) -> Result<B256, GravityStorageError> {
+        Err(GravityStorageError::HistoryUnavailable { block_number, error: None })
+    }
+
     // calculate state root by block_number
     fn state_root_with_updates(
         &self,
         block_number: u64,
     ) -> Result<(B256, Arc<HashedPostState>, Arc<TrieUpdates>), GravityStorageError>;
+
+    // Calculate state root by block_number from scratch without reusing the trie updates of the
+    // previous blocks, which is slow and only for cross-checking state_root_with_updates.
+    // Unsupported by default
+    fn state_root(&self, _block_number: u64) -> Result<B256, GravityStorageError> {
+        Err(GravityStorageError::Unsupported("state_root"))
+    }
+}
+
+fn check_block_id(
+    block_number: u64,
+    block_id: B256,
+    stored_block_id: B256,
+) -> Result<(), GravityStorageError> {
+    if stored_block_id != block_id {
+        return Err(GravityStorageError::BlockIdMismatch {
+            block_number,
+            block_id,
+            stored_block_id,
+        });
+    }
+    Ok(())
 }
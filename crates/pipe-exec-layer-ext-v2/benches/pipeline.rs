@@ -0,0 +1,177 @@
+#![allow(missing_docs)]
+use alloy_consensus::{Header, TxLegacy};
+use alloy_primitives::{Address, PrimitiveSignature as Signature, TxKind, B256, U256};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use reth_chainspec::MAINNET;
+use reth_ethereum_primitives::{Transaction, TransactionSigned};
+use reth_pipe_exec_layer_ext_v2::{
+    test_utils::{new_test_pipe_exec_layer, InMemoryStorage},
+    ExecutedBlockMeta, ExecutionArgs, OrderedBlock, PipeExecLayerConfig, PipeExecLayerEvent,
+    StageTimings,
+};
+use revm::{
+    db::{CacheDB, EmptyDB},
+    primitives::AccountInfo,
+};
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+
+/// Number of blocks pushed through a fresh pipeline in each iteration
+const BLOCKS: u64 = 16;
+
+fn block_id(number: u64) -> B256 {
+    B256::from(U256::from(number))
+}
+
+/// Each sender transfers once per block, so the transactions of a block touch `senders.len()`
+/// accounts besides the recipient and the coinbase.
+fn ordered_block(number: u64, senders: &[Address]) -> OrderedBlock {
+    let transactions = senders
+        .iter()
+        .map(|_| {
+            TransactionSigned::new_unhashed(
+                Transaction::Legacy(TxLegacy {
+                    chain_id: Some(MAINNET.chain().id()),
+                    nonce: number - 1,
+                    gas_price: 1,
+                    gas_limit: 21_000,
+                    to: TxKind::Call(Address::ZERO),
+                    ..Default::default()
+                }),
+                Signature::test_signature(),
+            )
+        })
+        .collect();
+    OrderedBlock {
+        parent_id: block_id(number - 1),
+        id: block_id(number),
+        number,
+        timestamp: number,
+        coinbase: Address::with_last_byte(0xff),
+        prev_randao: B256::ZERO,
+        withdrawals: Default::default(),
+        transactions,
+        senders: senders.to_vec(),
+        blob_sidecars: None,
+        presumed_valid: false,
+    }
+}
+
+/// Push `BLOCKS` blocks of `tx_count` transfers through a fresh pipeline over `InMemoryStorage`,
+/// with Coordinator verifying each block as soon as it's executed. Returns the time from the
+/// first push until the last block is made canonical, along with the stage timings of the blocks.
+async fn run_pipeline(tx_count: usize) -> (Duration, Vec<StageTimings>) {
+    let senders: Vec<_> =
+        (0..tx_count).map(|i| Address::from_word(B256::from(U256::from(i + 1)))).collect();
+    let mut state = CacheDB::new(EmptyDB::default());
+    for sender in &senders {
+        state.insert_account_info(
+            *sender,
+            AccountInfo { balance: U256::from(1_000_000_000), ..Default::default() },
+        );
+    }
+    let blocks: Vec<_> = (1..=BLOCKS).map(|number| ordered_block(number, &senders)).collect();
+
+    let (execution_args_tx, execution_args_rx) = oneshot::channel();
+    let (api, ext) = new_test_pipe_exec_layer(
+        MAINNET.clone(),
+        InMemoryStorage::new(state),
+        Header::default(),
+        B256::ZERO,
+        execution_args_rx,
+        PipeExecLayerConfig::default(),
+    )
+    .unwrap();
+    execution_args_tx
+        .send(ExecutionArgs { block_number_to_block_id: BTreeMap::from([(0, block_id(0))]) })
+        .unwrap();
+    let ext = Arc::new(ext);
+    let mut canonical_rx = ext.subscribe_canonical();
+    let el = {
+        let ext = ext.clone();
+        std::thread::spawn(move || {
+            while let Ok(event) = ext.event_rx.lock().unwrap().recv() {
+                if let PipeExecLayerEvent::MakeCanonical(_, tx) = event {
+                    let _ = tx.send(());
+                }
+            }
+        })
+    };
+
+    let api = Arc::new(api);
+    let start = Instant::now();
+    let coordinator = {
+        let api = api.clone();
+        tokio::spawn(async move {
+            for number in 1..=BLOCKS {
+                let block_id = block_id(number);
+                let block_hash = api.pull_executed_block_hash(block_id).await.unwrap();
+                api.commit_executed_block_hash(ExecutedBlockMeta { block_id, block_hash }).unwrap();
+            }
+        })
+    };
+    for block in blocks {
+        api.push_ordered_block(block).await.unwrap();
+    }
+    coordinator.await.unwrap();
+    while canonical_rx.recv().await.unwrap().block.header().number < BLOCKS {}
+    let elapsed = start.elapsed();
+
+    let stage_timings =
+        ext.recent_stage_timings().into_iter().map(|(_, timings)| timings).collect();
+    api.shutdown().await;
+    drop(api);
+    el.join().unwrap();
+    (elapsed, stage_timings)
+}
+
+/// Print the mean of each stage over the measured blocks, which criterion doesn't report.
+fn report_stage_timings(tx_count: usize, stage_timings: &[StageTimings]) {
+    let mean = |stage: fn(&StageTimings) -> Duration| {
+        stage_timings.iter().map(stage).sum::<Duration>() / stage_timings.len().max(1) as u32
+    };
+    println!(
+        "pipeline/process/{tx_count}: mean per block over {} blocks: execute {:?}, merklize {:?}, \
+         seal {:?}, verify {:?}, make_canonical {:?}",
+        stage_timings.len(),
+        mean(|timings| timings.execute),
+        mean(|timings| timings.merklize),
+        mean(|timings| timings.seal),
+        mean(|timings| timings.verify),
+        mean(|timings| timings.make_canonical),
+    );
+}
+
+/// Throughput of the whole pipeline, i.e. filtering, execution, merklization, sealing and making
+/// the blocks canonical, in blocks per second for blocks of varying sizes.
+pub fn pipeline_benchmark(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    let mut group = c.benchmark_group("pipeline");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(BLOCKS));
+    for tx_count in [0, 100, 1_000] {
+        let mut stage_timings = Vec::new();
+        group.bench_with_input(BenchmarkId::new("process", tx_count), &tx_count, |b, &tx_count| {
+            b.iter_custom(|iters| {
+                runtime.block_on(async {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        let (duration, timings) = run_pipeline(tx_count).await;
+                        elapsed += duration;
+                        stage_timings.extend(timings);
+                    }
+                    elapsed
+                })
+            })
+        });
+        report_stage_timings(tx_count, &stage_timings);
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pipeline_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+#![allow(missing_docs)]
+use alloy_consensus::{Header, TxLegacy};
+use alloy_primitives::{PrimitiveSignature as Signature, TxKind, B256};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use reth_ethereum_primitives::{Block, BlockBody, Transaction, TransactionSigned};
+use reth_primitives_traits::Block as _;
+
+fn block_with_txs(tx_count: u64) -> Block {
+    let transactions = (0..tx_count)
+        .map(|nonce| {
+            TransactionSigned::new_unhashed(
+                Transaction::Legacy(TxLegacy {
+                    nonce,
+                    gas_limit: 21_000,
+                    to: TxKind::Call(Default::default()),
+                    ..Default::default()
+                }),
+                Signature::test_signature(),
+            )
+        })
+        .collect();
+    Block {
+        header: Header {
+            number: 1,
+            transactions_root: B256::with_last_byte(1),
+            receipts_root: B256::with_last_byte(2),
+            state_root: B256::with_last_byte(3),
+            ..Default::default()
+        },
+        body: BlockBody { transactions, ..Default::default() },
+    }
+}
+
+/// Sealing a block only hashes its header, whose roots are filled beforehand, so the duration
+/// must not grow with the number of transactions.
+pub fn seal_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("seal");
+    for tx_count in [0, 1_000] {
+        let block = block_with_txs(tx_count);
+        group.bench_with_input(BenchmarkId::new("seal_slow", tx_count), &block, |b, block| {
+            b.iter_batched(
+                || block.clone(),
+                |block| block.seal_slow(),
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, seal_benchmark);
+criterion_main!(benches);
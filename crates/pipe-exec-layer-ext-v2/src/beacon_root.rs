@@ -0,0 +1,50 @@
+use crate::OrderedBlock;
+use alloy_consensus::Header;
+use alloy_primitives::B256;
+
+/// Source of the `parent_beacon_block_root` of the blocks since Cancun.
+///
+/// The root is written into the block header before execution, and the executor stores it into
+/// the EIP-4788 beacon roots contract with a system call before the transactions of the block,
+/// where contracts can read it back by the block timestamp. So it changes both the state root and
+/// the block hash, and must be derived deterministically from the ordered block and its parent,
+/// otherwise the nodes of the chain would disagree on the executed block hash.
+pub trait ParentBeaconBlockRoot: Send + Sync {
+    /// The `parent_beacon_block_root` of the block executed from `ordered_block` on top of
+    /// `parent_header`.
+    fn parent_beacon_block_root(
+        &self,
+        ordered_block: &OrderedBlock,
+        parent_header: &Header,
+    ) -> B256;
+}
+
+impl<F> ParentBeaconBlockRoot for F
+where
+    F: Fn(&OrderedBlock, &Header) -> B256 + Send + Sync,
+{
+    fn parent_beacon_block_root(
+        &self,
+        ordered_block: &OrderedBlock,
+        parent_header: &Header,
+    ) -> B256 {
+        self(ordered_block, parent_header)
+    }
+}
+
+impl std::fmt::Debug for dyn ParentBeaconBlockRoot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParentBeaconBlockRoot").finish_non_exhaustive()
+    }
+}
+
+/// Use the id of the parent block generated by Gravity SDK as the `parent_beacon_block_root`,
+/// since there's no beacon chain behind the pipeline.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParentBlockIdAsBeaconRoot;
+
+impl ParentBeaconBlockRoot for ParentBlockIdAsBeaconRoot {
+    fn parent_beacon_block_root(&self, ordered_block: &OrderedBlock, _: &Header) -> B256 {
+        ordered_block.parent_id
+    }
+}
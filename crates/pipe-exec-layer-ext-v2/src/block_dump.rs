@@ -0,0 +1,448 @@
+use alloy_consensus::{ReceiptWithBloom, TxReceipt};
+use alloy_eips::eip7685::Requests;
+use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_rlp::{Decodable, Encodable};
+use gravity_storage::GravityStorageError;
+use reth_ethereum_primitives::{Block, Receipt};
+use reth_evm::execute::BlockExecutionError;
+use reth_execution_types::ExecutionOutcome;
+use reth_primitives_traits::{RecoveredBlock, SealedBlock};
+use revm::{
+    db::{
+        states::{
+            reverts::{AccountInfoRevert, Reverts},
+            StorageSlot,
+        },
+        AccountRevert, AccountStatus, BundleAccount, BundleState, RevertToSlot,
+    },
+    primitives::{AccountInfo, Bytecode},
+};
+use std::path::Path;
+
+/// Leading bytes of a block dump.
+pub const BLOCK_DUMP_MAGIC: [u8; 8] = *b"GRAVBLCK";
+
+/// Version of the layout written by [`BlockDump::encode`], bumped on any change of the layout so
+/// that dumps written by different versions are never mistaken for a divergence.
+pub const BLOCK_DUMP_VERSION: u16 = 1;
+
+/// The tags of the account statuses are their positions, so new statuses must be appended.
+const ACCOUNT_STATUSES: [AccountStatus; 8] = [
+    AccountStatus::LoadedNotExisting,
+    AccountStatus::Loaded,
+    AccountStatus::LoadedEmptyEIP161,
+    AccountStatus::InMemoryChange,
+    AccountStatus::Changed,
+    AccountStatus::Destroyed,
+    AccountStatus::DestroyedChanged,
+    AccountStatus::DestroyedAgain,
+];
+
+/// A sealed block along with its execution outcome, for comparing the blocks of two nodes which
+/// have diverged. Unlike the JSON dump of a failed block, the encoding doesn't depend on the
+/// iteration order of the hash maps of the bundle state, so the dumps of the same block by two
+/// nodes are identical byte for byte unless the nodes disagree on the block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockDump {
+    /// The sealed block, along with the senders of its transactions
+    pub block: RecoveredBlock<Block>,
+    /// Receipts and state changes of the block
+    pub execution_outcome: ExecutionOutcome,
+}
+
+impl BlockDump {
+    /// Serialize the dump as follows, with the integers in big-endian:
+    /// - `BLOCK_DUMP_MAGIC`, then `BLOCK_DUMP_VERSION` as `u16`
+    /// - The RLP encoding of the block, then its senders
+    /// - The first block number, the receipts and the requests of the execution outcome
+    /// - The accounts of the bundle state sorted by address, each with its storage sorted by slot,
+    ///   then the contracts sorted by code hash, then the reverts of each block sorted by address
+    ///
+    /// Sequences are prefixed with their length as `u64`, and optional values with a 0 or 1 tag.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer.fixed(&BLOCK_DUMP_MAGIC);
+        writer.fixed(&BLOCK_DUMP_VERSION.to_be_bytes());
+        writer.rlp(&self.block.sealed_block().clone_block());
+        writer.list(self.block.senders().iter(), |writer, sender| writer.fixed(sender.as_slice()));
+
+        let execution_outcome = &self.execution_outcome;
+        writer.u64(execution_outcome.first_block);
+        writer.list(execution_outcome.receipts.iter(), |writer, receipts| {
+            writer.list(receipts.iter(), |writer, receipt| {
+                writer.rlp(&ReceiptWithBloom {
+                    logs_bloom: receipt.bloom(),
+                    receipt: receipt.clone(),
+                })
+            })
+        });
+        writer.list(execution_outcome.requests.iter(), |writer, requests| {
+            writer.list(requests.iter(), |writer, request| writer.bytes(request))
+        });
+
+        let bundle = &execution_outcome.bundle;
+        writer.list(sorted(&bundle.state).into_iter(), |writer, (address, account)| {
+            writer.fixed(address.as_slice());
+            writer.bundle_account(account);
+        });
+        writer.list(sorted(&bundle.contracts).into_iter(), |writer, (code_hash, code)| {
+            writer.fixed(code_hash.as_slice());
+            writer.bytes(&code.original_bytes());
+        });
+        writer.list(bundle.reverts.iter(), |writer, reverts| {
+            let mut reverts = reverts.iter().collect::<Vec<_>>();
+            reverts.sort_unstable_by_key(|(address, _)| *address);
+            writer.list(reverts.into_iter(), |writer, (address, revert)| {
+                writer.fixed(address.as_slice());
+                writer.account_revert(revert);
+            });
+        });
+        writer.0
+    }
+
+    /// Deserialize a dump written by [`BlockDump::encode`] of the same `BLOCK_DUMP_VERSION`.
+    pub fn decode(buf: &[u8]) -> Result<Self, BlockDumpError> {
+        let mut reader = Reader(buf);
+        if reader.take(BLOCK_DUMP_MAGIC.len())? != BLOCK_DUMP_MAGIC {
+            return Err(BlockDumpError::NotABlockDump);
+        }
+        let version = u16::from_be_bytes(reader.array()?);
+        if version != BLOCK_DUMP_VERSION {
+            return Err(BlockDumpError::UnsupportedVersion(version));
+        }
+        let block = reader.rlp::<Block>()?;
+        let senders = reader.list(Reader::address)?;
+
+        let first_block = reader.u64()?;
+        let receipts = reader.list(|reader| {
+            reader.list(|reader| Ok(reader.rlp::<ReceiptWithBloom<Receipt>>()?.receipt))
+        })?;
+        let requests = reader.list(|reader| Ok(Requests::from(reader.list(Reader::bytes)?)))?;
+
+        let state = reader.list(|reader| Ok((reader.address()?, reader.bundle_account()?)))?;
+        let contracts = reader.list(|reader| {
+            let code_hash = reader.b256()?;
+            Ok((code_hash, reader.bytecode()?))
+        })?;
+        let reverts = reader.list(|reader| {
+            reader.list(|reader| Ok((reader.address()?, reader.account_revert()?)))
+        })?;
+        if !reader.0.is_empty() {
+            return Err(BlockDumpError::Malformed("trailing bytes"));
+        }
+
+        let bundle = BundleState {
+            state_size: state.iter().map(|(_, account)| account.size_hint()).sum(),
+            reverts_size: reverts.iter().flatten().map(|(_, revert)| revert.size_hint()).sum(),
+            state: state.into_iter().collect(),
+            contracts: contracts.into_iter().collect(),
+            reverts: Reverts::new(reverts),
+        };
+        Ok(Self {
+            block: RecoveredBlock::new_sealed(SealedBlock::seal_slow(block), senders),
+            execution_outcome: ExecutionOutcome::new(bundle, receipts, first_block, requests),
+        })
+    }
+
+    /// Write the dump into the file at `path`, replacing it if it exists.
+    pub fn write(&self, path: &Path) -> Result<(), BlockDumpError> {
+        Ok(std::fs::write(path, self.encode())?)
+    }
+}
+
+/// Load a dump written by `PipeExecLayerApi::dump_block`.
+pub fn load_block_dump(path: &Path) -> Result<BlockDump, BlockDumpError> {
+    BlockDump::decode(&std::fs::read(path)?)
+}
+
+/// Errors of dumping a block, or loading the dump
+#[derive(Debug, thiserror::Error)]
+pub enum BlockDumpError {
+    /// The block or its parent is not canonical, or not provided by the storage. The genesis
+    /// block has no parent to be executed on top of, so it can't be dumped either
+    #[error("canonical block {0} unavailable")]
+    BlockUnavailable(u64),
+    /// The storage doesn't provide the historical state to execute the block again
+    #[error(transparent)]
+    Storage(#[from] GravityStorageError),
+    /// The block failed to execute again
+    #[error("failed to execute block: {0}")]
+    ExecutionFailed(#[from] BlockExecutionError),
+    /// The dump can't be written or read
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// The data doesn't start with `BLOCK_DUMP_MAGIC`
+    #[error("not a block dump")]
+    NotABlockDump,
+    /// The dump is written in another version of the layout
+    #[error("unsupported block dump version {0}")]
+    UnsupportedVersion(u16),
+    /// The dump is truncated or corrupted
+    #[error("malformed block dump: {0}")]
+    Malformed(&'static str),
+    /// The block or a receipt of the dump can't be decoded
+    #[error("malformed block dump: {0}")]
+    Rlp(#[from] alloy_rlp::Error),
+}
+
+/// The entries of the hash map sorted by key.
+fn sorted<'a, K: Ord + 'a, V: 'a>(
+    map: impl IntoIterator<Item = (&'a K, &'a V)>,
+) -> Vec<(&'a K, &'a V)> {
+    let mut entries = map.into_iter().collect::<Vec<_>>();
+    entries.sort_unstable_by_key(|(key, _)| *key);
+    entries
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn fixed(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.fixed(&value.to_be_bytes());
+    }
+
+    fn u256(&mut self, value: U256) {
+        self.fixed(&value.to_be_bytes::<32>());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len() as u64);
+        self.fixed(bytes);
+    }
+
+    fn rlp(&mut self, value: &impl Encodable) {
+        let mut buf = Vec::with_capacity(value.length());
+        value.encode(&mut buf);
+        self.bytes(&buf);
+    }
+
+    fn option<T>(&mut self, value: Option<T>, f: impl FnOnce(&mut Self, T)) {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                f(self, value);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn list<T>(
+        &mut self,
+        items: impl ExactSizeIterator<Item = T>,
+        mut f: impl FnMut(&mut Self, T),
+    ) {
+        self.u64(items.len() as u64);
+        for item in items {
+            f(self, item);
+        }
+    }
+
+    fn account_status(&mut self, status: AccountStatus) {
+        let tag = ACCOUNT_STATUSES.iter().position(|known| *known == status);
+        self.u8(tag.expect("all account statuses are listed") as u8);
+    }
+
+    fn account_info(&mut self, info: &AccountInfo) {
+        self.u256(info.balance);
+        self.u64(info.nonce);
+        self.fixed(info.code_hash.as_slice());
+        self.option(info.code.as_ref(), |writer, code| writer.bytes(&code.original_bytes()));
+    }
+
+    fn bundle_account(&mut self, account: &BundleAccount) {
+        self.option(account.info.as_ref(), Self::account_info);
+        self.option(account.original_info.as_ref(), Self::account_info);
+        self.account_status(account.status);
+        self.list(sorted(&account.storage).into_iter(), |writer, (slot, value)| {
+            writer.u256(*slot);
+            writer.u256(value.previous_or_original_value);
+            writer.u256(value.present_value);
+        });
+    }
+
+    fn account_revert(&mut self, revert: &AccountRevert) {
+        match &revert.account {
+            AccountInfoRevert::DoNothing => self.u8(0),
+            AccountInfoRevert::DeleteIt => self.u8(1),
+            AccountInfoRevert::RevertTo(info) => {
+                self.u8(2);
+                self.account_info(info);
+            }
+        }
+        self.list(sorted(&revert.storage).into_iter(), |writer, (slot, value)| {
+            writer.u256(*slot);
+            match value {
+                RevertToSlot::Some(value) => {
+                    writer.u8(0);
+                    writer.u256(*value);
+                }
+                RevertToSlot::Destroyed => writer.u8(1),
+            }
+        });
+        self.account_status(revert.previous_status);
+        self.u8(u8::from(revert.wipe_storage));
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BlockDumpError> {
+        if self.0.len() < len {
+            return Err(BlockDumpError::Malformed("unexpected end of dump"));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], BlockDumpError> {
+        Ok(self.take(N)?.try_into().expect("taken N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, BlockDumpError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, BlockDumpError> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    fn u256(&mut self) -> Result<U256, BlockDumpError> {
+        Ok(U256::from_be_bytes(self.array::<32>()?))
+    }
+
+    fn address(&mut self) -> Result<Address, BlockDumpError> {
+        Ok(Address::new(self.array()?))
+    }
+
+    fn b256(&mut self) -> Result<B256, BlockDumpError> {
+        Ok(B256::new(self.array()?))
+    }
+
+    fn len(&mut self) -> Result<usize, BlockDumpError> {
+        usize::try_from(self.u64()?).map_err(|_| BlockDumpError::Malformed("length overflow"))
+    }
+
+    fn bytes(&mut self) -> Result<Bytes, BlockDumpError> {
+        let len = self.len()?;
+        Ok(Bytes::copy_from_slice(self.take(len)?))
+    }
+
+    fn rlp<T: Decodable>(&mut self) -> Result<T, BlockDumpError> {
+        let len = self.len()?;
+        let mut buf = self.take(len)?;
+        let value = T::decode(&mut buf)?;
+        if !buf.is_empty() {
+            return Err(BlockDumpError::Malformed("trailing bytes in RLP item"));
+        }
+        Ok(value)
+    }
+
+    fn option<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, BlockDumpError>,
+    ) -> Result<Option<T>, BlockDumpError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => f(self).map(Some),
+            _ => Err(BlockDumpError::Malformed("invalid option tag")),
+        }
+    }
+
+    /// Without preallocating, so that a corrupted length fails on the end of the dump rather than
+    /// on allocation.
+    fn list<T>(
+        &mut self,
+        mut f: impl FnMut(&mut Self) -> Result<T, BlockDumpError>,
+    ) -> Result<Vec<T>, BlockDumpError> {
+        let len = self.u64()?;
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(f(self)?);
+        }
+        Ok(items)
+    }
+
+    fn account_status(&mut self) -> Result<AccountStatus, BlockDumpError> {
+        ACCOUNT_STATUSES
+            .get(self.u8()? as usize)
+            .copied()
+            .ok_or(BlockDumpError::Malformed("invalid account status"))
+    }
+
+    /// The empty code is decoded as `Bytecode::new`, which the accounts without code carry,
+    /// rather than analyzed again.
+    fn bytecode(&mut self) -> Result<Bytecode, BlockDumpError> {
+        let bytes = self.bytes()?;
+        if bytes.is_empty() {
+            return Ok(Bytecode::new());
+        }
+        Bytecode::new_raw_checked(bytes).map_err(|_| BlockDumpError::Malformed("invalid bytecode"))
+    }
+
+    fn account_info(&mut self) -> Result<AccountInfo, BlockDumpError> {
+        Ok(AccountInfo {
+            balance: self.u256()?,
+            nonce: self.u64()?,
+            code_hash: self.b256()?,
+            code: self.option(Self::bytecode)?,
+        })
+    }
+
+    fn bundle_account(&mut self) -> Result<BundleAccount, BlockDumpError> {
+        Ok(BundleAccount {
+            info: self.option(Self::account_info)?,
+            original_info: self.option(Self::account_info)?,
+            status: self.account_status()?,
+            storage: self
+                .list(|reader| {
+                    let slot = reader.u256()?;
+                    let previous_or_original_value = reader.u256()?;
+                    let present_value = reader.u256()?;
+                    Ok((slot, StorageSlot { previous_or_original_value, present_value }))
+                })?
+                .into_iter()
+                .collect(),
+        })
+    }
+
+    fn account_revert(&mut self) -> Result<AccountRevert, BlockDumpError> {
+        let account = match self.u8()? {
+            0 => AccountInfoRevert::DoNothing,
+            1 => AccountInfoRevert::DeleteIt,
+            2 => AccountInfoRevert::RevertTo(self.account_info()?),
+            _ => return Err(BlockDumpError::Malformed("invalid account revert")),
+        };
+        let storage = self.list(|reader| {
+            let slot = reader.u256()?;
+            let value = match reader.u8()? {
+                0 => RevertToSlot::Some(reader.u256()?),
+                1 => RevertToSlot::Destroyed,
+                _ => return Err(BlockDumpError::Malformed("invalid slot revert")),
+            };
+            Ok((slot, value))
+        })?;
+        let previous_status = self.account_status()?;
+        let wipe_storage = match self.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(BlockDumpError::Malformed("invalid wipe storage flag")),
+        };
+        Ok(AccountRevert {
+            account,
+            storage: storage.into_iter().collect(),
+            previous_status,
+            wipe_storage,
+        })
+    }
+}
@@ -0,0 +1,193 @@
+use crate::PipeExecLayerError;
+use alloy_primitives::B256;
+use std::{collections::HashMap, sync::Mutex};
+use tokio::sync::{oneshot, watch};
+
+#[derive(Debug)]
+struct Cancellation {
+    /// Turns `true` once the block is cancelled
+    cancelled_tx: watch::Sender<bool>,
+    /// Notified once the pipeline has rolled back to the parent of the cancelled block
+    rolled_back_txs: Vec<oneshot::Sender<()>>,
+}
+
+impl Default for Cancellation {
+    fn default() -> Self {
+        Self { cancelled_tx: watch::channel(false).0, rolled_back_txs: vec![] }
+    }
+}
+
+impl Cancellation {
+    fn is_cancelled(&self) -> bool {
+        *self.cancelled_tx.borrow()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancellations: HashMap<B256, Cancellation>,
+    closed: bool,
+}
+
+/// Cancellations of the blocks in flight, keyed by block id, through which Coordinator cancels a
+/// block it has learned to be invalid. A block may be cancelled before it's processed, in which
+/// case it's discarded at its first stage boundary. The entries of the blocks not processed yet
+/// are dropped on rollback, since the blocks they belong to are discarded anyway.
+#[derive(Debug, Default)]
+pub(crate) struct Cancellations {
+    inner: Mutex<Inner>,
+}
+
+impl Cancellations {
+    /// Register the block being processed. Its entry is removed once the token is dropped.
+    pub(crate) fn token(&self, block_id: B256) -> CancellationToken<'_> {
+        let cancelled_rx = self
+            .inner
+            .lock()
+            .unwrap()
+            .cancellations
+            .entry(block_id)
+            .or_default()
+            .cancelled_tx
+            .subscribe();
+        CancellationToken { cancellations: self, block_id, cancelled_rx }
+    }
+
+    /// Cancel the block, unless `committed` tells that it's too late, which is called under the
+    /// same lock as the closure of `CancellationToken::settle`.
+    pub(crate) fn cancel(
+        &self,
+        block_id: B256,
+        rolled_back_tx: oneshot::Sender<()>,
+        committed: impl FnOnce() -> bool,
+    ) -> Result<(), PipeExecLayerError> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return Err(PipeExecLayerError::Closed);
+        }
+        if committed() {
+            return Err(PipeExecLayerError::NotCancellable(block_id));
+        }
+        let cancellation = inner.cancellations.entry(block_id).or_default();
+        cancellation.cancelled_tx.send_replace(true);
+        cancellation.rolled_back_txs.push(rolled_back_tx);
+        Ok(())
+    }
+
+    /// Drop all the entries, whose waiters in `PipeExecLayerApi::cancel` get
+    /// `PipeExecLayerError::Discarded`.
+    pub(crate) fn clear(&self) {
+        self.inner.lock().unwrap().cancellations.clear();
+    }
+
+    /// Drop all the entries and refuse further cancellations, once the pipeline is closed.
+    pub(crate) fn close(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.closed = true;
+        inner.cancellations.clear();
+    }
+}
+
+/// Cancellation token of a block being processed, checked at each stage boundary.
+#[derive(Debug)]
+pub(crate) struct CancellationToken<'a> {
+    cancellations: &'a Cancellations,
+    block_id: B256,
+    cancelled_rx: watch::Receiver<bool>,
+}
+
+impl CancellationToken<'_> {
+    /// Take the senders to notify once rolled back, if the block has been cancelled.
+    pub(crate) fn take_cancelled(&self) -> Option<Vec<oneshot::Sender<()>>> {
+        let cancellations = &mut self.cancellations.inner.lock().unwrap().cancellations;
+        if !cancellations.get(&self.block_id).is_some_and(Cancellation::is_cancelled) {
+            return None;
+        }
+        cancellations.remove(&self.block_id).map(|cancellation| cancellation.rolled_back_txs)
+    }
+
+    /// Resolve once the block is cancelled. Never resolves if the entry of the block has been
+    /// dropped, i.e. the block has been discarded by a rollback.
+    pub(crate) async fn cancelled(&mut self) {
+        if self.cancelled_rx.wait_for(|cancelled| *cancelled).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Remove the entry of the block once it can no longer be cancelled, running `commit` under
+    /// the lock so that a concurrent `Cancellations::cancel` sees its effect. Returns the senders
+    /// to notify instead if the block has been cancelled meanwhile, in which case `commit` is not
+    /// run.
+    pub(crate) fn settle(&self, commit: impl FnOnce()) -> Result<(), Vec<oneshot::Sender<()>>> {
+        let mut inner = self.cancellations.inner.lock().unwrap();
+        match inner.cancellations.remove(&self.block_id) {
+            Some(cancellation) if cancellation.is_cancelled() => Err(cancellation.rolled_back_txs),
+            _ => {
+                commit();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for CancellationToken<'_> {
+    /// A block discarded before being settled drops its entry, and the senders along with it.
+    fn drop(&mut self) {
+        self.cancellations.inner.lock().unwrap().cancellations.remove(&self.block_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_cancel_before_settle() {
+        let cancellations = Cancellations::default();
+        let block_id = B256::with_last_byte(1);
+        let token = cancellations.token(block_id);
+        assert!(token.take_cancelled().is_none());
+
+        let (rolled_back_tx, mut rolled_back_rx) = oneshot::channel();
+        cancellations.cancel(block_id, rolled_back_tx, || false).unwrap();
+        let rolled_back_txs = token.settle(|| panic!("cancelled block committed")).unwrap_err();
+        assert_eq!(rolled_back_txs.len(), 1);
+        assert!(rolled_back_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_cancel_after_settle() {
+        let cancellations = Cancellations::default();
+        let block_id = B256::with_last_byte(1);
+        let committed = AtomicBool::new(false);
+        let token = cancellations.token(block_id);
+        token.settle(|| committed.store(true, Ordering::Relaxed)).unwrap();
+
+        let (rolled_back_tx, _) = oneshot::channel();
+        assert_eq!(
+            cancellations
+                .cancel(block_id, rolled_back_tx, || { committed.load(Ordering::Relaxed) }),
+            Err(PipeExecLayerError::NotCancellable(block_id))
+        );
+    }
+
+    #[test]
+    fn test_dropped_token() {
+        let cancellations = Cancellations::default();
+        let block_id = B256::with_last_byte(1);
+        let token = cancellations.token(block_id);
+        let (rolled_back_tx, rolled_back_rx) = oneshot::channel();
+        cancellations.cancel(block_id, rolled_back_tx, || false).unwrap();
+        // The block is discarded without reaching a stage boundary
+        drop(token);
+        assert!(rolled_back_rx.blocking_recv().is_err());
+
+        cancellations.close();
+        let (rolled_back_tx, _) = oneshot::channel();
+        assert_eq!(
+            cancellations.cancel(block_id, rolled_back_tx, || false),
+            Err(PipeExecLayerError::Closed)
+        );
+    }
+}
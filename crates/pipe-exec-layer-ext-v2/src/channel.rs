@@ -1,8 +1,5 @@
 use std::{
-    collections::HashMap,
-    fmt::Debug,
-    hash::Hash,
-    sync::{Mutex, MutexGuard},
+    collections::HashMap, fmt::Debug, future::Future, hash::Hash, sync::Mutex, time::Duration,
 };
 
 use tokio::sync::oneshot;
@@ -18,19 +15,56 @@ enum State<V> {
     Notified(V),
 }
 
+/// Error returned by [`Channel::wait_timeout`] if the key is not notified before the deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChannelTimeout;
+
+#[derive(Debug)]
+enum WaitState<V> {
+    Ready(Option<V>),
+    Pending(oneshot::Receiver<V>),
+}
+
 #[derive(Debug)]
 struct Inner<K, V> {
     states: HashMap<K, State<V>>,
+    /// Waiters registered by `subscribe`, which are woken by `notify_all` in addition to the
+    /// single consumer of each key
+    subscribers: HashMap<K, Vec<oneshot::Sender<V>>>,
     closed: bool,
 }
 
+impl<K: Eq + Debug + Hash, V> Inner<K, V> {
+    fn notify(&mut self, key: K, val: V) {
+        let state = self.states.remove(&key);
+        match state {
+            Some(State::Waiting(tx)) => {
+                let _ = tx.send(val);
+            }
+            Some(State::Notified(_)) => {
+                panic!("unexpected state: {:?}", key);
+            }
+            None => {
+                self.states.insert(key, State::Notified(val));
+            }
+        }
+    }
+}
+
 impl<K: Eq + Clone + Debug + Hash, V> Channel<K, V> {
     pub(crate) fn new() -> Self {
-        Self { inner: Mutex::new(Inner { states: HashMap::new(), closed: false }) }
+        Self {
+            inner: Mutex::new(Inner {
+                states: HashMap::new(),
+                subscribers: HashMap::new(),
+                closed: false,
+            }),
+        }
     }
 
     pub(crate) fn new_with_states<I: IntoIterator<Item = (K, V)>>(states: I) -> Self {
-        let mut inner = Inner { states: HashMap::new(), closed: false };
+        let mut inner =
+            Inner { states: HashMap::new(), subscribers: HashMap::new(), closed: false };
         for (k, v) in states {
             inner.states.insert(k, State::Notified(v));
         }
@@ -39,60 +73,175 @@ impl<K: Eq + Clone + Debug + Hash, V> Channel<K, V> {
 
     /// Wait until the key is notified.
     /// Returns `None` if the barrier has been closed.
-    pub(crate) async fn wait(&self, key: K) -> Option<V> {
-        // ATTN: We can guarantee that `.await` will not occur within the critical zone, which means
-        // `MutexGuard` will not be sent across threads.
-        struct SendMutexGuard<'a, T>(MutexGuard<'a, T>);
-        unsafe impl<'a, T> Send for SendMutexGuard<'a, T> {}
-
-        let mut inner = SendMutexGuard(self.inner.lock().unwrap());
-        if inner.0.closed {
-            return None;
+    /// The waiter is registered when this is called rather than when the future is first polled.
+    pub(crate) fn wait(&self, key: K) -> impl Future<Output = Option<V>> {
+        let state = self.register(key);
+        async move {
+            match state {
+                WaitState::Ready(v) => v,
+                WaitState::Pending(rx) => rx.await.ok(),
+            }
         }
+    }
+
+    /// Wait until the key is notified or the timeout elapses.
+    /// Returns `Ok(None)` if the barrier has been closed, or `Err(ChannelTimeout)` if the key is
+    /// not notified in time. It's safe to wait for the same key again after a timeout.
+    /// The waiter is registered when this is called rather than when the future is first polled.
+    pub(crate) fn wait_timeout(
+        &self,
+        key: K,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Option<V>, ChannelTimeout>> + '_ {
+        let state = self.register(key.clone());
+        async move {
+            let mut rx = match state {
+                WaitState::Ready(v) => return Ok(v),
+                WaitState::Pending(rx) => rx,
+            };
+            if let Ok(v) = tokio::time::timeout(timeout, &mut rx).await {
+                return Ok(v.ok());
+            }
 
-        let state = inner.0.states.remove(&key);
+            let mut inner = self.inner.lock().unwrap();
+            match inner.states.get(&key) {
+                Some(State::Waiting(_)) => {
+                    // Unregister the waiter so that the value will be kept until the next wait
+                    inner.states.remove(&key);
+                    Err(ChannelTimeout)
+                }
+                // The key has been notified (or the channel has been closed) right after the
+                // timeout
+                _ => Ok(rx.try_recv().ok()),
+            }
+        }
+    }
+
+    /// Take the notified value of the key, or register a waiter for it.
+    fn register(&self, key: K) -> WaitState<V> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return WaitState::Ready(None);
+        }
+
+        let state = inner.states.remove(&key);
         match state {
-            Some(State::Notified(v)) => Some(v),
+            Some(State::Notified(v)) => WaitState::Ready(Some(v)),
             Some(State::Waiting(_)) => {
                 panic!("unexpected state: {:?}", key);
             }
             None => {
                 let (tx, rx) = oneshot::channel();
-                inner.0.states.insert(key, State::Waiting(tx));
-                drop(inner);
-
-                rx.await.ok()
+                inner.states.insert(key, State::Waiting(tx));
+                WaitState::Pending(rx)
             }
         }
     }
 
     /// Notify the key with the value.
     /// Returns `None` if the barrier has been closed.
+    /// Subscribers of the key are not woken, see `notify_all`.
     pub(crate) fn notify(&self, key: K, val: V) -> Option<()> {
         let mut inner = self.inner.lock().unwrap();
         if inner.closed {
             return None;
         }
 
-        let state = inner.states.remove(&key);
-        match state {
+        inner.notify(key, val);
+        Some(())
+    }
+
+    /// Notify the key with the value again, e.g. in case the previous value was missed by the
+    /// receiver. Unlike `notify`, the value not yet taken is replaced instead of panicking.
+    /// Returns `None` if the barrier has been closed.
+    pub(crate) fn renotify(&self, key: K, val: V) -> Option<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return None;
+        }
+
+        match inner.states.remove(&key) {
             Some(State::Waiting(tx)) => {
                 let _ = tx.send(val);
             }
-            Some(State::Notified(_)) => {
-                panic!("unexpected state: {:?}", key);
-            }
-            None => {
+            Some(State::Notified(_)) | None => {
                 inner.states.insert(key, State::Notified(val));
             }
         }
         Some(())
     }
 
+    /// Remove the states of the keys not satisfying the predicate.
+    /// Waiters and subscribers of the removed keys will get `None`.
+    pub(crate) fn retain(&self, mut f: impl FnMut(&K) -> bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.states.retain(|k, _| f(k));
+        inner.subscribers.retain(|k, _| f(k));
+    }
+
+    /// Number of keys holding a value not yet taken, a waiter or subscribers. A key is removed
+    /// once its value is taken by `wait`, so for keys which are each notified and waited once,
+    /// this stays bounded by the number of keys in flight.
+    pub(crate) fn pending_keys(&self) -> usize {
+        let inner = self.inner.lock().unwrap();
+        inner.states.len() +
+            inner.subscribers.keys().filter(|k| !inner.states.contains_key(k)).count()
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.inner.lock().unwrap().closed
+    }
+
     pub(crate) fn close(&self) {
         let mut inner = self.inner.lock().unwrap();
         inner.closed = true;
         inner.states.clear();
+        inner.subscribers.clear();
+    }
+}
+
+impl<K: Eq + Clone + Debug + Hash, V: Clone> Channel<K, V> {
+    /// Wait until the key is notified by `notify_all`, without taking the value from the single
+    /// consumer waiting with `wait`. Any number of subscribers may wait for the same key.
+    /// Returns `None` if the barrier has been closed.
+    /// If the key has been notified but its value not yet taken, a copy is returned immediately.
+    /// Otherwise the subscriber waits for the next `notify_all`, so it should subscribe before
+    /// the key is notified.
+    pub(crate) fn subscribe(&self, key: K) -> impl Future<Output = Option<V>> {
+        let state = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.closed {
+                WaitState::Ready(None)
+            } else if let Some(State::Notified(v)) = inner.states.get(&key) {
+                WaitState::Ready(Some(v.clone()))
+            } else {
+                let (tx, rx) = oneshot::channel();
+                inner.subscribers.entry(key).or_default().push(tx);
+                WaitState::Pending(rx)
+            }
+        };
+        async move {
+            match state {
+                WaitState::Ready(v) => v,
+                WaitState::Pending(rx) => rx.await.ok(),
+            }
+        }
+    }
+
+    /// Notify the key with the value like `notify`, and broadcast a copy of the value to all the
+    /// subscribers of the key.
+    /// Returns `None` if the barrier has been closed.
+    pub(crate) fn notify_all(&self, key: K, val: V) -> Option<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return None;
+        }
+
+        for tx in inner.subscribers.remove(&key).unwrap_or_default() {
+            let _ = tx.send(val.clone());
+        }
+        inner.notify(key, val);
+        Some(())
     }
 }
 
@@ -120,4 +269,99 @@ mod test {
 
         tasks.join_all().await;
     }
+
+    #[tokio::test]
+    async fn test_wait_timeout() {
+        let barrier = Arc::new(super::Channel::new());
+        let timeout = std::time::Duration::from_millis(100);
+
+        assert_eq!(barrier.wait_timeout(1, timeout).await, Err(super::ChannelTimeout));
+
+        // Waiting for the same key again after a timeout must not lose the value
+        let notifier = {
+            let barrier = barrier.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+                barrier.notify(1, 42).unwrap();
+            })
+        };
+        assert_eq!(barrier.wait_timeout(1, timeout).await, Err(super::ChannelTimeout));
+        notifier.await.unwrap();
+        assert_eq!(barrier.wait_timeout(1, timeout).await, Ok(Some(42)));
+
+        barrier.close();
+        assert_eq!(barrier.wait_timeout(2, timeout).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_retain() {
+        let barrier = Arc::new(super::Channel::new_with_states([(0, 0), (1, 1)]));
+        let waiter = {
+            let barrier = barrier.clone();
+            tokio::spawn(async move { barrier.wait(2).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        barrier.retain(|k| *k == 0);
+        assert_eq!(waiter.await.unwrap(), None);
+        assert_eq!(barrier.wait(0).await, Some(0));
+        barrier.notify(1, 10).unwrap();
+        assert_eq!(barrier.wait(1).await, Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_notify_all() {
+        let barrier = Arc::new(super::Channel::new());
+
+        let mut tasks = JoinSet::new();
+        for _ in 0..4 {
+            let subscriber = barrier.subscribe(1);
+            tasks.spawn(subscriber);
+        }
+        let waiter = {
+            let barrier = barrier.clone();
+            tokio::spawn(async move { barrier.wait(1).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        barrier.notify_all(1, 10).unwrap();
+        assert_eq!(tasks.join_all().await, vec![Some(10); 4]);
+        assert_eq!(waiter.await.unwrap(), Some(10));
+
+        // A subscriber gets a copy of a value not yet taken, and leaves it for the consumer
+        barrier.notify_all(2, 20).unwrap();
+        assert_eq!(barrier.subscribe(2).await, Some(20));
+        assert_eq!(barrier.wait(2).await, Some(20));
+
+        let subscriber = barrier.subscribe(3);
+        barrier.close();
+        assert!(barrier.is_closed());
+        assert_eq!(subscriber.await, None);
+    }
+
+    #[tokio::test]
+    async fn test_pending_keys() {
+        let barrier = Arc::new(super::Channel::new());
+
+        // Notified before waited
+        barrier.notify(1, 1).unwrap();
+        assert_eq!(barrier.pending_keys(), 1);
+        assert_eq!(barrier.wait(1).await, Some(1));
+        assert_eq!(barrier.pending_keys(), 0);
+
+        // Waited before notified
+        let waiter = barrier.wait(2);
+        assert_eq!(barrier.pending_keys(), 1);
+        barrier.notify(2, 2).unwrap();
+        assert_eq!(waiter.await, Some(2));
+        assert_eq!(barrier.pending_keys(), 0);
+
+        // Subscribers are removed once notified, along with the value taken by the consumer
+        let subscriber = barrier.subscribe(3);
+        assert_eq!(barrier.pending_keys(), 1);
+        barrier.notify_all(3, 3).unwrap();
+        assert_eq!(subscriber.await, Some(3));
+        assert_eq!(barrier.wait(3).await, Some(3));
+        assert_eq!(barrier.pending_keys(), 0);
+    }
 }
@@ -0,0 +1,827 @@
+use crate::{
+    AcceptAnyPrevRandao, MetricsSink, ParentBeaconBlockRoot, ParentBlockIdAsBeaconRoot,
+    PrevRandaoValidator, TxTraceSink,
+};
+use alloy_eips::merge::BEACON_NONCE;
+use alloy_primitives::{map::HashSet, Address, U256};
+use reth_chainspec::{ChainSpec, EthereumHardfork, EthereumHardforks, ForkCondition};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+/// Default gas limit of each block.
+pub const DEFAULT_BLOCK_GAS_LIMIT: u64 = 1_000_000_000;
+
+/// Default number of ordered blocks queued for execution before Coordinator is throttled.
+///
+/// A queued block holds its transactions in memory. Calldata costs at least 4 gas per byte, so a
+/// 1G-gas block carries at most ~250 MB of transactions, and the queue holds at most ~2 GB with
+/// this capacity. Real blocks are far smaller, so it's typically a few MB.
+pub const DEFAULT_ORDERED_BLOCK_CHANNEL_CAPACITY: usize = 8;
+
+/// Default number of blocks processed by the pipeline at the same time, i.e. a few blocks for each
+/// of the five stages (execute, merklize, seal, verify and make canonical).
+pub const DEFAULT_MAX_IN_FLIGHT_BLOCKS: usize = 16;
+
+/// Default interval between two adjacent blocks starting execution, beyond which the later one
+/// is reported as slow. Blocks are produced well within a second on a healthy chain.
+pub const DEFAULT_SLOW_BLOCK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Default number of ordered blocks received ahead of their turn which are buffered.
+pub const DEFAULT_REORDER_BUFFER_CAPACITY: usize = 16;
+
+/// Default number of times an executed block hash is pushed to Coordinator before the
+/// verification is considered timed out.
+pub const DEFAULT_VERIFY_ATTEMPTS: usize = 3;
+
+/// Default number of recently committed blocks whose stage timings are kept.
+pub const DEFAULT_STAGE_TIMINGS_CAPACITY: usize = 256;
+
+/// Default number of canonical blocks below the head which the pipeline can roll back to on a
+/// reorg.
+pub const DEFAULT_MAX_REORG_DEPTH: usize = 64;
+
+/// Default number of canonical blocks below the head whose bundle states are kept in storage,
+/// i.e. all the blocks a reorg of the default depth can roll back to.
+pub const DEFAULT_BUNDLE_STATE_RETENTION: usize = DEFAULT_MAX_REORG_DEPTH;
+
+/// Default number of recently executed blocks whose hashes are cached for the blocks pushed again.
+pub const DEFAULT_EXECUTED_BLOCK_CACHE_CAPACITY: usize = 32;
+
+/// Default number of recently committed blocks whose execution outcomes are cached.
+///
+/// An execution outcome holds the receipts and the bundle state of the block, whose size grows
+/// with the touched accounts and slots, e.g. a few MB for a block full of token transfers.
+pub const DEFAULT_EXECUTION_OUTCOME_CACHE_CAPACITY: usize = 16;
+
+/// Default number of recently committed blocks whose status is kept for
+/// `PipeExecLayerApi::status`.
+pub const DEFAULT_BLOCK_STATUS_CAPACITY: usize = 256;
+
+/// Default number of consecutive execution failures after which the pipeline halts, i.e. the
+/// first failure halts it, since a deterministic failure would recur however often the block is
+/// delivered again.
+pub const DEFAULT_MAX_CONSECUTIVE_EXECUTION_FAILURES: usize = 1;
+
+/// Default number of canonical block notifications buffered for each subscriber.
+pub const DEFAULT_CANONICAL_NOTIFICATION_CAPACITY: usize = 64;
+
+/// Default number of transactions in a block below which they are filtered sequentially.
+///
+/// Checking the transactions of a sender takes an account read from the state view plus a few
+/// arithmetic checks, a few microseconds in total when the account is cached, while splitting
+/// the senders across the rayon pool and collecting the results back costs a few tens of
+/// microseconds. Parallel filtering pays off once a block carries some dozens of transactions.
+pub const DEFAULT_PARALLEL_FILTER_THRESHOLD: usize = 64;
+
+/// How the ordered blocks with the zero address as coinbase are treated. The priority fees of
+/// such a block are credited to the zero address, i.e. burnt, which is usually a misconfigured
+/// Coordinator.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZeroCoinbasePolicy {
+    /// Execute the block silently
+    Allow,
+    /// Execute the block, logging a warning and counting it in the `zero_coinbase_blocks` metric
+    #[default]
+    Warn,
+    /// Reject the block in `PipeExecLayerApi::push_ordered_block`
+    Reject,
+}
+
+/// Configuration of the pipeline execution layer.
+#[derive(Debug, Clone)]
+pub struct PipeExecLayerConfig {
+    /// Gas limit of each executed block, which is written into the block header as is.
+    pub block_gas_limit: u64,
+    /// Difficulty written into each block header. Zero as required since the merge, other values
+    /// are only for chains without the merge, e.g. test harnesses of other consensus.
+    pub block_difficulty: U256,
+    /// Nonce written into each block header. `BEACON_NONCE` as required since the merge, other
+    /// values are only for chains without the merge like `block_difficulty`.
+    pub block_nonce: u64,
+    /// Base fee of every block since London in place of the one derived from the parent block,
+    /// e.g. for deterministic test vectors. The transactions are filtered and charged against the
+    /// same base fee as written into the header. `None` means the base fee follows EIP-1559.
+    pub base_fee_override: Option<u64>,
+    /// Recipient of the priority fees (and the block rewards before Paris) of every block in place
+    /// of its coinbase, e.g. a treasury. The EVM always pays the beneficiary of the header, so the
+    /// recipient is sealed as the beneficiary, and the coinbase of the ordered block is not
+    /// recorded in the block. The `COINBASE` opcode returns the recipient as well. The blocks
+    /// remain valid under the regular Ethereum validation, but all the nodes of the network must
+    /// set the same recipient, since the block hashes and state roots differ otherwise.
+    ///
+    /// `None` means the fees are paid to the coinbase.
+    pub fee_recipient_override: Option<Address>,
+    /// How long a stage waits for the previous block before reporting a stall. The stage keeps
+    /// waiting after reporting. `None` means waiting silently forever.
+    pub barrier_timeout: Option<Duration>,
+    /// How long to wait for Coordinator to verify an executed block hash before pushing it again.
+    /// `None` means waiting forever.
+    pub verify_timeout: Option<Duration>,
+    /// Number of times an executed block hash is pushed to Coordinator before the pipeline is
+    /// halted for verification timeout, if `verify_timeout` is set. Must be nonzero.
+    pub verify_attempts: usize,
+    /// Whether the pipeline verifies the executed block hashes itself instead of pushing them to
+    /// Coordinator, e.g. for a single-node dev setup without a separate Coordinator. Each block is
+    /// verified against its own computed hash and proceeds to be made canonical right away, so
+    /// `PipeExecLayerApi::pull_executed_block_hash` never returns and the hashes committed
+    /// through `PipeExecLayerApi::commit_executed_block_hash` are ignored.
+    pub single_node: bool,
+    /// Interval between two adjacent blocks starting execution beyond which the later block is
+    /// counted in `slow_block_interval_total` and logged, since it arrived late or its parent was
+    /// slow to execute. Must be nonzero.
+    pub slow_block_interval: Duration,
+    /// Number of ordered blocks queued for execution before `push_ordered_block` waits. Must be
+    /// nonzero.
+    pub ordered_block_channel_capacity: usize,
+    /// Number of ordered blocks received ahead of their turn which are buffered until the gap is
+    /// filled. Blocks ahead of their turn are discarded if the buffer is full.
+    pub reorder_buffer_capacity: usize,
+    /// Number of blocks processed by the pipeline stages at the same time. Each of them holds its
+    /// transactions and bundle state in memory, so the next block waits until one finishes, and
+    /// no more ordered blocks are received meanwhile. Must be nonzero.
+    pub max_in_flight_blocks: usize,
+    /// Number of blocks the make canonical stage may fall behind execution. A block more than
+    /// this ahead of the latest canonical block is held, and no more ordered blocks are received
+    /// meanwhile, so that a slow make canonical stage throttles execution instead of piling up
+    /// sealed blocks in memory. `None` means the lag is only bounded by `max_in_flight_blocks`.
+    /// Must be nonzero.
+    pub max_canonical_lag: Option<usize>,
+    /// Approximate bytes of the bundle states held by the blocks in flight beyond which the next
+    /// block is held, like when `max_in_flight_blocks` is reached, which bounds the memory by
+    /// bytes rather than by block count for blocks of varying sizes. A block is counted once
+    /// executed, so the block crossing the budget still completes, and a block larger than the
+    /// budget is processed alone. `None` means the memory is only bounded by
+    /// `max_in_flight_blocks`. Must be nonzero.
+    pub max_in_flight_bundle_state_bytes: Option<usize>,
+    /// Whether to read the accounts touched by an ordered block, i.e. the senders and the
+    /// recipients along with their code, in parallel before its execution, overlapping the I/O
+    /// with the execution and merklization of the previous blocks.
+    pub prefetch_state: bool,
+    /// Number of threads of the dedicated rayon pool used to filter and execute transactions.
+    /// `None` means using the global rayon pool, which is shared with the other rayon users.
+    ///
+    /// The pool threads are separate from the tokio worker threads, while the pipeline stages
+    /// block the tokio worker threads they run on until execution finishes. To avoid
+    /// oversubscription, the pool size plus the tokio worker threads should not exceed the
+    /// available cores by much.
+    pub executor_threads: Option<usize>,
+    /// Number of recently committed blocks whose stage timings are kept for querying. Zero
+    /// disables keeping them.
+    pub stage_timings_capacity: usize,
+    /// Directory into which the block which failed to execute is dumped, if
+    /// `PIPE_DUMP_FAILED_BLOCK` is set. `None` means the temporary directory of the system.
+    pub failed_block_dump_dir: Option<PathBuf>,
+    /// Number of consecutive blocks failing to execute after which the pipeline halts, stops
+    /// accepting ordered blocks and emits `PipeExecLayerEvent::Halted`. Below it, the pipeline
+    /// rolls back to the parent of the failed block, so that Coordinator can deliver the block
+    /// again, e.g. once a transient storage failure is over. A block executed successfully
+    /// resets the count. Must be nonzero.
+    pub max_consecutive_execution_failures: usize,
+    /// Number of canonical blocks below the head which the pipeline can roll back to, when
+    /// Coordinator delivers a block forking from one of them. Deeper reorgs are refused, and zero
+    /// disables reorgs.
+    pub max_reorg_depth: usize,
+    /// Number of canonical blocks below the head whose bundle states are kept in storage, the
+    /// older ones are pruned as blocks are made canonical. Must not be lower than
+    /// `max_reorg_depth`, so that the blocks a reorg rolls back to keep their states.
+    pub bundle_state_retention: usize,
+    /// Number of recently executed blocks whose hashes are cached, so that a block pushed again
+    /// by Coordinator with the same id gets its hash pushed again instead of being discarded as
+    /// already accepted. Zero disables the cache.
+    pub executed_block_cache_capacity: usize,
+    /// Number of recently committed blocks whose execution outcomes, i.e. the receipts and the
+    /// state changes, are cached for `PipeExecLayerApi::execution_outcome`. The oldest block is
+    /// evicted first, and the blocks rolled back by a reorg are discarded. Zero disables the
+    /// cache.
+    pub execution_outcome_cache_capacity: usize,
+    /// Number of recently committed blocks whose status is kept for `PipeExecLayerApi::status`,
+    /// beyond which the oldest ones are reported as `BlockStatus::Unknown`. The blocks in flight
+    /// are always tracked.
+    pub block_status_capacity: usize,
+    /// Whether to send a `PipeExecLayerEvent::StateDiff` with the net state changes of each block
+    /// made canonical. Nothing is collected if disabled.
+    pub emit_state_diff: bool,
+    /// Number of canonical block notifications buffered for the subscribers of
+    /// `PipeExecLayerExt::subscribe_canonical`. A subscriber lagging further behind misses the
+    /// oldest ones. Must be nonzero.
+    pub canonical_notification_capacity: usize,
+    /// Number of transactions in a block below which they are filtered sequentially on the
+    /// current thread, as the rayon overhead outweighs the parallelism for small blocks.
+    pub parallel_filter_threshold: usize,
+    /// Whether to always filter the transactions sequentially, e.g. for deterministic debugging.
+    /// The filtered transactions are the same either way.
+    pub sequential_filter: bool,
+    /// Whether to execute the transactions of each block sequentially instead of with Grevm,
+    /// which executes the transactions of a block in parallel, detects the ones hitting a
+    /// conflict and re-executes them, so that the resulting state is the same as sequential
+    /// execution. Sequential execution is for ruling out the parallel executor, e.g. when
+    /// debugging a state divergence. `EVM_DISABLE_GREVM` disables Grevm for the whole process.
+    pub sequential_execution: bool,
+    /// Maximum gas limit of a single transaction, e.g. 30M, beyond which the transaction is
+    /// discarded, so that a pathological transaction can't monopolize the execution of a block.
+    /// `None` means the transactions are only bounded by `block_gas_limit`. Must be nonzero.
+    pub max_tx_gas_limit: Option<u64>,
+    /// Maximum cumulative EIP-2718 encoded size in bytes of the transactions of a block, beyond
+    /// which the following transactions are discarded, so that calldata-heavy blocks don't
+    /// overwhelm the network and the storage. `None` means the blocks are only bounded by
+    /// `block_gas_limit`. Must be nonzero.
+    pub max_block_size: Option<usize>,
+    /// Maximum number of valid transactions of a sender in a block, beyond which the ones with the
+    /// highest nonces are discarded. `None` means unlimited. Must be nonzero.
+    pub max_txs_per_sender: Option<usize>,
+    /// Source of the `parent_beacon_block_root` of the blocks since Cancun, which is stored into
+    /// the beacon roots contract during execution. Defaults to `ParentBlockIdAsBeaconRoot`.
+    pub parent_beacon_block_root: Arc<dyn ParentBeaconBlockRoot>,
+    /// Check of the `prev_randao` of the ordered blocks, which are refused with
+    /// `InvalidPrevRandao` rather than executed if the check fails. Defaults to
+    /// `AcceptAnyPrevRandao`.
+    pub prev_randao_validator: Arc<dyn PrevRandaoValidator>,
+    /// Sink of the outcomes of the executed transactions, for debugging. `None` means nothing is
+    /// traced.
+    pub tx_trace_sink: Option<Arc<dyn TxTraceSink>>,
+    /// Backend of the pipeline metrics. `None` means the global recorder of `reth_metrics`.
+    pub metrics_sink: Option<Arc<dyn MetricsSink>>,
+    /// Number of the latest canonical blocks replayed on startup, on top of the historical states
+    /// of their parents read from `GravityStorage`. The pipeline refuses to start if a replayed
+    /// block doesn't hash to the stored one, so that a storage corrupted by a crash is caught
+    /// before any new block is executed on top of it. The storage must provide the canonical
+    /// blocks and the historical states, and the block ids of the replayed blocks and their
+    /// parent must be passed in `ExecutionArgs`. Zero disables the replay.
+    pub startup_replay_blocks: u64,
+    /// Whether `PipeExecLayerApi::force_make_canonical` may bypass the verification by
+    /// Coordinator, for disaster recovery only. Off by default.
+    pub allow_force_canonical: bool,
+    /// Senders whose transactions are all discarded by the filter, e.g. for compliance or
+    /// incident response. A presumed valid block carrying a transaction of a denied sender is
+    /// filtered as well. `None` disables the denylist, including its updates through
+    /// `PipeExecLayerApi::set_sender_denylist`.
+    pub sender_denylist: Option<HashSet<Address>>,
+    /// How the ordered blocks with the zero address as coinbase are treated. Defaults to
+    /// `ZeroCoinbasePolicy::Warn`.
+    pub zero_coinbase: ZeroCoinbasePolicy,
+}
+
+impl Default for PipeExecLayerConfig {
+    fn default() -> Self {
+        Self {
+            block_gas_limit: DEFAULT_BLOCK_GAS_LIMIT,
+            block_difficulty: U256::ZERO,
+            block_nonce: BEACON_NONCE,
+            base_fee_override: None,
+            fee_recipient_override: None,
+            barrier_timeout: None,
+            verify_timeout: None,
+            verify_attempts: DEFAULT_VERIFY_ATTEMPTS,
+            single_node: false,
+            slow_block_interval: DEFAULT_SLOW_BLOCK_INTERVAL,
+            prefetch_state: false,
+            executor_threads: None,
+            ordered_block_channel_capacity: DEFAULT_ORDERED_BLOCK_CHANNEL_CAPACITY,
+            reorder_buffer_capacity: DEFAULT_REORDER_BUFFER_CAPACITY,
+            max_in_flight_blocks: DEFAULT_MAX_IN_FLIGHT_BLOCKS,
+            max_canonical_lag: None,
+            max_in_flight_bundle_state_bytes: None,
+            stage_timings_capacity: DEFAULT_STAGE_TIMINGS_CAPACITY,
+            failed_block_dump_dir: None,
+            max_consecutive_execution_failures: DEFAULT_MAX_CONSECUTIVE_EXECUTION_FAILURES,
+            max_reorg_depth: DEFAULT_MAX_REORG_DEPTH,
+            bundle_state_retention: DEFAULT_BUNDLE_STATE_RETENTION,
+            executed_block_cache_capacity: DEFAULT_EXECUTED_BLOCK_CACHE_CAPACITY,
+            execution_outcome_cache_capacity: DEFAULT_EXECUTION_OUTCOME_CACHE_CAPACITY,
+            block_status_capacity: DEFAULT_BLOCK_STATUS_CAPACITY,
+            emit_state_diff: false,
+            canonical_notification_capacity: DEFAULT_CANONICAL_NOTIFICATION_CAPACITY,
+            parallel_filter_threshold: DEFAULT_PARALLEL_FILTER_THRESHOLD,
+            sequential_filter: false,
+            sequential_execution: false,
+            max_tx_gas_limit: None,
+            max_block_size: None,
+            max_txs_per_sender: None,
+            parent_beacon_block_root: Arc::new(ParentBlockIdAsBeaconRoot),
+            prev_randao_validator: Arc::new(AcceptAnyPrevRandao),
+            tx_trace_sink: None,
+            metrics_sink: None,
+            startup_replay_blocks: 0,
+            allow_force_canonical: false,
+            sender_denylist: None,
+            zero_coinbase: ZeroCoinbasePolicy::Warn,
+        }
+    }
+}
+
+impl PipeExecLayerConfig {
+    /// Set the gas limit of each executed block.
+    pub const fn with_block_gas_limit(mut self, block_gas_limit: u64) -> Self {
+        self.block_gas_limit = block_gas_limit;
+        self
+    }
+
+    /// Set the difficulty of each executed block, for chains without the merge.
+    pub const fn with_block_difficulty(mut self, block_difficulty: U256) -> Self {
+        self.block_difficulty = block_difficulty;
+        self
+    }
+
+    /// Set the nonce of each executed block, for chains without the merge.
+    pub const fn with_block_nonce(mut self, block_nonce: u64) -> Self {
+        self.block_nonce = block_nonce;
+        self
+    }
+
+    /// Pin the base fee of every block since London.
+    pub const fn with_base_fee_override(mut self, base_fee: u64) -> Self {
+        self.base_fee_override = Some(base_fee);
+        self
+    }
+
+    /// Pay the priority fees of every block to `fee_recipient` instead of its coinbase.
+    pub const fn with_fee_recipient_override(mut self, fee_recipient: Address) -> Self {
+        self.fee_recipient_override = Some(fee_recipient);
+        self
+    }
+
+    /// Set the deadline of waiting for the previous block in each stage.
+    pub const fn with_barrier_timeout(mut self, barrier_timeout: Duration) -> Self {
+        self.barrier_timeout = Some(barrier_timeout);
+        self
+    }
+
+    /// Set the deadline of waiting for Coordinator to verify an executed block hash.
+    pub const fn with_verify_timeout(mut self, verify_timeout: Duration) -> Self {
+        self.verify_timeout = Some(verify_timeout);
+        self
+    }
+
+    /// Set the interval between two adjacent blocks starting execution beyond which the later
+    /// block is reported as slow.
+    pub const fn with_slow_block_interval(mut self, slow_block_interval: Duration) -> Self {
+        self.slow_block_interval = slow_block_interval;
+        self
+    }
+
+    /// Set the number of times an executed block hash is pushed to Coordinator.
+    pub const fn with_verify_attempts(mut self, verify_attempts: usize) -> Self {
+        self.verify_attempts = verify_attempts;
+        self
+    }
+
+    /// Set whether the pipeline verifies the executed block hashes itself.
+    pub const fn with_single_node(mut self, single_node: bool) -> Self {
+        self.single_node = single_node;
+        self
+    }
+
+    /// Set whether to prefetch the accounts touched by an ordered block before its execution.
+    pub const fn with_prefetch_state(mut self, prefetch_state: bool) -> Self {
+        self.prefetch_state = prefetch_state;
+        self
+    }
+
+    /// Set the number of threads of the dedicated executor thread pool.
+    pub const fn with_executor_threads(mut self, executor_threads: usize) -> Self {
+        self.executor_threads = Some(executor_threads);
+        self
+    }
+
+    /// Set the number of ordered blocks queued for execution.
+    pub const fn with_ordered_block_channel_capacity(
+        mut self,
+        ordered_block_channel_capacity: usize,
+    ) -> Self {
+        self.ordered_block_channel_capacity = ordered_block_channel_capacity;
+        self
+    }
+
+    /// Set the number of ordered blocks received ahead of their turn which are buffered.
+    pub const fn with_reorder_buffer_capacity(mut self, reorder_buffer_capacity: usize) -> Self {
+        self.reorder_buffer_capacity = reorder_buffer_capacity;
+        self
+    }
+
+    /// Set the number of blocks processed by the pipeline stages at the same time.
+    pub const fn with_max_in_flight_blocks(mut self, max_in_flight_blocks: usize) -> Self {
+        self.max_in_flight_blocks = max_in_flight_blocks;
+        self
+    }
+
+    /// Set the number of blocks the make canonical stage may fall behind execution.
+    pub const fn with_max_canonical_lag(mut self, max_canonical_lag: usize) -> Self {
+        self.max_canonical_lag = Some(max_canonical_lag);
+        self
+    }
+
+    /// Set the approximate bytes of the bundle states held by the blocks in flight.
+    pub const fn with_max_in_flight_bundle_state_bytes(mut self, bytes: usize) -> Self {
+        self.max_in_flight_bundle_state_bytes = Some(bytes);
+        self
+    }
+
+    /// Set the number of recently committed blocks whose stage timings are kept.
+    pub const fn with_stage_timings_capacity(mut self, stage_timings_capacity: usize) -> Self {
+        self.stage_timings_capacity = stage_timings_capacity;
+        self
+    }
+
+    /// Set the number of canonical blocks below the head which the pipeline can roll back to.
+    pub const fn with_max_reorg_depth(mut self, max_reorg_depth: usize) -> Self {
+        self.max_reorg_depth = max_reorg_depth;
+        self
+    }
+
+    /// Set the number of canonical blocks below the head whose bundle states are kept.
+    pub const fn with_bundle_state_retention(mut self, bundle_state_retention: usize) -> Self {
+        self.bundle_state_retention = bundle_state_retention;
+        self
+    }
+
+    /// Set the number of recently executed blocks whose hashes are cached.
+    pub const fn with_executed_block_cache_capacity(
+        mut self,
+        executed_block_cache_capacity: usize,
+    ) -> Self {
+        self.executed_block_cache_capacity = executed_block_cache_capacity;
+        self
+    }
+
+    /// Set the number of recently committed blocks whose execution outcomes are cached.
+    pub const fn with_execution_outcome_cache_capacity(
+        mut self,
+        execution_outcome_cache_capacity: usize,
+    ) -> Self {
+        self.execution_outcome_cache_capacity = execution_outcome_cache_capacity;
+        self
+    }
+
+    /// Set the number of recently committed blocks whose status is kept.
+    pub const fn with_block_status_capacity(mut self, block_status_capacity: usize) -> Self {
+        self.block_status_capacity = block_status_capacity;
+        self
+    }
+
+    /// Set whether to send the net state changes of each block made canonical.
+    pub const fn with_emit_state_diff(mut self, emit_state_diff: bool) -> Self {
+        self.emit_state_diff = emit_state_diff;
+        self
+    }
+
+    /// Set the number of canonical block notifications buffered for each subscriber.
+    pub const fn with_canonical_notification_capacity(
+        mut self,
+        canonical_notification_capacity: usize,
+    ) -> Self {
+        self.canonical_notification_capacity = canonical_notification_capacity;
+        self
+    }
+
+    /// Set the number of transactions in a block below which they are filtered sequentially.
+    pub const fn with_parallel_filter_threshold(
+        mut self,
+        parallel_filter_threshold: usize,
+    ) -> Self {
+        self.parallel_filter_threshold = parallel_filter_threshold;
+        self
+    }
+
+    /// Set whether to always filter the transactions sequentially.
+    pub const fn with_sequential_filter(mut self, sequential_filter: bool) -> Self {
+        self.sequential_filter = sequential_filter;
+        self
+    }
+
+    /// Set whether to execute the transactions of each block sequentially.
+    pub const fn with_sequential_execution(mut self, sequential_execution: bool) -> Self {
+        self.sequential_execution = sequential_execution;
+        self
+    }
+
+    /// Set the maximum gas limit of a single transaction.
+    pub const fn with_max_tx_gas_limit(mut self, max_tx_gas_limit: u64) -> Self {
+        self.max_tx_gas_limit = Some(max_tx_gas_limit);
+        self
+    }
+
+    /// Set the maximum cumulative encoded size in bytes of the transactions of a block.
+    pub const fn with_max_block_size(mut self, max_block_size: usize) -> Self {
+        self.max_block_size = Some(max_block_size);
+        self
+    }
+
+    /// Set the maximum number of valid transactions of a sender in a block.
+    pub const fn with_max_txs_per_sender(mut self, max_txs_per_sender: usize) -> Self {
+        self.max_txs_per_sender = Some(max_txs_per_sender);
+        self
+    }
+
+    /// Whether to filter the transactions of a block with `tx_count` transactions in parallel.
+    pub const fn parallel_filter(&self, tx_count: usize) -> bool {
+        !self.sequential_filter && tx_count >= self.parallel_filter_threshold
+    }
+
+    /// Set the directory into which the block which failed to execute is dumped.
+    pub fn with_failed_block_dump_dir(mut self, failed_block_dump_dir: impl Into<PathBuf>) -> Self {
+        self.failed_block_dump_dir = Some(failed_block_dump_dir.into());
+        self
+    }
+
+    /// Set the number of consecutive execution failures after which the pipeline halts.
+    pub const fn with_max_consecutive_execution_failures(mut self, failures: usize) -> Self {
+        self.max_consecutive_execution_failures = failures;
+        self
+    }
+
+    /// Set the source of the `parent_beacon_block_root` of the blocks since Cancun, e.g. the
+    /// roots of the original blocks when replaying mainnet blocks exactly. The writes of the
+    /// beacon roots contract call are included in `PipeExecLayerEvent::StateDiff`.
+    pub fn with_parent_beacon_block_root(
+        mut self,
+        parent_beacon_block_root: impl ParentBeaconBlockRoot + 'static,
+    ) -> Self {
+        self.parent_beacon_block_root = Arc::new(parent_beacon_block_root);
+        self
+    }
+
+    /// Set the check of the `prev_randao` of the ordered blocks, e.g. against the randomness of a
+    /// beacon chain or a VRF.
+    pub fn with_prev_randao_validator(
+        mut self,
+        prev_randao_validator: impl PrevRandaoValidator + 'static,
+    ) -> Self {
+        self.prev_randao_validator = Arc::new(prev_randao_validator);
+        self
+    }
+
+    /// Set the number of the latest canonical blocks replayed on startup.
+    pub const fn with_startup_replay_blocks(mut self, startup_replay_blocks: u64) -> Self {
+        self.startup_replay_blocks = startup_replay_blocks;
+        self
+    }
+
+    /// Set whether blocks may be forced canonical without the verification by Coordinator.
+    pub const fn with_allow_force_canonical(mut self, allow_force_canonical: bool) -> Self {
+        self.allow_force_canonical = allow_force_canonical;
+        self
+    }
+
+    /// Enable the sender denylist, starting with the given senders.
+    pub fn with_sender_denylist(mut self, senders: impl IntoIterator<Item = Address>) -> Self {
+        self.sender_denylist = Some(senders.into_iter().collect());
+        self
+    }
+
+    /// Set how the ordered blocks with the zero address as coinbase are treated.
+    pub const fn with_zero_coinbase(mut self, zero_coinbase: ZeroCoinbasePolicy) -> Self {
+        self.zero_coinbase = zero_coinbase;
+        self
+    }
+
+    /// Set the sink of the outcomes of the executed transactions.
+    pub fn with_tx_trace_sink(mut self, tx_trace_sink: impl TxTraceSink + 'static) -> Self {
+        self.tx_trace_sink = Some(Arc::new(tx_trace_sink));
+        self
+    }
+
+    /// Set the backend of the pipeline metrics, e.g. `NoopMetricsSink` for benchmarks.
+    pub fn with_metrics_sink(mut self, metrics_sink: impl MetricsSink + 'static) -> Self {
+        self.metrics_sink = Some(Arc::new(metrics_sink));
+        self
+    }
+
+    /// The directory into which the block which failed to execute is dumped.
+    pub fn failed_block_dump_dir(&self) -> PathBuf {
+        self.failed_block_dump_dir.clone().unwrap_or_else(std::env::temp_dir)
+    }
+
+    /// Check that the configuration is usable.
+    pub fn validate(&self) -> Result<(), PipeExecLayerConfigError> {
+        if self.block_gas_limit == 0 {
+            return Err(PipeExecLayerConfigError::ZeroBlockGasLimit);
+        }
+        if self.barrier_timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return Err(PipeExecLayerConfigError::ZeroBarrierTimeout);
+        }
+        if self.verify_timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return Err(PipeExecLayerConfigError::ZeroVerifyTimeout);
+        }
+        if self.verify_attempts == 0 {
+            return Err(PipeExecLayerConfigError::ZeroVerifyAttempts);
+        }
+        if self.slow_block_interval.is_zero() {
+            return Err(PipeExecLayerConfigError::ZeroSlowBlockInterval);
+        }
+        if self.executor_threads == Some(0) {
+            return Err(PipeExecLayerConfigError::ZeroExecutorThreads);
+        }
+        if self.ordered_block_channel_capacity == 0 {
+            return Err(PipeExecLayerConfigError::ZeroOrderedBlockChannelCapacity);
+        }
+        if self.max_in_flight_blocks == 0 {
+            return Err(PipeExecLayerConfigError::ZeroMaxInFlightBlocks);
+        }
+        if self.max_canonical_lag == Some(0) {
+            return Err(PipeExecLayerConfigError::ZeroMaxCanonicalLag);
+        }
+        if self.max_in_flight_bundle_state_bytes == Some(0) {
+            return Err(PipeExecLayerConfigError::ZeroMaxInFlightBundleStateBytes);
+        }
+        if self.max_consecutive_execution_failures == 0 {
+            return Err(PipeExecLayerConfigError::ZeroMaxConsecutiveExecutionFailures);
+        }
+        if self.canonical_notification_capacity == 0 {
+            return Err(PipeExecLayerConfigError::ZeroCanonicalNotificationCapacity);
+        }
+        if self.max_tx_gas_limit == Some(0) {
+            return Err(PipeExecLayerConfigError::ZeroMaxTxGasLimit);
+        }
+        if self.max_block_size == Some(0) {
+            return Err(PipeExecLayerConfigError::ZeroMaxBlockSize);
+        }
+        if self.max_txs_per_sender == Some(0) {
+            return Err(PipeExecLayerConfigError::ZeroMaxTxsPerSender);
+        }
+        if self.bundle_state_retention < self.max_reorg_depth {
+            return Err(PipeExecLayerConfigError::BundleStateRetentionBelowReorgDepth {
+                bundle_state_retention: self.bundle_state_retention,
+                max_reorg_depth: self.max_reorg_depth,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that the configuration is usable with the chain. The difficulty and nonce can only
+    /// be overridden on a chain without the merge, since the blocks since Paris must have them
+    /// zeroed, along with the header fields of the later hardforks (e.g. the withdrawals root
+    /// since Shanghai).
+    pub fn validate_chain(&self, chain_spec: &ChainSpec) -> Result<(), PipeExecLayerConfigError> {
+        let overridden = !self.block_difficulty.is_zero() || self.block_nonce != BEACON_NONCE;
+        if overridden &&
+            chain_spec.ethereum_fork_activation(EthereumHardfork::Paris) != ForkCondition::Never
+        {
+            return Err(PipeExecLayerConfigError::PowHeaderFieldsAfterMerge);
+        }
+        Ok(())
+    }
+}
+
+/// Errors of an invalid [`PipeExecLayerConfig`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PipeExecLayerConfigError {
+    /// The block gas limit is zero
+    #[error("block gas limit must be nonzero")]
+    ZeroBlockGasLimit,
+    /// The barrier timeout is zero
+    #[error("barrier timeout must be nonzero")]
+    ZeroBarrierTimeout,
+    /// The verify timeout is zero
+    #[error("verify timeout must be nonzero")]
+    ZeroVerifyTimeout,
+    /// The number of verify attempts is zero
+    #[error("verify attempts must be nonzero")]
+    ZeroVerifyAttempts,
+    /// The slow block interval is zero
+    #[error("slow block interval must be nonzero")]
+    ZeroSlowBlockInterval,
+    /// The executor thread pool has zero threads
+    #[error("executor thread pool size must be nonzero")]
+    ZeroExecutorThreads,
+    /// The ordered block channel capacity is zero
+    #[error("ordered block channel capacity must be nonzero")]
+    ZeroOrderedBlockChannelCapacity,
+    /// The maximum number of in-flight blocks is zero
+    #[error("max in-flight blocks must be nonzero")]
+    ZeroMaxInFlightBlocks,
+    /// The maximum lag of the make canonical stage is zero
+    #[error("max canonical lag must be nonzero")]
+    ZeroMaxCanonicalLag,
+    /// The budget of the bundle states in flight is zero
+    #[error("max in-flight bundle state bytes must be nonzero")]
+    ZeroMaxInFlightBundleStateBytes,
+    /// The number of consecutive execution failures to halt at is zero
+    #[error("max consecutive execution failures must be nonzero")]
+    ZeroMaxConsecutiveExecutionFailures,
+    /// The canonical notification capacity is zero
+    #[error("canonical notification capacity must be nonzero")]
+    ZeroCanonicalNotificationCapacity,
+    /// The per-transaction gas cap is zero
+    #[error("max transaction gas limit must be nonzero")]
+    ZeroMaxTxGasLimit,
+    /// The maximum block size is zero
+    #[error("max block size must be nonzero")]
+    ZeroMaxBlockSize,
+    /// The maximum number of transactions per sender is zero
+    #[error("max transactions per sender must be nonzero")]
+    ZeroMaxTxsPerSender,
+    /// Bundle states are pruned before the blocks a reorg can roll back to
+    #[error(
+        "bundle state retention {bundle_state_retention} must not be lower than max reorg depth \
+         {max_reorg_depth}"
+    )]
+    BundleStateRetentionBelowReorgDepth { bundle_state_retention: usize, max_reorg_depth: usize },
+    /// The difficulty or nonce is overridden on a chain with the merge
+    #[error("block difficulty and nonce can't be overridden on a chain with the merge")]
+    PowHeaderFieldsAfterMerge,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reth_chainspec::{ChainSpecBuilder, MAINNET};
+
+    #[test]
+    fn test_validate_block_gas_limit() {
+        assert_eq!(PipeExecLayerConfig::default().block_gas_limit, DEFAULT_BLOCK_GAS_LIMIT);
+        assert!(PipeExecLayerConfig::default().validate().is_ok());
+        assert!(PipeExecLayerConfig::default().with_block_gas_limit(30_000_000).validate().is_ok());
+        assert_eq!(
+            PipeExecLayerConfig::default().with_block_gas_limit(0).validate(),
+            Err(PipeExecLayerConfigError::ZeroBlockGasLimit)
+        );
+    }
+
+    #[test]
+    fn test_validate_ordered_block_channel_capacity() {
+        assert_eq!(
+            PipeExecLayerConfig::default().ordered_block_channel_capacity,
+            DEFAULT_ORDERED_BLOCK_CHANNEL_CAPACITY
+        );
+        assert_eq!(
+            PipeExecLayerConfig::default().with_ordered_block_channel_capacity(0).validate(),
+            Err(PipeExecLayerConfigError::ZeroOrderedBlockChannelCapacity)
+        );
+    }
+
+    #[test]
+    fn test_validate_chain() {
+        let pre_merge = ChainSpecBuilder::default()
+            .chain(MAINNET.chain)
+            .genesis(MAINNET.genesis.clone())
+            .build();
+        let overridden = PipeExecLayerConfig::default()
+            .with_block_difficulty(U256::from(131_072))
+            .with_block_nonce(0x42);
+        assert!(PipeExecLayerConfig::default().validate_chain(&MAINNET).is_ok());
+        assert!(PipeExecLayerConfig::default().validate_chain(&pre_merge).is_ok());
+        assert!(overridden.validate_chain(&pre_merge).is_ok());
+        assert_eq!(
+            overridden.validate_chain(&MAINNET),
+            Err(PipeExecLayerConfigError::PowHeaderFieldsAfterMerge)
+        );
+        assert_eq!(
+            PipeExecLayerConfig::default().with_block_nonce(1).validate_chain(&MAINNET),
+            Err(PipeExecLayerConfigError::PowHeaderFieldsAfterMerge)
+        );
+    }
+
+    #[test]
+    fn test_validate_slow_block_interval() {
+        assert_eq!(PipeExecLayerConfig::default().slow_block_interval, DEFAULT_SLOW_BLOCK_INTERVAL);
+        assert_eq!(
+            PipeExecLayerConfig::default().with_slow_block_interval(Duration::ZERO).validate(),
+            Err(PipeExecLayerConfigError::ZeroSlowBlockInterval)
+        );
+    }
+
+    #[test]
+    fn test_validate_max_canonical_lag() {
+        assert!(PipeExecLayerConfig::default().with_max_canonical_lag(1).validate().is_ok());
+        assert_eq!(
+            PipeExecLayerConfig::default().with_max_canonical_lag(0).validate(),
+            Err(PipeExecLayerConfigError::ZeroMaxCanonicalLag)
+        );
+    }
+
+    #[test]
+    fn test_validate_max_in_flight_bundle_state_bytes() {
+        let config = PipeExecLayerConfig::default().with_max_in_flight_bundle_state_bytes(1);
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            PipeExecLayerConfig::default().with_max_in_flight_bundle_state_bytes(0).validate(),
+            Err(PipeExecLayerConfigError::ZeroMaxInFlightBundleStateBytes)
+        );
+    }
+
+    #[test]
+    fn test_validate_max_consecutive_execution_failures() {
+        let config = PipeExecLayerConfig::default();
+        assert_eq!(config.max_consecutive_execution_failures, 1);
+        assert!(config.with_max_consecutive_execution_failures(3).validate().is_ok());
+        assert_eq!(
+            PipeExecLayerConfig::default().with_max_consecutive_execution_failures(0).validate(),
+            Err(PipeExecLayerConfigError::ZeroMaxConsecutiveExecutionFailures)
+        );
+    }
+
+    #[test]
+    fn test_validate_bundle_state_retention() {
+        assert!(PipeExecLayerConfig::default()
+            .with_max_reorg_depth(0)
+            .with_bundle_state_retention(0)
+            .validate()
+            .is_ok());
+        assert_eq!(
+            PipeExecLayerConfig::default().with_bundle_state_retention(1).validate(),
+            Err(PipeExecLayerConfigError::BundleStateRetentionBelowReorgDepth {
+                bundle_state_retention: 1,
+                max_reorg_depth: DEFAULT_MAX_REORG_DEPTH,
+            })
+        );
+    }
+}
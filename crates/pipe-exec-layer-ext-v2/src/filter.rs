@@ -0,0 +1,1404 @@
+use alloy_consensus::Transaction;
+use alloy_eips::{
+    eip2718::Encodable2718,
+    eip4844::{env_settings::EnvKzgSettings, BlobTransactionSidecar},
+    eip7702::SignedAuthorization,
+};
+use alloy_primitives::{Address, TxHash, U256};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use reth_ethereum_primitives::TransactionSigned;
+use reth_evm::ParallelDatabase;
+use revm::primitives::{AccountInfo, HashMap, HashSet};
+use std::sync::Mutex;
+
+use tracing::*;
+
+/// The reason why a transaction was discarded by [`filter_invalid_txs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxFilterReason {
+    /// The transaction nonce doesn't match the sender's account nonce
+    NonceMismatch {
+        /// Nonce expected by the sender's account
+        expected: u64,
+        /// Nonce carried by the transaction
+        got: u64,
+    },
+    /// The sender can't afford the transaction
+    InsufficientBalance {
+        /// Balance left in the sender's account
+        have: U256,
+        /// Balance required by the transaction
+        need: U256,
+    },
+    /// The sender doesn't exist in the state
+    SenderNotFound,
+    /// The transaction is signed for another chain
+    ChainIdMismatch {
+        /// Chain id of the configured chain spec
+        expected: u64,
+        /// Chain id carried by the transaction
+        got: u64,
+    },
+    /// The transaction is an EIP-7702 transaction, but Prague is not active
+    Eip7702NotActive,
+    /// The EIP-7702 transaction carries no authorization
+    EmptyAuthorizationList,
+    /// The cumulative gas limit of the transactions exceeds the block gas limit
+    GasLimitExceeded,
+    /// The cumulative encoded size of the transactions exceeds the maximum block size
+    BlockSizeExceeded,
+    /// The blob transaction comes without its sidecar
+    MissingBlobSidecar,
+    /// The sidecar of the blob transaction doesn't match its versioned hashes, or carries invalid
+    /// KZG proofs
+    InvalidBlobSidecar,
+    /// The blob transaction can't pay the blob base fee of the block
+    BlobFeeTooLow {
+        /// Max fee per blob gas carried by the transaction
+        max_fee_per_blob_gas: u128,
+        /// Blob base fee of the block
+        blob_base_fee: u128,
+    },
+    /// The sender already has `max_txs_per_sender` valid transactions in the block
+    PerSenderLimitExceeded,
+    /// The same transaction appears earlier in the block
+    DuplicateTransaction,
+    /// The sender is in the sender denylist
+    SenderDenied,
+    /// The gas limit of the transaction exceeds the per-transaction gas cap
+    PerTxGasCapExceeded {
+        /// The per-transaction gas cap
+        cap: u64,
+        /// Gas limit carried by the transaction
+        got: u64,
+    },
+}
+
+impl TxFilterReason {
+    /// Short name of the reason, used as the metrics label.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::NonceMismatch { .. } => "nonce_mismatch",
+            Self::InsufficientBalance { .. } => "insufficient_balance",
+            Self::SenderNotFound => "sender_not_found",
+            Self::ChainIdMismatch { .. } => "chain_id_mismatch",
+            Self::Eip7702NotActive => "eip7702_not_active",
+            Self::EmptyAuthorizationList => "empty_authorization_list",
+            Self::GasLimitExceeded => "gas_limit_exceeded",
+            Self::BlockSizeExceeded => "block_size_exceeded",
+            Self::MissingBlobSidecar => "missing_blob_sidecar",
+            Self::InvalidBlobSidecar => "invalid_blob_sidecar",
+            Self::BlobFeeTooLow { .. } => "blob_fee_too_low",
+            Self::PerSenderLimitExceeded => "per_sender_limit_exceeded",
+            Self::DuplicateTransaction => "duplicate_transaction",
+            Self::SenderDenied => "sender_denied",
+            Self::PerTxGasCapExceeded { .. } => "per_tx_gas_cap_exceeded",
+        }
+    }
+}
+
+/// Account of a sender as predicted by [`filter_invalid_txs`] once its valid transactions are
+/// executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PredictedAccount {
+    /// Nonce following the last valid transaction
+    pub(crate) nonce: u64,
+    /// Balance left if each valid transaction used up its gas limit, which is a lower bound of the
+    /// balance after execution, since the unused gas is refunded and the sender may receive funds
+    pub(crate) min_balance: U256,
+    /// Hash of the last valid transaction of the sender
+    pub(crate) last_tx_hash: TxHash,
+}
+
+/// Predicted accounts of the senders with valid transactions, keyed by sender.
+pub(crate) type PredictedAccounts = HashMap<Address, PredictedAccount>;
+
+/// Return the filtered valid transactions with sender without changing the relative order of
+/// the transactions, along with the index (in the original list) and the reason of each discarded
+/// transaction, sorted by index.
+///
+/// With debug assertions, the accounts of the senders as tracked by the filter are returned as
+/// well, to be compared with the state after execution by [`account_drifts`]. The senders whose
+/// accounts have code, i.e. are delegated by EIP-7702, are not predicted since their code may
+/// spend their balance, nor are the senders with transactions dropped for the block gas limit.
+/// The map is empty without debug assertions.
+///
+/// If `blob_sidecars` is provided, each blob transaction must come with a sidecar whose
+/// commitments match its versioned hashes and whose KZG proofs are valid.
+///
+/// `blob_base_fee` is the blob base fee of the block since Cancun, derived from its excess blob
+/// gas, which each blob transaction must be willing to pay.
+///
+/// If `max_block_size` is set, the valid transactions are truncated once their cumulative
+/// EIP-2718 encoded size exceeds it, the same way as once their cumulative gas limit exceeds
+/// `block_gas_limit`.
+///
+/// If `max_tx_gas_limit` is set, each transaction whose gas limit exceeds it is discarded, so
+/// that a single transaction can't take up the whole block gas limit.
+///
+/// If `max_txs_per_sender` is set, the valid transactions of each sender beyond it are discarded,
+/// keeping the ones with the lowest nonces.
+///
+/// If `sender_denylist` is set, all the transactions of the senders in it are discarded.
+///
+/// The senders are checked on the rayon pool if `parallel` is set, otherwise one after another on
+/// the current thread. The result is the same either way.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn filter_invalid_txs<DB: ParallelDatabase>(
+    db: DB,
+    txs: Vec<TransactionSigned>,
+    senders: Vec<Address>,
+    blob_sidecars: Option<&HashMap<TxHash, BlobTransactionSidecar>>,
+    base_fee_per_gas: u64,
+    blob_base_fee: Option<u128>,
+    chain_id: u64,
+    prague_active: bool,
+    block_gas_limit: u64,
+    max_block_size: Option<usize>,
+    max_tx_gas_limit: Option<u64>,
+    max_txs_per_sender: Option<usize>,
+    sender_denylist: Option<&HashSet<Address>>,
+    parallel: bool,
+) -> (Vec<TransactionSigned>, Vec<Address>, Vec<(usize, TxFilterReason)>, PredictedAccounts) {
+    // The transactions of the denied senders and the later duplicates of a transaction are
+    // discarded before the other checks, so that they neither consume the nonce nor the balance of
+    // the sender
+    let mut seen_hashes = HashSet::default();
+    let mut discarded_early = Vec::new();
+    let mut sender_idx: HashMap<&Address, Vec<usize>> = HashMap::default();
+    for (i, (tx, sender)) in txs.iter().zip(&senders).enumerate() {
+        if sender_denylist.is_some_and(|denylist| denylist.contains(sender)) {
+            debug!(target: "filter_invalid_txs",
+                tx_hash=?tx.hash(),
+                sender=?sender,
+                "sender denied"
+            );
+            discarded_early.push((i, TxFilterReason::SenderDenied));
+            continue;
+        }
+        if !seen_hashes.insert(*tx.hash()) {
+            debug!(target: "filter_invalid_txs",
+                tx_hash=?tx.hash(),
+                sender=?sender,
+                "duplicate transaction"
+            );
+            discarded_early.push((i, TxFilterReason::DuplicateTransaction));
+            continue;
+        }
+        sender_idx.entry(sender).or_insert_with(Vec::new).push(i);
+    }
+
+    let is_tx_valid = |tx: &TransactionSigned,
+                       sender: &Address,
+                       account: &mut AccountInfo|
+     -> Result<(), TxFilterReason> {
+        if let Some(cap) = max_tx_gas_limit.filter(|cap| tx.transaction().gas_limit() > *cap) {
+            debug!(target: "filter_invalid_txs",
+                tx_hash=?tx.hash(),
+                sender=?sender,
+                gas_limit=?tx.transaction().gas_limit(),
+                cap=?cap,
+                "per-transaction gas cap exceeded"
+            );
+            return Err(TxFilterReason::PerTxGasCapExceeded {
+                cap,
+                got: tx.transaction().gas_limit(),
+            });
+        }
+        // Pre-EIP-155 legacy transactions don't carry a chain id and are valid on any chain
+        if let Some(tx_chain_id) = tx.transaction().chain_id() {
+            if tx_chain_id != chain_id {
+                debug!(target: "filter_invalid_txs",
+                    tx_hash=?tx.hash(),
+                    sender=?sender,
+                    tx_chain_id=?tx_chain_id,
+                    chain_id=?chain_id,
+                    "chain id mismatch"
+                );
+                return Err(TxFilterReason::ChainIdMismatch {
+                    expected: chain_id,
+                    got: tx_chain_id,
+                });
+            }
+        }
+        if let Some(authorization_list) = tx.transaction().authorization_list() {
+            if !prague_active {
+                debug!(target: "filter_invalid_txs",
+                    tx_hash=?tx.hash(),
+                    sender=?sender,
+                    "eip-7702 transaction before prague"
+                );
+                return Err(TxFilterReason::Eip7702NotActive);
+            }
+            if authorization_list.is_empty() {
+                debug!(target: "filter_invalid_txs",
+                    tx_hash=?tx.hash(),
+                    sender=?sender,
+                    "empty authorization list"
+                );
+                return Err(TxFilterReason::EmptyAuthorizationList);
+            }
+            for authorization in authorization_list {
+                validate_authorization(tx, authorization, chain_id)?;
+            }
+        }
+        if let (Some(blob_versioned_hashes), Some(blob_sidecars)) =
+            (tx.transaction().blob_versioned_hashes(), blob_sidecars)
+        {
+            let Some(sidecar) = blob_sidecars.get(tx.hash()) else {
+                debug!(target: "filter_invalid_txs",
+                    tx_hash=?tx.hash(),
+                    sender=?sender,
+                    "missing blob sidecar"
+                );
+                return Err(TxFilterReason::MissingBlobSidecar);
+            };
+            if let Err(err) = sidecar.validate(blob_versioned_hashes, EnvKzgSettings::Default.get())
+            {
+                debug!(target: "filter_invalid_txs",
+                    tx_hash=?tx.hash(),
+                    sender=?sender,
+                    %err,
+                    "invalid blob sidecar"
+                );
+                return Err(TxFilterReason::InvalidBlobSidecar);
+            }
+        }
+        if account.nonce != tx.transaction().nonce() {
+            debug!(target: "filter_invalid_txs",
+                tx_hash=?tx.hash(),
+                sender=?sender,
+                nonce=?tx.transaction().nonce(),
+                account_nonce=?account.nonce,
+                "nonce mismatch"
+            );
+            return Err(TxFilterReason::NonceMismatch {
+                expected: account.nonce,
+                got: tx.transaction().nonce(),
+            });
+        }
+        let mut blob_fee_needed = U256::ZERO;
+        let mut blob_fee_spent = U256::ZERO;
+        if let (Some(max_fee_per_blob_gas), Some(blob_base_fee)) =
+            (tx.transaction().max_fee_per_blob_gas(), blob_base_fee)
+        {
+            if max_fee_per_blob_gas < blob_base_fee {
+                debug!(target: "filter_invalid_txs",
+                    tx_hash=?tx.hash(),
+                    sender=?sender,
+                    max_fee_per_blob_gas=?max_fee_per_blob_gas,
+                    blob_base_fee=?blob_base_fee,
+                    "blob fee too low"
+                );
+                return Err(TxFilterReason::BlobFeeTooLow { max_fee_per_blob_gas, blob_base_fee });
+            }
+            // The EVM requires the balance to cover the blob gas at `max_fee_per_blob_gas`, while
+            // only the blob base fee is charged
+            let blob_gas = U256::from(tx.transaction().blob_gas_used().unwrap_or_default());
+            blob_fee_needed = blob_gas * U256::from(max_fee_per_blob_gas);
+            blob_fee_spent = blob_gas * U256::from(blob_base_fee);
+        }
+        // Likewise, the EVM requires the balance to cover the gas limit at `max_fee_per_gas`, i.e.
+        // the gas price for legacy transactions, while only the effective gas price is charged,
+        // i.e. the base fee plus the priority fee capped by `max_fee_per_gas` for EIP-1559
+        // transactions.
+        let gas_limit = U256::from(tx.transaction().gas_limit());
+        let gas_fee_needed = gas_limit * U256::from(tx.transaction().max_fee_per_gas());
+        let gas_price = tx.transaction().effective_gas_price(Some(base_fee_per_gas));
+        let gas_spent = gas_limit * U256::from(gas_price);
+        let balance_needed = gas_fee_needed + tx.transaction().value() + blob_fee_needed;
+        if account.balance < balance_needed {
+            debug!(target: "filter_invalid_txs",
+                tx_hash=?tx.hash(),
+                sender=?sender,
+                balance=?account.balance,
+                gas_spent=?gas_spent,
+                balance_needed=?balance_needed,
+                "insufficient balance"
+            );
+            return Err(TxFilterReason::InsufficientBalance {
+                have: account.balance,
+                need: balance_needed,
+            });
+        }
+        account.balance -= gas_spent + tx.transaction().value() + blob_fee_spent;
+        account.nonce += 1;
+        Ok(())
+    };
+
+    let predicted_accounts = Mutex::new(PredictedAccounts::default());
+    let check_sender = |(sender, idxs): (&Address, Vec<usize>)| {
+        if let Some(mut account) = db.basic_ref(*sender).unwrap() {
+            let mut valid_txs = 0;
+            let mut last_valid_idx = None;
+            let discarded = idxs
+                .into_iter()
+                .filter_map(|idx| {
+                    // The valid transactions of the sender come in ascending order of nonce, so
+                    // the ones beyond the limit have the highest nonces
+                    if max_txs_per_sender.is_some_and(|max| valid_txs >= max) {
+                        debug!(target: "filter_invalid_txs",
+                            tx_hash=?txs[idx].hash(),
+                            sender=?sender,
+                            max_txs_per_sender=?max_txs_per_sender,
+                            "per-sender limit exceeded"
+                        );
+                        return Some((idx, TxFilterReason::PerSenderLimitExceeded));
+                    }
+                    match is_tx_valid(&txs[idx], sender, &mut account) {
+                        Ok(()) => {
+                            valid_txs += 1;
+                            last_valid_idx = Some(idx);
+                            None
+                        }
+                        Err(reason) => Some((idx, reason)),
+                    }
+                })
+                .collect::<Vec<_>>();
+            if let Some(idx) =
+                last_valid_idx.filter(|_| cfg!(debug_assertions) && account.is_empty_code_hash())
+            {
+                predicted_accounts.lock().unwrap().insert(
+                    *sender,
+                    PredictedAccount {
+                        nonce: account.nonce,
+                        min_balance: account.balance,
+                        last_tx_hash: *txs[idx].hash(),
+                    },
+                );
+            }
+            discarded
+        } else {
+            // Sender should exist in the state
+            debug!(target: "filter_invalid_txs",
+                tx_hash=?txs[idxs[0]].hash(),
+                sender=?sender,
+                "sender not found"
+            );
+            idxs.into_iter().map(|idx| (idx, TxFilterReason::SenderNotFound)).collect::<Vec<_>>()
+        }
+    };
+    // Each sender is checked against its own copy of its account, and the discarded transactions
+    // are sorted by index below, so the order in which the senders are checked doesn't matter
+    let mut discarded = if parallel {
+        sender_idx.into_par_iter().flat_map(check_sender).collect::<Vec<_>>()
+    } else {
+        sender_idx.into_iter().flat_map(check_sender).collect::<Vec<_>>()
+    };
+    discarded.extend(discarded_early);
+    let mut predicted_accounts = predicted_accounts.into_inner().unwrap();
+    let mut invalid_idxs = discarded.iter().map(|(idx, _)| *idx).collect::<HashSet<_>>();
+
+    // Truncate the valid transactions once their cumulative gas limit exceeds the block gas
+    // limit, or their cumulative encoded size exceeds the maximum block size. All the following
+    // transactions are dropped, so that no sender is left with a transaction whose nonce follows
+    // a dropped one.
+    let mut cumulative_gas_limit = 0u64;
+    let mut cumulative_size = 0usize;
+    let overflow = (0..txs.len()).filter(|idx| !invalid_idxs.contains(idx)).find_map(|idx| {
+        cumulative_gas_limit =
+            cumulative_gas_limit.saturating_add(txs[idx].transaction().gas_limit());
+        if cumulative_gas_limit > block_gas_limit {
+            return Some((idx, TxFilterReason::GasLimitExceeded));
+        }
+        // The encoded size is computed from the fields without encoding the transaction
+        cumulative_size = cumulative_size.saturating_add(txs[idx].encode_2718_len());
+        max_block_size
+            .is_some_and(|max_block_size| cumulative_size > max_block_size)
+            .then_some((idx, TxFilterReason::BlockSizeExceeded))
+    });
+    if let Some((overflow_idx, reason)) = overflow {
+        let overflow_idxs =
+            (overflow_idx..txs.len()).filter(|idx| !invalid_idxs.contains(idx)).collect::<Vec<_>>();
+        debug!(target: "filter_invalid_txs",
+            tx_hash=?txs[overflow_idx].hash(),
+            reason=reason.as_str(),
+            block_gas_limit=?block_gas_limit,
+            max_block_size=?max_block_size,
+            dropped=?overflow_idxs.len(),
+            "block limit exceeded"
+        );
+        for idx in overflow_idxs {
+            invalid_idxs.insert(idx);
+            discarded.push((idx, reason.clone()));
+            predicted_accounts.remove(&senders[idx]);
+        }
+    }
+
+    if !discarded.is_empty() {
+        discarded.sort_unstable_by_key(|(idx, _)| *idx);
+        let mut filtered_txs = Vec::with_capacity(txs.len() - invalid_idxs.len());
+        let mut filtered_senders = Vec::with_capacity(filtered_txs.capacity());
+        for (i, (tx, sender)) in txs.into_iter().zip(senders.into_iter()).enumerate() {
+            if invalid_idxs.contains(&i) {
+                continue;
+            }
+            filtered_txs.push(tx);
+            filtered_senders.push(sender);
+        }
+        (filtered_txs, filtered_senders, discarded, predicted_accounts)
+    } else {
+        (txs, senders, discarded, predicted_accounts)
+    }
+}
+
+/// Compare the accounts of the senders after execution, looked up by `post_account`, with the
+/// ones predicted by [`filter_invalid_txs`]. Returns the senders whose nonce differs or whose
+/// balance is below the predicted lower bound, sorted by address, and logs each of them along
+/// with its last valid transaction. A drift points at a bug in the accounting of the filter, which
+/// may let through a transaction failing the block. The senders missing from the post-state are
+/// not compared.
+pub(crate) fn account_drifts(
+    predicted_accounts: &PredictedAccounts,
+    post_account: impl Fn(&Address) -> Option<AccountInfo>,
+) -> Vec<Address> {
+    let mut drifts = predicted_accounts
+        .iter()
+        .filter_map(|(sender, predicted)| {
+            let account = post_account(sender)?;
+            if account.nonce == predicted.nonce && account.balance >= predicted.min_balance {
+                return None;
+            }
+            error!(target: "filter_invalid_txs",
+                tx_hash=?predicted.last_tx_hash,
+                sender=?sender,
+                nonce=?account.nonce,
+                predicted_nonce=?predicted.nonce,
+                balance=?account.balance,
+                predicted_min_balance=?predicted.min_balance,
+                "sender account drifted from the filter prediction"
+            );
+            Some(*sender)
+        })
+        .collect::<Vec<_>>();
+    drifts.sort_unstable();
+    drifts
+}
+
+/// Validate an authorization entry of an EIP-7702 transaction.
+///
+/// The EVM skips invalid authorization entries instead of failing the transaction, so the entries
+/// which will be skipped are only logged for now. This is the place to validate the entries
+/// against their authority accounts, e.g. the authority nonce, which also affects the following
+/// transactions of the authority in the block if the authority is a sender.
+fn validate_authorization(
+    tx: &TransactionSigned,
+    authorization: &SignedAuthorization,
+    chain_id: u64,
+) -> Result<(), TxFilterReason> {
+    if !authorization.chain_id.is_zero() && authorization.chain_id != U256::from(chain_id) {
+        debug!(target: "filter_invalid_txs",
+            tx_hash=?tx.hash(),
+            authorization_chain_id=?authorization.chain_id,
+            chain_id=?chain_id,
+            "authorization for another chain will be skipped"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloy_consensus::{TxEip1559, TxEip4844, TxEip7702, TxLegacy};
+    use alloy_eips::{
+        eip4844::builder::{SidecarBuilder, SimpleCoder},
+        eip7702::Authorization,
+    };
+    use alloy_primitives::{PrimitiveSignature as Signature, TxKind};
+    use reth_ethereum_primitives::Transaction as EthTransaction;
+    use revm::db::{CacheDB, EmptyDB};
+
+    const BASE_FEE: u64 = 7;
+    const CHAIN_ID: u64 = 1;
+    const BLOCK_GAS_LIMIT: u64 = 30_000_000;
+    const GAS_LIMIT: u64 = 21_000;
+
+    fn sign(tx: EthTransaction) -> TransactionSigned {
+        TransactionSigned::new_unhashed(tx, Signature::test_signature())
+    }
+
+    fn legacy_tx(nonce: u64, gas_price: u128, value: u64) -> TransactionSigned {
+        legacy_tx_with_chain_id(Some(CHAIN_ID), nonce, gas_price, value)
+    }
+
+    fn legacy_tx_with_chain_id(
+        chain_id: Option<u64>,
+        nonce: u64,
+        gas_price: u128,
+        value: u64,
+    ) -> TransactionSigned {
+        sign(EthTransaction::Legacy(TxLegacy {
+            chain_id,
+            nonce,
+            gas_price,
+            gas_limit: GAS_LIMIT,
+            to: TxKind::Call(Address::ZERO),
+            value: U256::from(value),
+            ..Default::default()
+        }))
+    }
+
+    fn eip1559_tx(
+        nonce: u64,
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+        value: u64,
+    ) -> TransactionSigned {
+        sign(EthTransaction::Eip1559(TxEip1559 {
+            chain_id: CHAIN_ID,
+            nonce,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+            gas_limit: GAS_LIMIT,
+            to: TxKind::Call(Address::ZERO),
+            value: U256::from(value),
+            ..Default::default()
+        }))
+    }
+
+    fn eip7702_tx(nonce: u64, authorization_list: Vec<SignedAuthorization>) -> TransactionSigned {
+        sign(EthTransaction::Eip7702(TxEip7702 {
+            chain_id: CHAIN_ID,
+            nonce,
+            max_fee_per_gas: BASE_FEE.into(),
+            gas_limit: GAS_LIMIT,
+            to: Address::ZERO,
+            authorization_list,
+            ..Default::default()
+        }))
+    }
+
+    fn authorization(chain_id: u64) -> SignedAuthorization {
+        Authorization {
+            chain_id: U256::from(chain_id),
+            address: Address::with_last_byte(9),
+            nonce: 0,
+        }
+        .into_signed(Signature::test_signature())
+    }
+
+    fn db_with_accounts(accounts: &[(Address, u64, u64)]) -> CacheDB<EmptyDB> {
+        let mut db = CacheDB::new(EmptyDB::default());
+        for (address, nonce, balance) in accounts {
+            db.insert_account_info(
+                *address,
+                AccountInfo { nonce: *nonce, balance: U256::from(*balance), ..Default::default() },
+            );
+        }
+        db
+    }
+
+    #[test]
+    fn test_legacy_tx_pays_gas_price() {
+        let sender = Address::with_last_byte(1);
+        // The legacy gas price already includes the base fee
+        let db = db_with_accounts(&[(sender, 0, GAS_LIMIT * 10 + 100)]);
+        let (txs, senders, discarded, _) = filter_invalid_txs(
+            &db,
+            vec![legacy_tx(0, 10, 100)],
+            vec![sender],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert_eq!(txs.len(), 1);
+        assert_eq!(senders, vec![sender]);
+        assert!(discarded.is_empty());
+    }
+
+    #[test]
+    fn test_eip1559_tx_pays_effective_gas_price() {
+        let sender = Address::with_last_byte(1);
+        let poor_sender = Address::with_last_byte(2);
+        // max_fee_per_gas exceeds base_fee + priority_fee, so the effective gas price is
+        // base_fee + priority_fee, while the balance must cover max_fee_per_gas
+        let max_fee_per_gas = 100;
+        let effective_gas_price = BASE_FEE + 2;
+        let db = db_with_accounts(&[
+            (sender, 0, GAS_LIMIT * max_fee_per_gas),
+            (poor_sender, 0, GAS_LIMIT * effective_gas_price),
+        ]);
+        let (txs, senders, discarded, _) = filter_invalid_txs(
+            &db,
+            vec![
+                eip1559_tx(0, max_fee_per_gas.into(), 2, 0),
+                eip1559_tx(0, max_fee_per_gas.into(), 2, 0),
+                eip1559_tx(1, max_fee_per_gas.into(), 2, 0),
+            ],
+            vec![sender, poor_sender, sender],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert_eq!(txs.len(), 1);
+        assert_eq!(senders, vec![sender]);
+        // Only the effective gas price is deducted from the balance of the sender
+        assert_eq!(
+            discarded,
+            vec![
+                (
+                    1,
+                    TxFilterReason::InsufficientBalance {
+                        have: U256::from(GAS_LIMIT * effective_gas_price),
+                        need: U256::from(GAS_LIMIT * max_fee_per_gas)
+                    }
+                ),
+                (
+                    2,
+                    TxFilterReason::InsufficientBalance {
+                        have: U256::from(GAS_LIMIT * (max_fee_per_gas - effective_gas_price)),
+                        need: U256::from(GAS_LIMIT * max_fee_per_gas)
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_value_and_gas_barely_fit() {
+        let sender = Address::with_last_byte(1);
+        let poor_sender = Address::with_last_byte(2);
+        let need = GAS_LIMIT * 10 + 1_000;
+        let db = db_with_accounts(&[(sender, 0, need), (poor_sender, 0, need - 1)]);
+        let (txs, senders, discarded, _) = filter_invalid_txs(
+            &db,
+            vec![legacy_tx(0, 10, 1_000), legacy_tx(0, 10, 1_000), legacy_tx(1, 10, 0)],
+            vec![sender, poor_sender, sender],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert_eq!(txs.len(), 1);
+        assert_eq!(senders, vec![sender]);
+        assert_eq!(
+            discarded,
+            vec![
+                (
+                    1,
+                    TxFilterReason::InsufficientBalance {
+                        have: U256::from(need - 1),
+                        need: U256::from(need)
+                    }
+                ),
+                (
+                    2,
+                    TxFilterReason::InsufficientBalance {
+                        have: U256::ZERO,
+                        need: U256::from(GAS_LIMIT * 10)
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chain_id_mismatch() {
+        let sender = Address::with_last_byte(1);
+        let db = db_with_accounts(&[(sender, 0, GAS_LIMIT * 10 * 2)]);
+        let (txs, _, discarded, _) = filter_invalid_txs(
+            &db,
+            vec![legacy_tx_with_chain_id(Some(CHAIN_ID + 1), 0, 10, 0), legacy_tx(0, 10, 0)],
+            vec![sender, sender],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert_eq!(txs.len(), 1);
+        assert_eq!(
+            discarded,
+            vec![(0, TxFilterReason::ChainIdMismatch { expected: CHAIN_ID, got: CHAIN_ID + 1 })]
+        );
+    }
+
+    #[test]
+    fn test_pre_eip155_legacy_tx() {
+        let sender = Address::with_last_byte(1);
+        let db = db_with_accounts(&[(sender, 0, GAS_LIMIT * 10)]);
+        let (txs, _, discarded, _) = filter_invalid_txs(
+            &db,
+            vec![legacy_tx_with_chain_id(None, 0, 10, 0)],
+            vec![sender],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert_eq!(txs.len(), 1);
+        assert!(discarded.is_empty());
+    }
+
+    #[test]
+    fn test_eip7702_tx() {
+        let sender = Address::with_last_byte(1);
+        let db = db_with_accounts(&[(sender, 0, GAS_LIMIT * BASE_FEE * 2)]);
+        // An authorization for another chain is skipped by the EVM without invalidating the tx
+        let txs = vec![
+            eip7702_tx(0, vec![authorization(CHAIN_ID), authorization(CHAIN_ID + 1)]),
+            eip7702_tx(1, vec![]),
+            eip7702_tx(1, vec![authorization(0)]),
+        ];
+        let (filtered_txs, _, discarded, _) = filter_invalid_txs(
+            &db,
+            txs.clone(),
+            vec![sender; 3],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert_eq!(filtered_txs.len(), 2);
+        assert_eq!(discarded, vec![(1, TxFilterReason::EmptyAuthorizationList)]);
+
+        // The outer nonce and balance are still checked
+        let poor_db = db_with_accounts(&[(sender, 0, GAS_LIMIT * BASE_FEE - 1)]);
+        let (_, _, discarded, _) = filter_invalid_txs(
+            &poor_db,
+            txs[..1].to_vec(),
+            vec![sender],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert!(matches!(discarded[..], [(0, TxFilterReason::InsufficientBalance { .. })]));
+
+        // EIP-7702 transactions are invalid before Prague
+        let (filtered_txs, _, discarded, _) = filter_invalid_txs(
+            &db,
+            txs[..1].to_vec(),
+            vec![sender],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            false,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert!(filtered_txs.is_empty());
+        assert_eq!(discarded, vec![(0, TxFilterReason::Eip7702NotActive)]);
+    }
+
+    #[test]
+    fn test_block_gas_limit_exceeded() {
+        let sender = Address::with_last_byte(1);
+        let other_sender = Address::with_last_byte(2);
+        let db = db_with_accounts(&[(sender, 0, GAS_LIMIT * 10 * 3), (other_sender, 0, 0)]);
+        let (txs, senders, discarded, _) = filter_invalid_txs(
+            &db,
+            vec![
+                legacy_tx(0, 10, 0),
+                // Invalid txs don't count towards the cumulative gas limit
+                legacy_tx(0, 10, 0),
+                legacy_tx(1, 10, 0),
+                legacy_tx(2, 10, 0),
+            ],
+            vec![sender, other_sender, sender, sender],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            GAS_LIMIT * 2,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert_eq!(txs.len(), 2);
+        assert_eq!(senders, vec![sender, sender]);
+        assert_eq!(
+            discarded,
+            vec![
+                (
+                    1,
+                    TxFilterReason::InsufficientBalance {
+                        have: U256::ZERO,
+                        need: U256::from(GAS_LIMIT * 10)
+                    }
+                ),
+                (3, TxFilterReason::GasLimitExceeded),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_block_size_exceeded() {
+        let sender = Address::with_last_byte(1);
+        let other_sender = Address::with_last_byte(2);
+        let db =
+            db_with_accounts(&[(sender, 0, GAS_LIMIT * 10 * 3), (other_sender, 0, GAS_LIMIT * 10)]);
+        let calldata_tx = sign(EthTransaction::Legacy(TxLegacy {
+            chain_id: Some(CHAIN_ID),
+            gas_price: 10,
+            gas_limit: GAS_LIMIT,
+            to: TxKind::Call(Address::ZERO),
+            input: vec![1; 128 * 1024].into(),
+            ..Default::default()
+        }));
+        let txs = vec![legacy_tx(0, 10, 0), calldata_tx, legacy_tx(1, 10, 0), legacy_tx(2, 10, 0)];
+        // Room for the small transactions but not for the calldata
+        let max_block_size = txs[0].encode_2718_len() * 3;
+        let (txs, senders, discarded, _) = filter_invalid_txs(
+            &db,
+            txs,
+            vec![sender, other_sender, sender, sender],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            Some(max_block_size),
+            None,
+            None,
+            None,
+            true,
+        );
+        // The following transactions are dropped as well, even though they would fit
+        assert_eq!(txs.len(), 1);
+        assert_eq!(senders, vec![sender]);
+        assert_eq!(
+            discarded,
+            vec![
+                (1, TxFilterReason::BlockSizeExceeded),
+                (2, TxFilterReason::BlockSizeExceeded),
+                (3, TxFilterReason::BlockSizeExceeded),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_predicted_accounts() {
+        let sender = Address::with_last_byte(1);
+        let other_sender = Address::with_last_byte(2);
+        let balance = GAS_LIMIT * 10 * 3;
+        let db = db_with_accounts(&[(sender, 0, balance), (other_sender, 0, balance)]);
+        let txs = vec![legacy_tx(0, 10, 100), legacy_tx(1, 10, 200), legacy_tx(0, 10, 0)];
+        let last_tx_hash = *txs[1].hash();
+        let (_, _, discarded, predicted_accounts) = filter_invalid_txs(
+            &db,
+            txs,
+            vec![sender, sender, other_sender],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            GAS_LIMIT * 2,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert_eq!(discarded, vec![(2, TxFilterReason::GasLimitExceeded)]);
+        // The sender whose transaction is dropped for the block gas limit is not predicted
+        let min_balance = balance - GAS_LIMIT * 10 * 2 - 300;
+        let predicted =
+            PredictedAccount { nonce: 2, min_balance: U256::from(min_balance), last_tx_hash };
+        assert_eq!(predicted_accounts, PredictedAccounts::from_iter([(sender, predicted)]));
+
+        let post_account = |nonce, balance| {
+            move |_: &Address| {
+                Some(AccountInfo { nonce, balance: U256::from(balance), ..Default::default() })
+            }
+        };
+        // Part of the gas is refunded
+        assert!(account_drifts(&predicted_accounts, post_account(2, min_balance + 10)).is_empty());
+        assert!(account_drifts(&predicted_accounts, |_| None).is_empty());
+        assert_eq!(account_drifts(&predicted_accounts, post_account(1, min_balance)), vec![sender]);
+        assert_eq!(
+            account_drifts(&predicted_accounts, post_account(2, min_balance - 1)),
+            vec![sender]
+        );
+    }
+
+    #[test]
+    fn test_sequential_filter() {
+        // Every other sender can't afford its transactions, and the gas limit cuts the tail
+        let senders = (0..100u8).map(|i| Address::with_last_byte(i % 10)).collect::<Vec<_>>();
+        let accounts = (0..10u8)
+            .map(|i| {
+                (Address::with_last_byte(i), 0, if i % 2 == 0 { GAS_LIMIT * 10 * 10 } else { 0 })
+            })
+            .collect::<Vec<_>>();
+        let db = db_with_accounts(&accounts);
+        let txs = (0..100).map(|i| legacy_tx(i / 10, 10, 0)).collect::<Vec<_>>();
+
+        let filter = |parallel| {
+            filter_invalid_txs(
+                &db,
+                txs.clone(),
+                senders.clone(),
+                None,
+                BASE_FEE,
+                None,
+                CHAIN_ID,
+                true,
+                GAS_LIMIT * 40,
+                None,
+                None,
+                None,
+                None,
+                parallel,
+            )
+        };
+        let (parallel_txs, parallel_senders, parallel_discarded, _) = filter(true);
+        let (sequential_txs, sequential_senders, sequential_discarded, _) = filter(false);
+        assert_eq!(parallel_txs.len(), 40);
+        assert_eq!(parallel_discarded.len(), 60);
+        assert_eq!(parallel_txs, sequential_txs);
+        assert_eq!(parallel_senders, sequential_senders);
+        assert_eq!(parallel_discarded, sequential_discarded);
+    }
+
+    #[test]
+    fn test_filter_is_deterministic() {
+        // Interleaved transactions of senders funded for a varying number of them, some short by
+        // one wei, with a duplicate and a nonce gap
+        let cost = GAS_LIMIT * 10 + 1_000;
+        let accounts = (0..20u8)
+            .map(|i| (Address::with_last_byte(i), 0, cost * (i % 6) as u64 - (i % 2) as u64))
+            .collect::<Vec<_>>();
+        let db = db_with_accounts(&accounts);
+        let (mut txs, mut senders): (Vec<_>, Vec<_>) = (0..5u64)
+            .flat_map(|nonce| {
+                (0..20u8).map(move |i| {
+                    (legacy_tx(nonce, 10, 1_000 - i as u64), Address::with_last_byte(i))
+                })
+            })
+            .unzip();
+        txs.push(txs[3].clone());
+        senders.push(senders[3]);
+        txs.push(legacy_tx(7, 10, 0));
+        senders.push(Address::with_last_byte(5));
+
+        let filter = |parallel| {
+            filter_invalid_txs(
+                &db,
+                txs.clone(),
+                senders.clone(),
+                None,
+                BASE_FEE,
+                None,
+                CHAIN_ID,
+                true,
+                BLOCK_GAS_LIMIT,
+                None,
+                None,
+                None,
+                None,
+                parallel,
+            )
+        };
+        let expected = filter(false);
+        assert!(!expected.0.is_empty());
+        assert!(expected
+            .2
+            .iter()
+            .any(|(_, reason)| *reason == TxFilterReason::DuplicateTransaction));
+        for _ in 0..100 {
+            assert_eq!(filter(true), expected);
+        }
+    }
+
+    #[test]
+    fn test_max_txs_per_sender() {
+        let sender = Address::with_last_byte(1);
+        let other_sender = Address::with_last_byte(2);
+        let db =
+            db_with_accounts(&[(sender, 0, GAS_LIMIT * 10 * 4), (other_sender, 0, GAS_LIMIT * 10)]);
+        let txs = vec![
+            legacy_tx(0, 10, 0),
+            // Invalid txs don't count towards the limit
+            legacy_tx(5, 10, 0),
+            legacy_tx(1, 10, 0),
+            legacy_tx(0, 10, 0),
+            legacy_tx(2, 10, 0),
+            legacy_tx(3, 10, 0),
+        ];
+        let senders = vec![sender, sender, sender, other_sender, sender, sender];
+
+        let (filtered_txs, filtered_senders, discarded, _) = filter_invalid_txs(
+            &db,
+            txs.clone(),
+            senders.clone(),
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            Some(2),
+            None,
+            false,
+        );
+        assert_eq!(filtered_txs, vec![txs[0].clone(), txs[2].clone(), txs[3].clone()]);
+        assert_eq!(filtered_senders, vec![sender, sender, other_sender]);
+        assert_eq!(
+            discarded,
+            vec![
+                (1, TxFilterReason::NonceMismatch { expected: 1, got: 5 }),
+                (4, TxFilterReason::PerSenderLimitExceeded),
+                (5, TxFilterReason::PerSenderLimitExceeded),
+            ]
+        );
+
+        // Unlimited by default
+        let (filtered_txs, _, _, _) = filter_invalid_txs(
+            &db,
+            txs,
+            senders,
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            false,
+        );
+        assert_eq!(filtered_txs.len(), 5);
+    }
+
+    #[test]
+    fn test_duplicate_transaction() {
+        let sender = Address::with_last_byte(1);
+        let db = db_with_accounts(&[(sender, 0, GAS_LIMIT * 10 * 3)]);
+        let txs = vec![legacy_tx(0, 10, 0), legacy_tx(1, 10, 0), legacy_tx(0, 10, 0)];
+        assert_eq!(txs[0].hash(), txs[2].hash());
+
+        let (filtered_txs, filtered_senders, discarded, _) = filter_invalid_txs(
+            &db,
+            txs.clone(),
+            vec![sender; 3],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            false,
+        );
+        assert_eq!(filtered_txs, txs[..2]);
+        assert_eq!(filtered_senders, vec![sender; 2]);
+        assert_eq!(discarded, vec![(2, TxFilterReason::DuplicateTransaction)]);
+    }
+
+    #[test]
+    fn test_sender_denylist() {
+        let sender = Address::with_last_byte(1);
+        let denied = Address::with_last_byte(2);
+        let balance = GAS_LIMIT * 10 + 2;
+        let db = db_with_accounts(&[(sender, 0, balance), (denied, 0, balance)]);
+        let txs = vec![legacy_tx(0, 10, 1), legacy_tx(0, 10, 2)];
+        let filter = |sender_denylist| {
+            filter_invalid_txs(
+                &db,
+                txs.clone(),
+                vec![denied, sender],
+                None,
+                BASE_FEE,
+                None,
+                CHAIN_ID,
+                true,
+                BLOCK_GAS_LIMIT,
+                None,
+                None,
+                None,
+                sender_denylist,
+                true,
+            )
+        };
+
+        let (filtered_txs, filtered_senders, discarded, _) =
+            filter(Some(&HashSet::from_iter([denied])));
+        assert_eq!(filtered_txs, txs[1..]);
+        assert_eq!(filtered_senders, vec![sender]);
+        assert_eq!(discarded, vec![(0, TxFilterReason::SenderDenied)]);
+
+        // Nothing is denied without a denylist
+        let (filtered_txs, _, discarded, _) = filter(None);
+        assert_eq!(filtered_txs, txs);
+        assert!(discarded.is_empty());
+    }
+
+    #[test]
+    fn test_per_tx_gas_cap() {
+        let sender = Address::with_last_byte(1);
+        let other_sender = Address::with_last_byte(2);
+        let db =
+            db_with_accounts(&[(sender, 0, GAS_LIMIT * 20), (other_sender, 0, GAS_LIMIT * 20)]);
+        let over_cap_tx = sign(EthTransaction::Legacy(TxLegacy {
+            chain_id: Some(CHAIN_ID),
+            gas_price: 10,
+            gas_limit: GAS_LIMIT + 1,
+            to: TxKind::Call(Address::ZERO),
+            ..Default::default()
+        }));
+        let txs = vec![over_cap_tx, legacy_tx(0, 10, 0)];
+        let filter = |max_tx_gas_limit| {
+            filter_invalid_txs(
+                &db,
+                txs.clone(),
+                vec![sender, other_sender],
+                None,
+                BASE_FEE,
+                None,
+                CHAIN_ID,
+                true,
+                BLOCK_GAS_LIMIT,
+                None,
+                max_tx_gas_limit,
+                None,
+                None,
+                true,
+            )
+        };
+
+        let (filtered_txs, filtered_senders, discarded, _) = filter(Some(GAS_LIMIT));
+        assert_eq!(filtered_txs, txs[1..]);
+        assert_eq!(filtered_senders, vec![other_sender]);
+        assert_eq!(
+            discarded,
+            vec![(0, TxFilterReason::PerTxGasCapExceeded { cap: GAS_LIMIT, got: GAS_LIMIT + 1 })]
+        );
+
+        // The transactions are only bounded by the block gas limit without a cap
+        let (filtered_txs, _, discarded, _) = filter(None);
+        assert_eq!(filtered_txs, txs);
+        assert!(discarded.is_empty());
+    }
+
+    fn blob_sidecar(data: &[u8]) -> BlobTransactionSidecar {
+        SidecarBuilder::<SimpleCoder>::from_slice(data).build().unwrap()
+    }
+
+    fn blob_tx(nonce: u64, sidecar: &BlobTransactionSidecar) -> TransactionSigned {
+        blob_tx_with_blob_fee(nonce, sidecar, 0)
+    }
+
+    fn blob_tx_with_blob_fee(
+        nonce: u64,
+        sidecar: &BlobTransactionSidecar,
+        max_fee_per_blob_gas: u128,
+    ) -> TransactionSigned {
+        sign(EthTransaction::Eip4844(TxEip4844 {
+            chain_id: CHAIN_ID,
+            nonce,
+            max_fee_per_gas: BASE_FEE.into(),
+            max_fee_per_blob_gas,
+            gas_limit: GAS_LIMIT,
+            blob_versioned_hashes: sidecar.versioned_hashes().collect(),
+            ..Default::default()
+        }))
+    }
+
+    #[test]
+    fn test_blob_sidecars() {
+        let sender = Address::with_last_byte(1);
+        let other_sender = Address::with_last_byte(2);
+        let db = db_with_accounts(&[
+            (sender, 0, GAS_LIMIT * BASE_FEE * 3),
+            (other_sender, 0, GAS_LIMIT * BASE_FEE),
+        ]);
+        let sidecar = blob_sidecar(b"valid blob");
+        let txs = vec![
+            blob_tx(0, &sidecar),
+            // Committed to another blob than the one carried by the sidecar
+            blob_tx(1, &blob_sidecar(b"committed blob")),
+            blob_tx(0, &sidecar),
+        ];
+        let blob_sidecars = HashMap::from_iter([
+            (*txs[0].hash(), sidecar.clone()),
+            (*txs[1].hash(), blob_sidecar(b"carried blob")),
+        ]);
+
+        let (filtered_txs, filtered_senders, discarded, _) = filter_invalid_txs(
+            &db,
+            txs.clone(),
+            vec![sender, sender, other_sender],
+            Some(&blob_sidecars),
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert_eq!(filtered_txs, vec![txs[0].clone()]);
+        assert_eq!(filtered_senders, vec![sender]);
+        assert_eq!(
+            discarded,
+            vec![(1, TxFilterReason::InvalidBlobSidecar), (2, TxFilterReason::MissingBlobSidecar)]
+        );
+
+        // The sidecars are not validated if not provided
+        let (filtered_txs, _, discarded, _) = filter_invalid_txs(
+            &db,
+            txs.clone(),
+            vec![sender, sender, other_sender],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert_eq!(filtered_txs, txs);
+        assert!(discarded.is_empty());
+    }
+
+    #[test]
+    fn test_blob_base_fee() {
+        const BLOB_BASE_FEE: u128 = 5;
+        let sender = Address::with_last_byte(1);
+        let poor_sender = Address::with_last_byte(2);
+        let sidecar = blob_sidecar(b"blob");
+        let txs = vec![
+            blob_tx_with_blob_fee(0, &sidecar, BLOB_BASE_FEE - 1),
+            blob_tx_with_blob_fee(0, &sidecar, BLOB_BASE_FEE + 1),
+            blob_tx_with_blob_fee(0, &sidecar, BLOB_BASE_FEE + 1),
+        ];
+        // The balance must cover the blob gas at the max fee per blob gas
+        let blob_gas = txs[1].blob_gas_used().unwrap();
+        let need = GAS_LIMIT * BASE_FEE + blob_gas * (BLOB_BASE_FEE as u64 + 1);
+        let db = db_with_accounts(&[(sender, 0, need), (poor_sender, 0, need - 1)]);
+
+        let (filtered_txs, filtered_senders, discarded, _) = filter_invalid_txs(
+            &db,
+            txs.clone(),
+            vec![sender, sender, poor_sender],
+            None,
+            BASE_FEE,
+            Some(BLOB_BASE_FEE),
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert_eq!(filtered_txs, vec![txs[1].clone()]);
+        assert_eq!(filtered_senders, vec![sender]);
+        assert_eq!(
+            discarded,
+            vec![
+                (
+                    0,
+                    TxFilterReason::BlobFeeTooLow {
+                        max_fee_per_blob_gas: BLOB_BASE_FEE - 1,
+                        blob_base_fee: BLOB_BASE_FEE
+                    }
+                ),
+                (
+                    2,
+                    TxFilterReason::InsufficientBalance {
+                        have: U256::from(need - 1),
+                        need: U256::from(need)
+                    }
+                ),
+            ]
+        );
+
+        // The blob fee is not checked before Cancun
+        let (filtered_txs, _, discarded, _) = filter_invalid_txs(
+            &db,
+            txs[..1].to_vec(),
+            vec![sender],
+            None,
+            BASE_FEE,
+            None,
+            CHAIN_ID,
+            true,
+            BLOCK_GAS_LIMIT,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        assert_eq!(filtered_txs.len(), 1);
+        assert!(discarded.is_empty());
+    }
+}
@@ -0,0 +1,29 @@
+use alloy_consensus::Header;
+
+/// Fork-specific fields populated in a block, for auditing that the fork schedule is applied as
+/// configured, especially around the activation timestamps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AppliedForkFeatures {
+    /// Whether the block carries withdrawals and their root, since Shanghai
+    pub shanghai_withdrawals: bool,
+    /// Whether the block carries the blob gas fields and the parent beacon block root, since
+    /// Cancun
+    pub cancun_blob: bool,
+    /// Whether the block carries the requests hash, since Prague
+    pub prague_requests: bool,
+}
+
+impl AppliedForkFeatures {
+    /// The features populated in the header of a block whose roots have been calculated. They're
+    /// read back from the header rather than from the fork schedule, so that a field missed by
+    /// the pipeline shows up as a feature not applied.
+    pub const fn from_header(header: &Header) -> Self {
+        Self {
+            shanghai_withdrawals: header.withdrawals_root.is_some(),
+            cancun_blob: header.excess_blob_gas.is_some() &&
+                header.blob_gas_used.is_some() &&
+                header.parent_beacon_block_root.is_some(),
+            prague_requests: header.requests_hash.is_some(),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::ExecutedBlockInfo;
+use alloy_primitives::B256;
+use std::{collections::VecDeque, sync::Mutex};
+
+/// Cache of the hashes of the most recently executed blocks, in ascending order of block number,
+/// so that a block pushed again by Coordinator is answered without executing it again.
+#[derive(Debug)]
+pub(crate) struct ExecutedHashCache {
+    capacity: usize,
+    hashes: Mutex<VecDeque<(u64 /* block number */, B256 /* block id */, ExecutedBlockInfo)>>,
+}
+
+impl ExecutedHashCache {
+    /// Nothing is cached if `capacity` is zero.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { capacity, hashes: Mutex::new(VecDeque::with_capacity(capacity)) }
+    }
+
+    /// Record the hash of the executed block, evicting the oldest one if the cache is full.
+    /// Blocks are sealed in order, so `block_number` is always greater than the cached ones.
+    pub(crate) fn insert(&self, block_number: u64, block_id: B256, block_info: ExecutedBlockInfo) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut hashes = self.hashes.lock().unwrap();
+        if hashes.len() == self.capacity {
+            hashes.pop_front();
+        }
+        hashes.push_back((block_number, block_id, block_info));
+    }
+
+    /// Returns `None` if the block is not executed yet or has been evicted.
+    pub(crate) fn get(&self, block_id: B256) -> Option<ExecutedBlockInfo> {
+        let hashes = self.hashes.lock().unwrap();
+        hashes.iter().rev().find(|(_, id, _)| *id == block_id).map(|(_, _, info)| *info)
+    }
+
+    /// Discard the hashes of the blocks above `block_number`, which have been rolled back.
+    pub(crate) fn rollback_to(&self, block_number: u64) {
+        self.hashes.lock().unwrap().retain(|(number, _, _)| *number <= block_number);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_executed_hash_cache() {
+        let info =
+            ExecutedBlockInfo { block_hash: B256::with_last_byte(10), gas_used: 0, tx_count: 0 };
+        let cache = ExecutedHashCache::new(2);
+        for number in 1..=3 {
+            cache.insert(number, B256::with_last_byte(number as u8), info);
+        }
+        assert_eq!(cache.get(B256::with_last_byte(1)), None);
+        assert_eq!(cache.get(B256::with_last_byte(3)), Some(info));
+
+        cache.rollback_to(2);
+        assert_eq!(cache.get(B256::with_last_byte(2)), Some(info));
+        assert_eq!(cache.get(B256::with_last_byte(3)), None);
+
+        let disabled = ExecutedHashCache::new(0);
+        disabled.insert(1, B256::with_last_byte(1), info);
+        assert_eq!(disabled.get(B256::with_last_byte(1)), None);
+    }
+}
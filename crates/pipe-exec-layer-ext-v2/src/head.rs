@@ -0,0 +1,96 @@
+use alloy_primitives::B256;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The latest block made canonical by the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipeHead {
+    /// Number of the block
+    pub block_number: u64,
+    /// Hash of the block
+    pub block_hash: B256,
+    /// Id of the block. `None` for the block which the pipeline started from, if its id is not
+    /// known yet.
+    pub block_id: Option<B256>,
+    /// How long ago the block was made canonical, or the pipeline was started if no block has
+    /// been made canonical since then
+    pub last_commit_elapsed: Duration,
+}
+
+#[derive(Debug)]
+struct CommittedHead {
+    block_number: u64,
+    block_hash: B256,
+    block_id: Option<B256>,
+    committed_at: Instant,
+}
+
+/// Shared cell of the pipeline head, updated whenever a block is made canonical.
+#[derive(Debug)]
+pub(crate) struct HeadCell {
+    inner: Mutex<CommittedHead>,
+}
+
+impl HeadCell {
+    pub(crate) fn new(block_number: u64, block_hash: B256, started_at: Instant) -> Self {
+        Self {
+            inner: Mutex::new(CommittedHead {
+                block_number,
+                block_hash,
+                block_id: None,
+                committed_at: started_at,
+            }),
+        }
+    }
+
+    /// Fill the id of the block which the pipeline started from, once loaded from storage.
+    pub(crate) fn init_block_id(&self, block_id: Option<B256>) {
+        self.inner.lock().unwrap().block_id = block_id;
+    }
+
+    pub(crate) fn commit(
+        &self,
+        block_number: u64,
+        block_hash: B256,
+        block_id: B256,
+        committed_at: Instant,
+    ) {
+        *self.inner.lock().unwrap() =
+            CommittedHead { block_number, block_hash, block_id: Some(block_id), committed_at };
+    }
+
+    pub(crate) fn get(&self) -> PipeHead {
+        let head = self.inner.lock().unwrap();
+        PipeHead {
+            block_number: head.block_number,
+            block_hash: head.block_hash,
+            block_id: head.block_id,
+            last_commit_elapsed: head.committed_at.elapsed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_head_cell() {
+        let started_at = Instant::now();
+        let cell = HeadCell::new(10, B256::with_last_byte(10), started_at);
+        cell.init_block_id(Some(B256::with_last_byte(100)));
+        let head = cell.get();
+        assert_eq!(head.block_number, 10);
+        assert_eq!(head.block_id, Some(B256::with_last_byte(100)));
+
+        std::thread::sleep(Duration::from_millis(10));
+        cell.commit(11, B256::with_last_byte(11), B256::with_last_byte(101), Instant::now());
+        let head = cell.get();
+        assert_eq!(head.block_number, 11);
+        assert_eq!(head.block_hash, B256::with_last_byte(11));
+        assert_eq!(head.block_id, Some(B256::with_last_byte(101)));
+        assert!(head.last_commit_elapsed < started_at.elapsed());
+    }
+}
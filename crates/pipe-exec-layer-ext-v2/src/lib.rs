@@ -1,41 +1,118 @@
 //! Pipeline execution layer extension
+mod beacon_root;
+mod block_dump;
+mod cancel;
 #[macro_use]
 mod channel;
+mod config;
+mod filter;
+mod fork_features;
+mod hash_cache;
+mod head;
 mod metrics;
+mod outcome_cache;
+mod prefetch;
+mod prev_randao;
+mod reorder;
+mod self_test;
+mod simulate;
+mod state_diff;
+mod state_reads;
+mod status;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+mod timings;
+mod trace;
 
-use channel::Channel;
-use metrics::PipeExecLayerMetrics;
+pub use beacon_root::{ParentBeaconBlockRoot, ParentBlockIdAsBeaconRoot};
+pub use block_dump::{
+    load_block_dump, BlockDump, BlockDumpError, BLOCK_DUMP_MAGIC, BLOCK_DUMP_VERSION,
+};
+use cancel::Cancellations;
+use channel::{Channel, ChannelTimeout};
+pub use config::{
+    PipeExecLayerConfig, PipeExecLayerConfigError, ZeroCoinbasePolicy, DEFAULT_BLOCK_GAS_LIMIT,
+    DEFAULT_BLOCK_STATUS_CAPACITY, DEFAULT_BUNDLE_STATE_RETENTION,
+    DEFAULT_CANONICAL_NOTIFICATION_CAPACITY, DEFAULT_EXECUTED_BLOCK_CACHE_CAPACITY,
+    DEFAULT_EXECUTION_OUTCOME_CACHE_CAPACITY, DEFAULT_MAX_CONSECUTIVE_EXECUTION_FAILURES,
+    DEFAULT_MAX_IN_FLIGHT_BLOCKS, DEFAULT_MAX_REORG_DEPTH, DEFAULT_ORDERED_BLOCK_CHANNEL_CAPACITY,
+    DEFAULT_PARALLEL_FILTER_THRESHOLD, DEFAULT_REORDER_BUFFER_CAPACITY,
+    DEFAULT_SLOW_BLOCK_INTERVAL, DEFAULT_STAGE_TIMINGS_CAPACITY, DEFAULT_VERIFY_ATTEMPTS,
+};
+pub use filter::TxFilterReason;
+use filter::{account_drifts, filter_invalid_txs};
+pub use fork_features::AppliedForkFeatures;
+use hash_cache::ExecutedHashCache;
+use head::HeadCell;
+pub use head::PipeHead;
+use metrics::{bundle_state_size, trie_updates_node_count, PipeExecLayerMetrics, Throughput};
+pub use metrics::{MetricsSink, NoopMetricsSink};
+use outcome_cache::ExecutionOutcomeCache;
+use prefetch::{prefetch_addresses, prefetch_state};
+pub use prev_randao::{AcceptAnyPrevRandao, InvalidPrevRandao, PrevRandaoValidator};
+use reorder::ReorderBuffer;
+pub use self_test::self_test;
+use simulate::Simulator;
+pub use simulate::{ComputedBlockHash, SimulationError, SimulationResult};
+use state_diff::state_diff;
+pub use state_diff::{AccountDiff, StorageDiff};
+use state_reads::{CountingStateView, StateReads};
+pub use status::BlockStatus;
+use status::BlockStatusMap;
+pub use timings::StageTimings;
+use timings::StageTimingsBuffer;
+use trace::trace_txs;
+pub use trace::{TxTrace, TxTraceSink};
 
 use alloy_consensus::{
-    constants::EMPTY_WITHDRAWALS, BlockHeader, Header, Transaction, EMPTY_OMMER_ROOT_HASH,
+    constants::EMPTY_WITHDRAWALS, BlockHeader, Header, Transaction, TxReceipt,
+    EMPTY_OMMER_ROOT_HASH,
+};
+use alloy_eips::{eip4844::BlobTransactionSidecar, eip4895::Withdrawals};
+use alloy_primitives::{
+    map::{HashMap, HashSet},
+    Address, Bloom, TxHash, B256, U256,
 };
-use alloy_eips::{eip4895::Withdrawals, merge::BEACON_NONCE};
-use alloy_primitives::{Address, B256, U256};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use reth_chain_state::ExecutedBlockWithTrieUpdates;
-use reth_chainspec::{ChainSpec, EthereumHardforks};
+use reth_chainspec::{ChainSpec, EthChainSpec, EthereumHardforks};
 use reth_ethereum_primitives::{Block, BlockBody, Receipt, TransactionSigned};
 use reth_evm::{
     database::*,
-    execute::{BlockExecutorProvider, Executor},
-    parallel_database, ConfigureEvmEnv, NextBlockEnvAttributes,
+    execute::{BlockExecutionError, BlockExecutorProvider, Executor},
+    parallel_database, serial_database, ConfigureEvmEnv, NextBlockEnvAttributes,
 };
 use reth_evm_ethereum::{execute::EthExecutorProvider, EthEvmConfig};
 use reth_execution_types::{BlockExecutionOutput, ExecutionOutcome};
+use reth_metrics::metrics::{Gauge, Histogram};
 use reth_primitives::{EthPrimitives, NodePrimitives};
 use reth_primitives_traits::{
     proofs::{self},
     Block as _, RecoveredBlock,
 };
-use revm::primitives::{AccountInfo, HashMap, HashSet};
-use std::{any::Any, collections::BTreeMap, sync::Arc, time::Instant};
+use reth_trie::{updates::TrieUpdates, HashedPostState};
+use revm::db::{BundleState, WrapDatabaseRef};
+use std::{
+    any::Any,
+    collections::{BTreeMap, VecDeque},
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Instant, SystemTime},
+};
 
 use once_cell::sync::{Lazy, OnceCell};
 
-use gravity_storage::GravityStorage;
-use tokio::sync::{
-    mpsc::{UnboundedReceiver, UnboundedSender},
-    oneshot,
+use gravity_storage::{GravityStorage, GravityStorageError};
+use tokio::{
+    sync::{
+        broadcast,
+        mpsc::{Receiver, Sender},
+        oneshot, watch, OwnedSemaphorePermit, Semaphore,
+    },
+    task::JoinSet,
 };
 
 use tracing::*;
@@ -48,6 +125,18 @@ pub struct ExecutedBlockMeta {
     pub block_hash: B256,
 }
 
+/// Executed block pushed to Coordinator for verification, along with the figures Coordinator may
+/// schedule the next blocks by, so that it doesn't need to read the block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutedBlockInfo {
+    /// Block hash of the executed block, which is what Coordinator verifies
+    pub block_hash: B256,
+    /// Gas used by the transactions of the block
+    pub gas_used: u64,
+    /// Number of transactions in the block, excluding the ones discarded before execution
+    pub tx_count: usize,
+}
+
 #[derive(Debug)]
 pub struct OrderedBlock {
     /// BlockId of the parent block generated by Gravity SDK
@@ -63,112 +152,875 @@ pub struct OrderedBlock {
     pub transactions: Vec<TransactionSigned>,
     /// Senders of the transactions in the block
     pub senders: Vec<Address>,
+    /// Sidecars of the blob transactions in the block by transaction hash, against which the
+    /// blob transactions are validated. `None` if Coordinator doesn't carry the sidecars, in which
+    /// case the KZG commitments are not validated.
+    pub blob_sidecars: Option<HashMap<TxHash, BlobTransactionSidecar>>,
+    /// Whether Coordinator has validated the transactions against the parent state, so that they
+    /// are executed as is without being filtered. If the block fails to execute, it's executed
+    /// again with the transactions filtered.
+    pub presumed_valid: bool,
 }
 
 #[derive(Debug)]
 pub enum PipeExecLayerEvent<N: NodePrimitives> {
     /// Make executed block canonical
     MakeCanonical(ExecutedBlockWithTrieUpdates<N>, oneshot::Sender<()>),
+    /// The block has been executed and sealed, but not verified by Coordinator yet. Indexers can
+    /// build logs indexes from the receipts without re-reading them from the database.
+    BlockExecuted {
+        /// Number of the sealed block
+        block_number: u64,
+        /// Hash of the sealed block
+        block_hash: B256,
+        /// Receipts of the transactions in the block
+        receipts: Arc<Vec<N::Receipt>>,
+        /// Fork-specific fields populated in the block
+        fork_features: AppliedForkFeatures,
+    },
+    /// The ordered block failed to execute. The pipeline has rolled back to the parent of the
+    /// failed block by then, since no later block can be executed on top of it, so that
+    /// Coordinator can deliver the block again. Once
+    /// `PipeExecLayerConfig::max_consecutive_execution_failures` is reached, the pipeline is
+    /// halted instead, and `Halted` follows.
+    ExecutionFailed {
+        /// Id of the ordered block which failed to execute
+        block_id: B256,
+        /// The execution error
+        error: String,
+    },
+    /// The pipeline has been halted after a failure and no longer accepts ordered blocks, so the
+    /// node needs the attention of the operators.
+    Halted {
+        /// Why the pipeline has been halted
+        reason: String,
+    },
+    /// Net state changes of a block which has been made canonical, sent only if
+    /// `PipeExecLayerConfig::emit_state_diff` is set. Lets light consumers track balances and
+    /// storage without the full execution outcome.
+    StateDiff {
+        /// Number of the canonical block
+        block_number: u64,
+        /// Accounts changed by the block, sorted by address
+        accounts: Vec<AccountDiff>,
+        /// Storage slots changed by the block, sorted by address and slot
+        storage: Vec<StorageDiff>,
+    },
+    /// Coordinator delivered a block forking from a canonical block below the head, and the
+    /// canonical blocks above it have been rolled back in the pipeline. EL switches to the new
+    /// branch once its first block is made canonical.
+    Reorg {
+        /// Number of the canonical head before the reorg
+        from: u64,
+        /// Number of the block which the new branch forks from
+        to: u64,
+    },
+}
+
+/// Notification of a block which has been made canonical, broadcast to the subscribers of
+/// [`PipeExecLayerExt::subscribe_canonical`].
+#[derive(Debug, Clone)]
+pub struct Canonicalized {
+    /// Id of the ordered block which the block is executed from
+    pub block_id: B256,
+    /// The canonical block
+    pub block: Arc<RecoveredBlock<Block>>,
 }
 
 #[derive(Debug)]
 pub struct ExecutionArgs {
     pub block_number_to_block_id: BTreeMap<u64, B256>,
 }
+
+/// Verification result of an executed block sent by Coordinator
+#[derive(Debug)]
+enum Verification {
+    /// The block hash agreed by Coordinator
+    Verified(B256),
+    /// The block is rejected by Coordinator, who is notified once the pipeline has rolled back
+    Rejected(oneshot::Sender<()>),
+}
+
+/// Outcome of verifying an executed block
+#[derive(Debug)]
+enum VerifyOutcome {
+    Verified,
+    /// The block is rejected by Coordinator, or its hash mismatches the verified one
+    Rejected {
+        rolled_back_tx: Option<oneshot::Sender<()>>,
+    },
+}
+
+/// Blocks accepted by the service are discarded if a rollback happens before they become
+/// canonical, which starts a new epoch.
+#[derive(Debug)]
+struct Epoch {
+    /// Bumped by each rollback
+    id: u64,
+    /// Number of the latest block accepted by the service
+    latest_block_number: u64,
+    /// Id of the latest block accepted by the service, which is the parent id of the next block.
+    /// `None` until loaded from storage at startup.
+    latest_block_id: Option<B256>,
+    /// Blocks received ahead of their turn
+    reorder_buffer: ReorderBuffer,
+    /// Each in-flight block holds a receiver, so that a rollback can wait until all blocks of the
+    /// previous epoch have been discarded
+    alive_tx: watch::Sender<()>,
+}
+
+impl Epoch {
+    /// Check that the block follows the latest accepted block.
+    fn check_parent_id(&self, ordered_block: &OrderedBlock) -> Result<(), ProcessError> {
+        match self.latest_block_id {
+            Some(latest_block_id) if latest_block_id != ordered_block.parent_id => {
+                Err(ProcessError::ParentIdMismatch {
+                    block_id: ordered_block.id,
+                    expected: latest_block_id,
+                    got: ordered_block.parent_id,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Held by an in-flight block, for the epoch in which it was accepted
+#[derive(Debug)]
+struct EpochGuard {
+    id: u64,
+    _alive_rx: watch::Receiver<()>,
+}
+
+/// Why an ordered block was not made canonical
+#[derive(Debug, thiserror::Error)]
+enum ProcessError {
+    /// The block has been discarded by a rollback, or the pipeline has been closed
+    #[error("block processing aborted")]
+    Aborted,
+    /// The block failed to execute, the last of `failures` consecutive execution failures
+    #[error("failed to execute block {block_id} ({failures} consecutive failures): {error}")]
+    ExecutionFailed { block_id: B256, failures: usize, error: BlockExecutionError },
+    /// The state view of the parent block doesn't belong to the parent id of the block
+    #[error("parent id mismatch for block {block_id}, state view of {expected}, got {got}")]
+    ParentIdMismatch { block_id: B256, expected: B256, got: B256 },
+    /// Coordinator didn't verify the executed block hash in time
+    #[error("verification of block {block_id} timed out after {attempts} attempts")]
+    VerificationTimeout { block_id: B256, attempts: usize },
+    /// EL failed to make the block canonical
+    #[error("failed to make block {block_id} canonical: {error}")]
+    MakeCanonicalFailed { block_id: B256, error: MakeCanonicalError },
+}
+
+/// Why the latest canonical blocks failed to be replayed on startup
+#[derive(Debug, thiserror::Error)]
+enum StartupReplayError {
+    /// The storage doesn't provide the canonical block
+    #[error("canonical block {0} unavailable")]
+    BlockUnavailable(u64),
+    /// The block id of the canonical block is not passed in `ExecutionArgs`
+    #[error("block id of block {0} unavailable")]
+    BlockIdUnavailable(u64),
+    /// The storage doesn't provide the historical state
+    #[error(transparent)]
+    Storage(#[from] GravityStorageError),
+    /// The canonical block failed to execute again
+    #[error("failed to execute block {block_number}: {error}")]
+    ExecutionFailed { block_number: u64, error: BlockExecutionError },
+    /// The replayed block doesn't hash to the stored one
+    #[error("hash mismatch for block {block_number}, stored {expected}, replayed {got}")]
+    HashMismatch { block_number: u64, expected: B256, got: B256 },
+}
+
+/// Why EL failed to make an executed block canonical
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+enum MakeCanonicalError {
+    /// The event receiver of EL has been dropped
+    #[error("event channel closed")]
+    EventChannelClosed,
+    /// EL dropped the event without making the block canonical
+    #[error("make canonical cancelled")]
+    Cancelled,
+}
+
+/// Bytes of the bundle state of a block in flight, counted against
+/// `PipeExecLayerConfig::max_in_flight_bundle_state_bytes` until dropped
+#[derive(Debug)]
+struct BundleStateReservation<'a> {
+    in_flight_bytes: &'a AtomicUsize,
+    gauge: &'a Gauge,
+    bytes: usize,
+}
+
+impl Drop for BundleStateReservation<'_> {
+    fn drop(&mut self) {
+        self.in_flight_bytes.fetch_sub(self.bytes, Ordering::Relaxed);
+        self.gauge.decrement(self.bytes as f64);
+    }
+}
+
+/// The canonical block to roll back to, along with the states to reset the barriers
+#[derive(Debug, Clone)]
+struct RollbackTarget {
+    block_number: u64,
+    block_id: B256,
+    header: Header,
+    block_hash: B256,
+    start_execute_time: Instant,
+    finish_commit_time: Instant,
+}
+
 /// Owned by EL
 #[derive(Debug)]
-struct PipeExecService<Storage: GravityStorage> {
+struct PipeExecService<
+    Storage: GravityStorage,
+    ExecutorProvider: BlockExecutorProvider<Primitives = EthPrimitives>,
+    EvmConfig: ConfigureEvmEnv<Header = Header> = EthEvmConfig,
+> {
     /// Immutable part of the state
-    core: Arc<Core<Storage>>,
+    core: Arc<Core<Storage, ExecutorProvider, EvmConfig>>,
     /// Receive ordered block from Coordinator
-    ordered_block_rx: UnboundedReceiver<OrderedBlock>,
+    ordered_block_rx: Receiver<OrderedBlock>,
     /// Receive the execution init args from GravitySDK
     execution_args_rx: oneshot::Receiver<ExecutionArgs>,
+    /// Receive the shutdown request from Coordinator
+    shutdown_rx: watch::Receiver<bool>,
+    /// Notify Coordinator that the service has stopped
+    stopped_tx: watch::Sender<bool>,
+    /// Receive the block number to pause at from Coordinator, or `None` to resume
+    pause_at_rx: watch::Receiver<Option<u64>>,
+    /// One permit for each block being processed, bounding the blocks in flight
+    in_flight_permits: Arc<Semaphore>,
 }
 
+/// `ExecutorProvider` executes the ordered blocks, whose executors are created with
+/// `parallel_database! { state }` over the state view of the parent block, or with
+/// `serial_database!` if `PipeExecLayerConfig::sequential_execution` is set.
+/// `EvmConfig` derives the block env of each ordered block from its parent header.
 #[derive(Debug)]
-struct Core<Storage: GravityStorage> {
+struct Core<
+    Storage: GravityStorage,
+    ExecutorProvider: BlockExecutorProvider<Primitives = EthPrimitives>,
+    EvmConfig: ConfigureEvmEnv<Header = Header> = EthEvmConfig,
+> {
     /// Send executed block hash to Coordinator
-    executed_block_hash_tx: Arc<Channel<B256 /* block id */, B256 /* block hash */>>,
+    executed_block_hash_tx: Arc<Channel<B256 /* block id */, ExecutedBlockInfo>>,
     /// Receive verified block hash from Coordinator
-    verified_block_hash_rx: Arc<Channel<B256 /* block id */, B256 /* block hash */>>,
+    verified_block_hash_rx: Arc<Channel<B256 /* block id */, Verification>>,
     storage: Storage,
-    evm_config: EthEvmConfig,
+    evm_config: EvmConfig,
+    executor_provider: ExecutorProvider,
     chain_spec: Arc<ChainSpec>,
     event_tx: std::sync::mpsc::Sender<PipeExecLayerEvent<EthPrimitives>>,
+    /// Broadcast the blocks made canonical, shared with `PipeExecLayerExt`
+    canonical_tx: broadcast::Sender<Canonicalized>,
     execute_block_barrier: Channel<u64 /* block number */, (Header, Instant)>,
     merklize_barrier: Channel<u64 /* block number */, ()>,
     seal_barrier: Channel<u64 /* block number */, B256 /* block hash */>,
     make_canonical_barrier: Channel<u64 /* block number */, Instant>,
+    epoch: RwLock<Epoch>,
+    /// Header of the latest executed block, whose roots are not filled yet
+    latest_executed_header: Mutex<Header>,
+    config: PipeExecLayerConfig,
+    /// Thread pool for filtering and executing transactions, or `None` to use the global pool
+    executor_pool: Option<rayon::ThreadPool>,
     metrics: PipeExecLayerMetrics,
+    /// Moving averages of the throughput of the committed blocks
+    throughput: Mutex<Throughput>,
+    /// Approximate bytes of the bundle states held by the blocks in flight
+    in_flight_bundle_state_bytes: AtomicUsize,
+    /// Number of blocks which failed to execute since a block was last executed successfully
+    consecutive_execution_failures: AtomicUsize,
+    /// Whether the pipeline has been halted after a failure, in which case the service stops
+    /// receiving ordered blocks
+    halted: AtomicBool,
+    /// The latest canonical block, shared with `PipeExecLayerApi`
+    head: Arc<HeadCell>,
+    /// The most recent canonical blocks, which a reorg can roll back to
+    canonical_blocks: Mutex<BTreeMap<u64 /* block number */, RollbackTarget>>,
+    /// Stage timings of the most recently committed blocks, shared with `PipeExecLayerExt`
+    stage_timings: Arc<StageTimingsBuffer>,
+    /// Hashes of the most recently executed blocks, which answer the blocks pushed again
+    executed_hashes: ExecutedHashCache,
+    /// Execution outcomes of the most recently committed blocks, shared with `PipeExecLayerApi`
+    execution_outcomes: Arc<ExecutionOutcomeCache>,
+    /// Status of the blocks in flight and the most recently committed ones, shared with
+    /// `PipeExecLayerApi`
+    block_statuses: Arc<BlockStatusMap>,
+    /// Senders whose transactions are discarded, if enabled, shared with `PipeExecLayerApi`
+    sender_denylist: Option<Arc<RwLock<HashSet<Address>>>>,
+    /// Executed blocks waiting for verification, which `PipeExecLayerApi::force_make_canonical`
+    /// can verify in place of Coordinator
+    awaiting_verification: Arc<Mutex<BTreeMap<u64 /* block number */, ExecutedBlockMeta>>>,
+    /// Blocks cancelled through `PipeExecLayerApi::cancel`, shared with `PipeExecLayerApi`
+    cancellations: Arc<Cancellations>,
 }
 
-impl<Storage: GravityStorage> PipeExecService<Storage> {
-    async fn run(mut self, mut latest_block_number: u64) {
-        self.core.init_storage(self.execution_args_rx.await.unwrap());
+impl<
+        Storage: GravityStorage,
+        ExecutorProvider: BlockExecutorProvider<Primitives = EthPrimitives>,
+        EvmConfig: ConfigureEvmEnv<Header = Header>,
+    > PipeExecService<Storage, ExecutorProvider, EvmConfig>
+{
+    async fn run(mut self) {
+        let Ok(execution_args) = self.execution_args_rx.await else {
+            // Coordinator failed during initialization
+            error!(target: "PipeExecService.run",
+                "execution args sender dropped before sending, shutting down"
+            );
+            self.core.close();
+            self.stopped_tx.send_replace(true);
+            return;
+        };
+        self.core.init_storage(execution_args);
+        self.core.init_latest_block_id();
+        let start_time = Instant::now();
+        match self.core.replay_canonical_blocks() {
+            Ok(0) => {}
+            Ok(replayed) => info!(target: "PipeExecService.run",
+                replayed=?replayed,
+                elapsed=?start_time.elapsed(),
+                "latest canonical blocks replayed and verified"
+            ),
+            Err(err) => {
+                // The storage may be corrupted, so no block is executed on top of it
+                error!(target: "PipeExecService.run",
+                    %err,
+                    "failed to replay latest canonical blocks, shutting down"
+                );
+                self.core.close();
+                self.stopped_tx.send_replace(true);
+                return;
+            }
+        }
+        let mut tasks = JoinSet::new();
+        // Accepted blocks above the pause target, which are processed once resumed, or waiting
+        // for an in-flight block to finish
+        let mut held_blocks: VecDeque<(OrderedBlock, EpochGuard)> = VecDeque::new();
+        let mut paused = false;
+        let mut start_time = Instant::now();
         loop {
-            let start_time = Instant::now();
-            let ordered_block = match self.ordered_block_rx.recv().await {
-                Some(ordered_block) => ordered_block,
-                None => {
-                    self.core.executed_block_hash_tx.close();
-                    self.core.execute_block_barrier.close();
-                    self.core.merklize_barrier.close();
-                    self.core.make_canonical_barrier.close();
-                    return;
+            if self.core.halted.load(Ordering::Relaxed) {
+                warn!(target: "PipeExecService.run",
+                    in_flight_blocks=?tasks.len(),
+                    "pipeline halted, no more ordered blocks are accepted"
+                );
+                self.ordered_block_rx.close();
+                while tasks.join_next().await.is_some() {}
+                break;
+            }
+            let pause_at = *self.pause_at_rx.borrow_and_update();
+            // Whether the next block is held only because `max_in_flight_blocks`,
+            // `max_canonical_lag` or `max_in_flight_bundle_state_bytes` is reached, in which case
+            // no more blocks are received until an in-flight block finishes
+            let mut throttled = false;
+            while let Some(block_number) = held_blocks
+                .front()
+                .map(|(block, _)| block.number)
+                .filter(|number| pause_at.is_none_or(|n| *number <= n))
+            {
+                if self.core.exceeds_canonical_lag(block_number) ||
+                    self.core.exceeds_bundle_state_budget()
+                {
+                    throttled = true;
+                    break;
+                }
+                let Ok(permit) = self.in_flight_permits.clone().try_acquire_owned() else {
+                    throttled = true;
+                    break;
+                };
+                let (ordered_block, epoch) = held_blocks.pop_front().unwrap();
+                self.spawn_process(&mut tasks, ordered_block, epoch, permit);
+            }
+            let reached = (!throttled && !held_blocks.is_empty()) ||
+                pause_at
+                    .is_some_and(|n| self.core.epoch.read().unwrap().latest_block_number >= n);
+            if reached != paused {
+                paused = reached;
+                self.core.metrics.paused.set(if paused { 1.0 } else { 0.0 });
+                info!(target: "PipeExecService.run",
+                    pause_at=?pause_at,
+                    paused=?paused,
+                    "pause state changed"
+                );
+            }
+
+            let receiving = !paused && !throttled;
+            let ordered_block = tokio::select! {
+                ordered_block = self.ordered_block_rx.recv(), if receiving => match ordered_block {
+                    Some(ordered_block) => ordered_block,
+                    None => {
+                        self.core.close();
+                        break;
+                    }
+                },
+                Some(_) = tasks.join_next(), if !tasks.is_empty() => continue,
+                true = shutdown_requested(&mut self.shutdown_rx) => {
+                    info!(target: "PipeExecService.run",
+                        in_flight_blocks=?tasks.len(),
+                        "shutting down, waiting for in-flight blocks"
+                    );
+                    while tasks.join_next().await.is_some() {}
+                    self.core.close();
+                    break;
+                }
+                Ok(()) = self.pause_at_rx.changed() => continue,
+                // Paused while the api has been dropped, so it can never be resumed
+                else => {
+                    self.core.close();
+                    break;
                 }
             };
             self.core.metrics.recv_block_time_diff.record(start_time.elapsed());
-            // TODO: read latest block id from storage
-            // assert_eq!(ordered_block.parent_id, latest_block_id);
-            // latest_block_id = ordered_block.id;
-            assert_eq!(ordered_block.number, latest_block_number + 1);
-            latest_block_number = ordered_block.number;
-
-            let core = self.core.clone();
-            tokio::spawn(async move {
-                core.process(ordered_block).await;
-            });
+            start_time = Instant::now();
+            self.core.metrics.received_blocks.increment(1);
+            // Sampled right after dequeuing, so it's the backlog left behind this block
+            self.core.metrics.ordered_block_queue_depth.set(self.ordered_block_rx.len() as f64);
+            self.core.metrics.record_block_timestamp_lag(ordered_block.timestamp);
+            if ordered_block.coinbase.is_zero() &&
+                self.core.config.zero_coinbase == ZeroCoinbasePolicy::Warn
+            {
+                self.core.metrics.zero_coinbase_blocks.increment(1);
+                warn!(target: "PipeExecService.run",
+                    id=?ordered_block.id,
+                    number=?ordered_block.number,
+                    "ordered block with zero coinbase, its priority fees are burnt"
+                );
+            }
+            if self.core.resend_executed_hash(&ordered_block) {
+                continue;
+            }
+            if let Some(fork_block_number) = self.core.fork_point(&ordered_block) {
+                // The held blocks belong to the branch being discarded
+                held_blocks.clear();
+                self.core.reorg(fork_block_number).await;
+            }
+            held_blocks.extend(self.core.accept(ordered_block));
         }
+        self.stopped_tx.send_replace(true);
+    }
+
+    fn spawn_process(
+        &self,
+        tasks: &mut JoinSet<()>,
+        ordered_block: OrderedBlock,
+        epoch: EpochGuard,
+        permit: OwnedSemaphorePermit,
+    ) {
+        let core = self.core.clone();
+        let block_id = ordered_block.id;
+        core.metrics.in_flight_blocks.increment(1.0);
+        // All events of the stages of the block are nested under the span, which is entered
+        // whenever the task is polled
+        let span = info_span!("process_block", block_number = ordered_block.number, %block_id);
+        tasks.spawn(
+            async move {
+                let mut executed_gas_used = None;
+                match core.process(ordered_block, epoch, &mut executed_gas_used).await {
+                    Ok(()) => {}
+                    Err(ProcessError::Aborted) => {
+                        // The block was discarded after execution, e.g. by a rollback
+                        if let Some(gas_used) = executed_gas_used {
+                            core.metrics.wasted_blocks_total.increment(1);
+                            core.metrics.wasted_gas_total.increment(gas_used);
+                        }
+                    }
+                    Err(err) => core.halt(block_id, err),
+                }
+                core.metrics.in_flight_blocks.decrement(1.0);
+                drop(permit);
+            }
+            .instrument(span),
+        );
     }
 }
 
-const BLOCK_GAS_LIMIT_1G: u64 = 1_000_000_000;
+/// Wait until Coordinator requests shutdown. Returns `false` if the api has been dropped without
+/// requesting it.
+async fn shutdown_requested(shutdown_rx: &mut watch::Receiver<bool>) -> bool {
+    shutdown_rx.wait_for(|shutdown| *shutdown).await.is_ok()
+}
+
+/// The result of executing an ordered block.
+#[derive(Debug)]
+struct ExecuteOrderedBlockResult {
+    /// The executed block, whose roots and hashes are not filled yet
+    block: Block,
+    /// Senders of the transactions included in the block
+    senders: Vec<Address>,
+    /// The execution output of the block
+    execution_output: BlockExecutionOutput<Receipt>,
+    /// Transactions discarded before execution, with their index in the ordered block
+    discarded_txs: Vec<(usize, TxFilterReason)>,
+}
+
+impl<
+        Storage: GravityStorage,
+        ExecutorProvider: BlockExecutorProvider<Primitives = EthPrimitives>,
+        EvmConfig: ConfigureEvmEnv<Header = Header>,
+    > Core<Storage, ExecutorProvider, EvmConfig>
+{
+    /// A core detached from any pipeline, whose latest block is the default header at number
+    /// zero. Only the execution steps (e.g. `execute_ordered_block` and `calculate_roots`) are
+    /// meaningful on it, since nothing is received from or sent to Coordinator.
+    fn standalone(
+        executor_provider: ExecutorProvider,
+        evm_config: EvmConfig,
+        chain_spec: Arc<ChainSpec>,
+        storage: Storage,
+        config: PipeExecLayerConfig,
+    ) -> Self {
+        let start_time = Instant::now();
+        let metrics = PipeExecLayerMetrics::new(config.metrics_sink.as_ref());
+        let sender_denylist =
+            config.sender_denylist.clone().map(|senders| Arc::new(senders.into()));
+        Self {
+            executed_block_hash_tx: Arc::new(Channel::new()),
+            verified_block_hash_rx: Arc::new(Channel::new()),
+            storage,
+            evm_config,
+            executor_provider,
+            chain_spec,
+            event_tx: std::sync::mpsc::channel().0,
+            canonical_tx: broadcast::channel(1).0,
+            execute_block_barrier: Channel::new_with_states([(0, (Header::default(), start_time))]),
+            merklize_barrier: Channel::new_with_states([(0, ())]),
+            seal_barrier: Channel::new_with_states([(0, B256::ZERO)]),
+            make_canonical_barrier: Channel::new_with_states([(0, start_time)]),
+            epoch: RwLock::new(Epoch {
+                id: 0,
+                latest_block_number: 0,
+                latest_block_id: None,
+                reorder_buffer: ReorderBuffer::new(config.reorder_buffer_capacity),
+                alive_tx: watch::channel(()).0,
+            }),
+            latest_executed_header: Mutex::new(Header::default()),
+            config,
+            executor_pool: None,
+            metrics,
+            throughput: Mutex::default(),
+            in_flight_bundle_state_bytes: AtomicUsize::new(0),
+            consecutive_execution_failures: AtomicUsize::new(0),
+            halted: AtomicBool::new(false),
+            head: Arc::new(HeadCell::new(0, B256::ZERO, start_time)),
+            canonical_blocks: Mutex::default(),
+            stage_timings: Arc::new(StageTimingsBuffer::new(0)),
+            executed_hashes: ExecutedHashCache::new(0),
+            execution_outcomes: Arc::new(ExecutionOutcomeCache::new(0)),
+            block_statuses: Arc::new(BlockStatusMap::new(0)),
+            sender_denylist,
+            awaiting_verification: Arc::default(),
+            cancellations: Arc::default(),
+        }
+    }
+
+    /// Accept the received ordered block. Returns the blocks ready to be processed in order,
+    /// which are the block itself if it follows the latest accepted block, and the buffered blocks
+    /// following it.
+    fn accept(&self, ordered_block: OrderedBlock) -> Vec<(OrderedBlock, EpochGuard)> {
+        let mut epoch = self.epoch.write().unwrap();
+        let latest_block_number = epoch.latest_block_number;
+        let mut ready = epoch.reorder_buffer.push(latest_block_number, ordered_block).into_iter();
+        let mut accepted = Vec::with_capacity(ready.len());
+        for ordered_block in ready.by_ref() {
+            if let Err(err) = epoch.check_parent_id(&ordered_block) {
+                error!(target: "PipeExecService.run",
+                    id=?ordered_block.id,
+                    number=?ordered_block.number,
+                    error=%err,
+                    "discard ordered block which doesn't follow the latest block"
+                );
+                break;
+            }
+            epoch.latest_block_number = ordered_block.number;
+            epoch.latest_block_id = Some(ordered_block.id);
+            let guard = EpochGuard { id: epoch.id, _alive_rx: epoch.alive_tx.subscribe() };
+            accepted.push((ordered_block, guard));
+        }
+        // Put back the blocks following the discarded one, which can't be accepted either until
+        // the discarded block is replaced
+        for ordered_block in ready {
+            let latest_block_number = epoch.latest_block_number;
+            epoch.reorder_buffer.push(latest_block_number, ordered_block);
+        }
+        accepted
+    }
+
+    /// Whether the block is more than `max_canonical_lag` blocks ahead of the latest canonical
+    /// block, so that it must not be executed until the make canonical stage catches up. The
+    /// in-flight block made canonical next finishes right after the head moves, which wakes up
+    /// the service to check again.
+    fn exceeds_canonical_lag(&self, block_number: u64) -> bool {
+        self.config
+            .max_canonical_lag
+            .is_some_and(|lag| block_number > self.head.get().block_number + lag as u64)
+    }
+
+    /// Whether the bundle states of the blocks in flight exceed
+    /// `PipeExecLayerConfig::max_in_flight_bundle_state_bytes`, in which case the next block is
+    /// held until an in-flight block finishes.
+    fn exceeds_bundle_state_budget(&self) -> bool {
+        self.config.max_in_flight_bundle_state_bytes.is_some_and(|budget| {
+            self.in_flight_bundle_state_bytes.load(Ordering::Relaxed) > budget
+        })
+    }
+
+    /// Count the bundle state of an executed block as in flight until the returned reservation
+    /// is dropped.
+    fn reserve_bundle_state(&self, bundle_state: &BundleState) -> BundleStateReservation<'_> {
+        let bytes = bundle_state_size(bundle_state);
+        self.metrics.bundle_state_bytes.record(bytes as f64);
+        self.metrics.in_flight_bundle_state_bytes.increment(bytes as f64);
+        self.in_flight_bundle_state_bytes.fetch_add(bytes, Ordering::Relaxed);
+        BundleStateReservation {
+            in_flight_bytes: &self.in_flight_bundle_state_bytes,
+            gauge: &self.metrics.in_flight_bundle_state_bytes,
+            bytes,
+        }
+    }
+
+    /// Push the hash of the ordered block to Coordinator again if the block has been executed,
+    /// e.g. when Coordinator pushes it again after a perceived timeout, so that it's not executed
+    /// again. Returns `false` if the hash is not cached, in which case the block is handled as
+    /// usual.
+    fn resend_executed_hash(&self, ordered_block: &OrderedBlock) -> bool {
+        let Some(block_info) = self.executed_hashes.get(ordered_block.id) else {
+            return false;
+        };
+        info!(target: "PipeExecService.run",
+            id=?ordered_block.id,
+            number=?ordered_block.number,
+            block_hash=?block_info.block_hash,
+            "ordered block has been executed, resend its hash"
+        );
+        self.executed_block_hash_tx.renotify(ordered_block.id, block_info);
+        true
+    }
+
+    /// Run `f` in the dedicated executor thread pool if configured, so that the rayon tasks it
+    /// spawns also run in the pool. Otherwise run `f` in place, using the global rayon pool.
+    /// The current span is entered in the pool thread as well.
+    fn install<R: Send>(&self, f: impl FnOnce() -> R + Send) -> R {
+        match &self.executor_pool {
+            Some(executor_pool) => {
+                let span = Span::current();
+                executor_pool.install(move || span.in_scope(f))
+            }
+            None => f(),
+        }
+    }
+
+    /// Close the channel of executed block hashes and the barriers, so that the in-flight blocks
+    /// are aborted.
+    fn close(&self) {
+        self.executed_block_hash_tx.close();
+        self.execute_block_barrier.close();
+        self.merklize_barrier.close();
+        self.seal_barrier.close();
+        self.make_canonical_barrier.close();
+        self.cancellations.close();
+    }
+
+    /// Load the id of the latest block from storage, which is needed to verify the parent id of
+    /// the next block.
+    fn init_latest_block_id(&self) {
+        let mut epoch = self.epoch.write().unwrap();
+        epoch.latest_block_id = self.storage.get_block_id(epoch.latest_block_number);
+        self.head.init_block_id(epoch.latest_block_id);
+        if epoch.latest_block_id.is_none() {
+            warn!(target: "PipeExecService.run",
+                latest_block_number=?epoch.latest_block_number,
+                "unknown latest block id, the parent id of the next block is not verified"
+            );
+        }
+    }
+
+    /// Run `f` if no rollback has happened since the block was accepted.
+    /// Returns `ProcessError::Aborted` if the block has been discarded by a rollback.
+    fn in_epoch<R>(&self, epoch: &EpochGuard, f: impl FnOnce() -> R) -> Result<R, ProcessError> {
+        let current = self.epoch.read().unwrap();
+        if current.id == epoch.id {
+            Ok(f())
+        } else {
+            Err(ProcessError::Aborted)
+        }
+    }
+
+    /// Halt the pipeline after the block failed to be processed, and report it to EL.
+    /// Coordinator will get `None` when pulling the executed block hashes, and
+    /// `PipeExecLayerError::Closed` when pushing more ordered blocks.
+    fn halt(&self, block_id: B256, error: ProcessError) {
+        error!(target: "PipeExecService.process",
+            block_id=?block_id,
+            error=%error,
+            "failed to process block, halting the pipeline"
+        );
+        self.close();
+        self.halted.store(true, Ordering::Relaxed);
+        let reason = error.to_string();
+        let _ = self
+            .event_tx
+            .send(PipeExecLayerEvent::ExecutionFailed { block_id, error: reason.clone() });
+        let _ = self.event_tx.send(PipeExecLayerEvent::Halted { reason });
+    }
 
-impl<Storage: GravityStorage> Core<Storage> {
-    async fn process(&self, ordered_block: OrderedBlock) {
+    /// Process the ordered block through all stages until it's made canonical.
+    /// `executed_gas_used` holds the gas used by the block while it's executed but not canonical
+    /// yet, which is the work wasted if the block is discarded.
+    async fn process(
+        self: &Arc<Self>,
+        ordered_block: OrderedBlock,
+        epoch: EpochGuard,
+        executed_gas_used: &mut Option<u64>,
+    ) -> Result<(), ProcessError> {
         let block_number = ordered_block.number;
         let block_id = ordered_block.id;
+        let parent_id = ordered_block.parent_id;
         debug!(target: "PipeExecService.process",
             id=?block_id,
-            parent_id=?ordered_block.parent_id,
+            parent_id=?parent_id,
             number=?block_number,
             "new ordered block"
         );
+        let mut cancellation = self.cancellations.token(block_id);
+
+        if self.config.prefetch_state {
+            self.prefetch(&ordered_block);
+        }
 
-        self.storage.insert_block_id(block_number, block_id);
         // Retrieve the parent block header to generate the necessary configs for
         // executing the current block
-        let (parent_block_header, prev_start_execute_time) =
-            self.execute_block_barrier.wait(block_number - 1).await.unwrap();
+        let (parent_block_header, prev_start_execute_time) = self
+            .wait_barrier(
+                &self.execute_block_barrier,
+                "execute",
+                &self.metrics.execute_wait_duration,
+                block_number,
+                &epoch,
+            )
+            .await?;
+        self.in_epoch(&epoch, || self.storage.insert_block_id(block_number, block_id))?;
+        let mut stage_timings = StageTimings::default();
         let start_time = Instant::now();
-        let (mut block, senders, outcome) =
-            self.execute_ordered_block(ordered_block, &parent_block_header);
-        self.storage.insert_bundle_state(block_number, &outcome.state);
-        self.metrics.execute_duration.record(start_time.elapsed());
-        self.metrics.start_execute_time_diff.record(start_time - prev_start_execute_time);
-        self.execute_block_barrier
-            .notify(block_number, (block.header.clone(), start_time))
-            .unwrap();
+        // A storage failure is handled like an execution failure, so that the block is rolled
+        // back and delivered again
+        let result =
+            match self.storage.get_state_view_by_id_async(block_number - 1, parent_id).await {
+                Ok(state) => self.install(|| {
+                    self.execute_ordered_block(ordered_block, &parent_block_header, state)
+                }),
+                Err(GravityStorageError::BlockIdMismatch { stored_block_id, .. }) => {
+                    return Err(ProcessError::ParentIdMismatch {
+                        block_id,
+                        expected: stored_block_id,
+                        got: parent_id,
+                    });
+                }
+                Err(err) => Err(BlockExecutionError::other(err)),
+            };
+        let ExecuteOrderedBlockResult { block, senders, execution_output: outcome, discarded_txs } =
+            match result {
+                Ok(result) => {
+                    self.consecutive_execution_failures.store(0, Ordering::Relaxed);
+                    result
+                }
+                Err(error) => {
+                    let failures =
+                        self.consecutive_execution_failures.fetch_add(1, Ordering::Relaxed) + 1;
+                    if failures >= self.config.max_consecutive_execution_failures {
+                        return Err(ProcessError::ExecutionFailed { block_id, failures, error });
+                    }
+                    warn!(target: "PipeExecService.process",
+                        block_number=?block_number,
+                        block_id=?block_id,
+                        failures=?failures,
+                        error=%error,
+                        "failed to execute block, rolling back to its parent"
+                    );
+                    self.rollback_to_parent(
+                        block_number,
+                        parent_id,
+                        parent_block_header,
+                        None,
+                        prev_start_execute_time,
+                        epoch,
+                    )
+                    .await?;
+                    // Reported once rolled back, so that the block can be delivered again
+                    let _ = self.event_tx.send(PipeExecLayerEvent::ExecutionFailed {
+                        block_id,
+                        error: error.to_string(),
+                    });
+                    return Err(ProcessError::Aborted);
+                }
+            };
+        // Released whenever the block finishes, including when it's discarded
+        let _bundle_state = self.reserve_bundle_state(&outcome.state);
+        *executed_gas_used = Some(outcome.gas_used);
+        // Checked at each stage boundary until the block is verified, which discards the bundle
+        // state of a cancelled block before it's inserted into the storage
+        if let Some(rolled_back_txs) = cancellation.take_cancelled() {
+            self.rollback_to_parent(
+                block_number,
+                parent_id,
+                parent_block_header,
+                None,
+                prev_start_execute_time,
+                epoch,
+            )
+            .await?;
+            self.notify_cancelled(block_id, rolled_back_txs);
+            return Err(ProcessError::Aborted);
+        }
+        if let Some(sink) = &self.config.tx_trace_sink {
+            trace_txs(sink.as_ref(), block_number, &block.body.transactions, &outcome.receipts);
+        }
+        self.metrics.record_filtered_txs(&discarded_txs);
+        if !discarded_txs.is_empty() {
+            info!(target: "PipeExecService.process",
+                block_number=?block_number,
+                block_id=?block_id,
+                discarded=?discarded_txs.len(),
+                "transactions discarded"
+            );
+            debug!(target: "PipeExecService.process",
+                block_number=?block_number,
+                block_id=?block_id,
+                discarded_txs=?discarded_txs,
+                "transactions discarded"
+            );
+        }
+        let execute_interval = start_time - prev_start_execute_time;
+        self.in_epoch(&epoch, || {
+            self.storage.insert_bundle_state(block_number, &outcome.state);
+            *self.latest_executed_header.lock().unwrap() = block.header.clone();
+            stage_timings.execute = start_time.elapsed();
+            self.metrics.execute_duration.record(stage_timings.execute);
+            self.metrics.start_execute_time_diff.record(execute_interval);
+            self.execute_block_barrier.notify(block_number, (block.header.clone(), start_time))
+        })?
+        .ok_or(ProcessError::Aborted)?;
+        if execute_interval > self.config.slow_block_interval {
+            self.metrics.slow_block_interval_total.increment(1);
+            warn!(target: "PipeExecService.process",
+                block_number=?block_number,
+                block_id=?block_id,
+                interval=?execute_interval,
+                threshold=?self.config.slow_block_interval,
+                "block started execution late after the previous block"
+            );
+        }
+        let start_execute_time = start_time;
 
-        let execution_outcome = self.calculate_roots(&mut block, outcome);
+        // The other roots are calculated in parallel with merklization
+        let roots = self.spawn_calculate_roots(block, outcome);
 
         // Merkling the state trie
-        self.merklize_barrier.wait(block_number - 1).await.unwrap();
         let (state_root, hashed_state, trie_updates) =
-            self.storage.state_root_with_updates(block_number).unwrap();
-        self.metrics.merklize_duration.record(start_time.elapsed());
-        self.merklize_barrier.notify(block_number, ()).unwrap();
+            self.merklize_in_order(block_number, &epoch).await?;
+        let (mut block, execution_outcome) = roots.await.expect("calculate_roots panicked");
+        stage_timings.merklize = start_time.elapsed();
+        self.metrics.merklize_duration.record(stage_timings.merklize);
         debug!(target: "PipeExecService.process",
             block_number=?block_number,
             block_id=?block_id,
@@ -176,16 +1028,41 @@ impl<Storage: GravityStorage> Core<Storage> {
             "state trie merklized"
         );
         block.header.state_root = state_root;
+        if let Some(rolled_back_txs) = cancellation.take_cancelled() {
+            self.rollback_to_parent(
+                block_number,
+                parent_id,
+                parent_block_header,
+                None,
+                prev_start_execute_time,
+                epoch,
+            )
+            .await?;
+            self.notify_cancelled(block_id, rolled_back_txs);
+            return Err(ProcessError::Aborted);
+        }
 
-        let parent_hash = self.seal_barrier.wait(block_number - 1).await.unwrap();
+        let parent_hash = self
+            .wait_barrier(
+                &self.seal_barrier,
+                "seal",
+                &self.metrics.seal_wait_duration,
+                block_number,
+                &epoch,
+            )
+            .await?;
         let start_time = Instant::now();
         block.header.parent_hash = parent_hash;
 
-        // Seal the block
+        // Seal the block. Only the header is hashed, since all the roots have been filled into the
+        // header by now, so this takes a single keccak of the header RLP regardless of the block
+        // size (see the `seal` benchmark).
         let block = block.seal_slow();
         let block_hash = block.hash();
-        self.metrics.seal_duration.record(start_time.elapsed());
-        self.seal_barrier.notify(block_number, block_hash).unwrap();
+        stage_timings.seal = start_time.elapsed();
+        self.metrics.seal_duration.record(stage_timings.seal);
+        self.in_epoch(&epoch, || self.seal_barrier.notify(block_number, block_hash))?
+            .ok_or(ProcessError::Aborted)?;
         debug!(target: "PipeExecService.process",
             block_number=?block_number,
             block_id=?block_id,
@@ -194,11 +1071,98 @@ impl<Storage: GravityStorage> Core<Storage> {
             receipts_root=?block.header().receipts_root,
             "block sealed"
         );
+        if *PIPE_DOUBLE_CHECK_STATE_ROOT {
+            self.double_check_state_root(block_number, block_hash, state_root).await;
+        }
+        // The receipts are cloned only once here, and shared with the subscribers afterwards
+        let receipts = Arc::new(execution_outcome.receipts.first().cloned().unwrap_or_default());
+        let _ = self.event_tx.send(PipeExecLayerEvent::BlockExecuted {
+            block_number,
+            block_hash,
+            receipts,
+            fork_features: AppliedForkFeatures::from_header(block.header()),
+        });
 
         // Commit the executed block hash to Coordinator
+        let block_info = ExecutedBlockInfo {
+            block_hash,
+            gas_used: block.header().gas_used,
+            tx_count: block.body().transactions.len(),
+        };
+        self.in_epoch(&epoch, || self.executed_hashes.insert(block_number, block_id, block_info))?;
+        self.block_statuses.set(block_number, block_id, BlockStatus::Executed);
         let start_time = Instant::now();
-        self.verify_executed_block_hash(ExecutedBlockMeta { block_id, block_hash }).await.unwrap();
-        self.metrics.verify_duration.record(start_time.elapsed());
+        self.awaiting_verification
+            .lock()
+            .unwrap()
+            .insert(block_number, ExecutedBlockMeta { block_id, block_hash });
+        let verify_outcome = tokio::select! {
+            verify_outcome = self.verify_executed_block_hash(block_id, block_info, &epoch) => {
+                Some(verify_outcome)
+            }
+            () = cancellation.cancelled() => None,
+        };
+        {
+            // The entry may have been replaced by a block of another fork if this one is aborted
+            let mut awaiting_verification = self.awaiting_verification.lock().unwrap();
+            if awaiting_verification
+                .get(&block_number)
+                .is_some_and(|meta| meta.block_id == block_id)
+            {
+                awaiting_verification.remove(&block_number);
+            }
+        }
+        let cancelled = match verify_outcome.transpose()? {
+            Some(VerifyOutcome::Rejected { rolled_back_tx }) => {
+                // The parent block must be made canonical before rolling back to it
+                let prev_finish_commit_time = self
+                    .wait_barrier(
+                        &self.make_canonical_barrier,
+                        "make_canonical",
+                        &self.metrics.make_canonical_wait_duration,
+                        block_number,
+                        &epoch,
+                    )
+                    .await?;
+                self.rollback_to(
+                    RollbackTarget {
+                        block_number: block_number - 1,
+                        block_id: parent_id,
+                        header: parent_block_header,
+                        block_hash: parent_hash,
+                        start_execute_time: prev_start_execute_time,
+                        finish_commit_time: prev_finish_commit_time,
+                    },
+                    epoch,
+                )
+                .await?;
+                if let Some(rolled_back_tx) = rolled_back_tx {
+                    let _ = rolled_back_tx.send(());
+                }
+                return Err(ProcessError::Aborted);
+            }
+            // A cancellation arriving from now on is refused, since the status is set under the
+            // same lock
+            Some(VerifyOutcome::Verified) => cancellation
+                .settle(|| self.block_statuses.set(block_number, block_id, BlockStatus::Verified))
+                .err(),
+            None => Some(cancellation.take_cancelled().unwrap_or_default()),
+        };
+        if let Some(rolled_back_txs) = cancelled {
+            self.rollback_to_parent(
+                block_number,
+                parent_id,
+                parent_block_header,
+                Some(parent_hash),
+                prev_start_execute_time,
+                epoch,
+            )
+            .await?;
+            self.notify_cancelled(block_id, rolled_back_txs);
+            return Err(ProcessError::Aborted);
+        }
+        stage_timings.verify = start_time.elapsed();
+        self.metrics.verify_duration.record(stage_timings.verify);
         debug!(target: "PipeExecService.process",
             block_number=?block_number,
             block_id=?block_id,
@@ -207,130 +1171,813 @@ impl<Storage: GravityStorage> Core<Storage> {
         );
 
         let gas_used = block.gas_used;
+        let tx_count = block.body().transactions.len();
+        let header = block.header().clone();
 
         // Make the block canonical
-        let prev_finish_commit_time =
-            self.make_canonical_barrier.wait(block_number - 1).await.unwrap();
+        let prev_finish_commit_time = self
+            .wait_barrier(
+                &self.make_canonical_barrier,
+                "make_canonical",
+                &self.metrics.make_canonical_wait_duration,
+                block_number,
+                &epoch,
+            )
+            .await?;
+        let diff = self.config.emit_state_diff.then(|| state_diff(&execution_outcome.bundle));
+        let recovered_block = Arc::new(RecoveredBlock::new_sealed(block, senders));
+        let execution_outcome = Arc::new(execution_outcome);
         self.make_canonical(ExecutedBlockWithTrieUpdates::new(
-            Arc::new(RecoveredBlock::new_sealed(block, senders)),
-            Arc::new(execution_outcome),
+            recovered_block.clone(),
+            execution_outcome.clone(),
             hashed_state,
             trie_updates,
         ))
-        .await;
-        self.storage.update_canonical(block_number, block_hash);
+        .await
+        .map_err(|error| ProcessError::MakeCanonicalFailed { block_id, error })?;
+        self.storage.update_canonical_async(block_number, block_hash).await;
+        self.prune_bundle_states(block_number);
         let finish_commit_time = Instant::now();
-        self.metrics.make_canonical_duration.record(start_time.elapsed());
+        self.record_canonical(RollbackTarget {
+            block_number,
+            block_id,
+            header,
+            block_hash,
+            start_execute_time,
+            finish_commit_time,
+        });
+        self.block_statuses.set(block_number, block_id, BlockStatus::Canonical);
+        self.head.commit(block_number, block_hash, block_id, finish_commit_time);
+        *executed_gas_used = None;
+        // Fails only if there's no subscriber. Slow subscribers lag instead of blocking.
+        let _ = self.canonical_tx.send(Canonicalized { block_id, block: recovered_block });
+        if let Some((accounts, storage)) = diff {
+            let _ = self.event_tx.send(PipeExecLayerEvent::StateDiff {
+                block_number,
+                accounts,
+                storage,
+            });
+        }
+        stage_timings.make_canonical = start_time.elapsed();
+        self.metrics.make_canonical_duration.record(stage_timings.make_canonical);
         self.metrics.finish_commit_time_diff.record(finish_commit_time - prev_finish_commit_time);
-        self.make_canonical_barrier.notify(block_number, finish_commit_time).unwrap();
+        self.throughput.lock().unwrap().record_block(
+            &self.metrics,
+            tx_count,
+            gas_used,
+            finish_commit_time - prev_finish_commit_time,
+        );
+        self.stage_timings.push(block_number, stage_timings);
+        self.execution_outcomes.push(block_number, execution_outcome);
+        // A pending reorg may be waiting for the block to be made canonical
+        self.make_canonical_barrier
+            .notify_all(block_number, finish_commit_time)
+            .ok_or(ProcessError::Aborted)?;
+        self.record_barrier_keys();
 
         self.metrics.total_gas_used.increment(gas_used);
+        Ok(())
     }
 
-    /// Push executed block hash to Coordinator and wait for verification result from Coordinator.
-    /// Returns `None` if the channel has been closed.
-    async fn verify_executed_block_hash(&self, block_meta: ExecutedBlockMeta) -> Option<()> {
-        self.executed_block_hash_tx.notify(block_meta.block_id, block_meta.block_hash)?;
-        let block_hash = self.verified_block_hash_rx.wait(block_meta.block_id).await?;
-        assert_eq!(block_meta.block_hash, block_hash);
-        Some(())
+    /// Sample the number of keys outstanding in each barrier.
+    fn record_barrier_keys(&self) {
+        let metrics = &self.metrics;
+        metrics.execute_block_barrier_keys.set(self.execute_block_barrier.pending_keys() as f64);
+        metrics.merklize_barrier_keys.set(self.merklize_barrier.pending_keys() as f64);
+        metrics.seal_barrier_keys.set(self.seal_barrier.pending_keys() as f64);
+        metrics.make_canonical_barrier_keys.set(self.make_canonical_barrier.pending_keys() as f64);
     }
 
-    fn execute_ordered_block(
-        &self,
-        ordered_block: OrderedBlock,
-        parent_header: &Header,
-    ) -> (Block, Vec<Address>, BlockExecutionOutput<Receipt>) {
-        assert_eq!(ordered_block.transactions.len(), ordered_block.senders.len());
+    /// Warm the accounts touched by the ordered block in the background, while the previous blocks
+    /// are still being executed and merklized. The state view of the latest executed block is
+    /// used, since the parent block may not be executed yet.
+    fn prefetch(self: &Arc<Self>, ordered_block: &OrderedBlock) {
+        let addresses = prefetch_addresses(&ordered_block.transactions, &ordered_block.senders);
+        let core = self.clone();
+        let span = Span::current();
+        tokio::task::spawn_blocking(move || {
+            let _enter = span.enter();
+            let start_time = Instant::now();
+            let latest_block_number = core.latest_executed_header.lock().unwrap().number;
+            let state = match core.storage.get_state_view(latest_block_number) {
+                Ok((_, state)) => state,
+                Err(err) => {
+                    debug!(target: "PipeExecService.prefetch", %err, "failed to get state view");
+                    return;
+                }
+            };
+            core.install(|| prefetch_state(&state, addresses));
+            core.metrics.prefetch_duration.record(start_time.elapsed());
+        });
+    }
 
-        debug!(target: "execute_ordered_block",
-            id=?ordered_block.id,
-            parent_id=?ordered_block.parent_id,
-            number=?ordered_block.number,
-            "ready to execute block"
-        );
+    /// Calculate the roots of the block other than the state root on a rayon task, in the executor
+    /// thread pool if configured. Returns the block with the roots filled along with the
+    /// execution outcome.
+    fn spawn_calculate_roots(
+        self: &Arc<Self>,
+        mut block: Block,
+        outcome: BlockExecutionOutput<Receipt>,
+    ) -> oneshot::Receiver<(Block, ExecutionOutcome)> {
+        let (tx, rx) = oneshot::channel();
+        let core = self.clone();
+        let task = move || {
+            let start_time = Instant::now();
+            let execution_outcome = core.calculate_roots(&mut block, outcome);
+            core.metrics.calculate_roots_duration.record(start_time.elapsed());
+            let _ = tx.send((block, execution_outcome));
+        };
+        match &self.executor_pool {
+            Some(executor_pool) => executor_pool.spawn(task),
+            None => rayon::spawn(task),
+        }
+        rx
+    }
 
-        let evm_env = self
-            .evm_config
-            .next_evm_env(
-                parent_header,
-                NextBlockEnvAttributes {
-                    timestamp: ordered_block.timestamp,
-                    suggested_fee_recipient: ordered_block.coinbase,
-                    prev_randao: ordered_block.prev_randao,
-                    gas_limit: BLOCK_GAS_LIMIT_1G,
-                },
-            )
-            .unwrap();
+    /// Merklize the block once the previous block has been merklized, so that the trie updates
+    /// are applied in order.
+    async fn merklize_in_order(
+        self: &Arc<Self>,
+        block_number: u64,
+        epoch: &EpochGuard,
+    ) -> Result<(B256, Arc<HashedPostState>, Arc<TrieUpdates>), ProcessError> {
+        self.wait_barrier(
+            &self.merklize_barrier,
+            "merklize",
+            &self.metrics.merklize_wait_duration,
+            block_number,
+            epoch,
+        )
+        .await?;
+        let merklized = self.merklize(block_number).await;
+        self.metrics.trie_updates_node_count.record(trie_updates_node_count(&merklized.2) as f64);
+        self.in_epoch(epoch, || self.merklize_barrier.notify(block_number, ()))?
+            .ok_or(ProcessError::Aborted)?;
+        Ok(merklized)
+    }
 
-        let mut block = Block {
-            header: Header {
-                ommers_hash: EMPTY_OMMER_ROOT_HASH,
-                beneficiary: ordered_block.coinbase,
-                timestamp: ordered_block.timestamp,
-                mix_hash: ordered_block.prev_randao,
-                nonce: BEACON_NONCE.into(),
-                base_fee_per_gas: Some(evm_env.block_env.basefee.to::<u64>()),
-                number: ordered_block.number,
-                gas_limit: evm_env.block_env.gas_limit.to(),
-                difficulty: U256::ZERO,
-                ..Default::default()
-            },
-            body: BlockBody::default(),
-        };
+    /// Calculate the state root of the block on the blocking thread pool, so that the heavy trie
+    /// computation doesn't block the async runtime.
+    async fn merklize(
+        self: &Arc<Self>,
+        block_number: u64,
+    ) -> (B256, Arc<HashedPostState>, Arc<TrieUpdates>) {
+        let core = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let start_time = Instant::now();
+            let result = core.storage.state_root_with_updates(block_number).unwrap();
+            core.metrics.state_root_duration.record(start_time.elapsed());
+            result
+        })
+        .await
+        .unwrap()
+    }
 
-        if self.chain_spec.is_shanghai_active_at_timestamp(block.timestamp) {
-            if ordered_block.withdrawals.is_empty() {
-                block.header.withdrawals_root = Some(EMPTY_WITHDRAWALS);
-                block.body.withdrawals = Some(Withdrawals::default());
-            } else {
-                block.header.withdrawals_root =
-                    Some(proofs::calculate_withdrawals_root(&ordered_block.withdrawals));
-                block.body.withdrawals = Some(ordered_block.withdrawals);
-            }
+    /// Recalculate the state root of the block from scratch, and panic if it diverges from the
+    /// merklized one. The check is skipped if the storage can't recalculate the state root.
+    async fn double_check_state_root(
+        self: &Arc<Self>,
+        block_number: u64,
+        block_hash: B256,
+        state_root: B256,
+    ) {
+        let core = self.clone();
+        let recalculated_state_root =
+            match tokio::task::spawn_blocking(move || core.storage.state_root(block_number))
+                .await
+                .unwrap()
+            {
+                Ok(recalculated_state_root) => recalculated_state_root,
+                Err(err) => {
+                    warn!(target: "PipeExecService.process",
+                        block_number=?block_number,
+                        block_hash=?block_hash,
+                        error=%err,
+                        "state root not double checked"
+                    );
+                    return;
+                }
+            };
+        if recalculated_state_root != state_root {
+            error!(target: "PipeExecService.process",
+                block_number=?block_number,
+                block_hash=?block_hash,
+                state_root=?state_root,
+                recalculated_state_root=?recalculated_state_root,
+                "state root diverged"
+            );
+            panic!(
+                "State root diverged, block_number={block_number} block_hash={block_hash:?} \
+                 state_root={state_root:?} recalculated_state_root={recalculated_state_root:?}"
+            );
         }
+    }
 
-        // only determine cancun fields when active
-        if self.chain_spec.is_cancun_active_at_timestamp(block.timestamp) {
-            // FIXME: Is it OK to use the parent's block id as `parent_beacon_block_root` before
-            // execution?
-            block.header.parent_beacon_block_root = Some(ordered_block.parent_id);
+    /// Discard the block and the blocks after it, e.g. after it failed to execute or has been
+    /// cancelled, so that Coordinator can deliver it again. Like for a rejected block, the pipeline
+    /// rolls back once the parent block is made canonical. `parent_hash` is `None` if the block
+    /// hasn't waited for the seal barrier yet.
+    async fn rollback_to_parent(
+        &self,
+        block_number: u64,
+        parent_id: B256,
+        parent_header: Header,
+        parent_hash: Option<B256>,
+        prev_start_execute_time: Instant,
+        epoch: EpochGuard,
+    ) -> Result<(), ProcessError> {
+        let parent_hash = match parent_hash {
+            Some(parent_hash) => parent_hash,
+            None => {
+                self.wait_previous_block(&self.seal_barrier, "seal", block_number, &epoch).await?
+            }
+        };
+        let prev_finish_commit_time = self
+            .wait_previous_block(
+                &self.make_canonical_barrier,
+                "make_canonical",
+                block_number,
+                &epoch,
+            )
+            .await?;
+        self.rollback_to(
+            RollbackTarget {
+                block_number: block_number - 1,
+                block_id: parent_id,
+                header: parent_header,
+                block_hash: parent_hash,
+                start_execute_time: prev_start_execute_time,
+                finish_commit_time: prev_finish_commit_time,
+            },
+            epoch,
+        )
+        .await
+    }
 
-            // TODO(nekomoto): fill `excess_blob_gas` and `blob_gas_used` fields
-            block.header.excess_blob_gas = Some(0);
-            block.header.blob_gas_used = Some(0);
+    /// Notify the callers of `PipeExecLayerApi::cancel` once the cancelled block has been rolled
+    /// back.
+    fn notify_cancelled(&self, block_id: B256, rolled_back_txs: Vec<oneshot::Sender<()>>) {
+        info!(target: "PipeExecService.process",
+            block_id=?block_id,
+            "cancelled block rolled back"
+        );
+        for rolled_back_tx in rolled_back_txs {
+            let _ = rolled_back_tx.send(());
         }
+    }
 
-        let (parent_id, state) = self.storage.get_state_view(block.number - 1).unwrap();
-        assert_eq!(parent_id, ordered_block.parent_id);
-
-        // Discard the invalid txs
-        let start_time = Instant::now();
-        let (txs, senders) = filter_invalid_txs(
-            &state,
-            ordered_block.transactions,
-            ordered_block.senders,
-            evm_env.block_env.basefee,
+    /// Discard all blocks above the target block, which has been made canonical, and reset the
+    /// pipeline so that execution resumes from the block following it. On a reorg, the canonical
+    /// blocks above the target are discarded as well.
+    /// Returns `ProcessError::Aborted` if the storage failed to roll back, in which case the
+    /// pipeline has been halted.
+    async fn rollback_to(
+        &self,
+        target: RollbackTarget,
+        epoch: EpochGuard,
+    ) -> Result<(), ProcessError> {
+        let block_number = target.block_number;
+        let prev_alive_tx = {
+            let mut current = self.epoch.write().unwrap();
+            if current.id != epoch.id {
+                return Ok(());
+            }
+            current.id += 1;
+            current.latest_block_number = block_number;
+            current.latest_block_id = Some(target.block_id);
+            current.reorder_buffer.clear();
+            *self.latest_executed_header.lock().unwrap() = target.header.clone();
+            // Wake up the discarded blocks waiting on the barriers or verification
+            self.execute_block_barrier.retain(|number| *number <= block_number);
+            self.merklize_barrier.retain(|number| *number <= block_number);
+            self.seal_barrier.retain(|number| *number <= block_number);
+            self.make_canonical_barrier.retain(|number| *number <= block_number);
+            self.executed_block_hash_tx.retain(|_| false);
+            self.verified_block_hash_rx.retain(|_| false);
+            self.cancellations.clear();
+            self.executed_hashes.rollback_to(block_number);
+            std::mem::replace(&mut current.alive_tx, watch::channel(()).0)
+        };
+        warn!(target: "PipeExecService.rollback",
+            block_number=?block_number,
+            block_hash=?target.block_hash,
+            "rolling back executed blocks"
         );
-        self.metrics.filter_transaction_duration.record(start_time.elapsed());
 
-        block.body.transactions = txs;
-        let recovered_block = RecoveredBlock::new_unhashed(block, senders);
+        // Wait until all blocks of the previous epoch have been discarded
+        drop(epoch);
+        prev_alive_tx.closed().await;
 
-        let executor = EthExecutorProvider::ethereum(self.chain_spec.clone())
-            .executor(parallel_database! { state });
+        // On a reorg, the target is below the canonical head
+        let head_block_number = self.head.get().block_number;
+        if head_block_number > block_number {
+            if let Err(err) = self.storage.unwind_canonical(block_number, target.block_hash) {
+                self.halt_on_storage_error(block_number, err);
+                return Err(ProcessError::Aborted);
+            }
+            self.canonical_blocks.lock().unwrap().retain(|number, _| *number <= block_number);
+            self.stage_timings.rollback_to(block_number);
+            self.execution_outcomes.rollback_to(block_number);
+            self.head.commit(block_number, target.block_hash, target.block_id, Instant::now());
+            let _ = self
+                .event_tx
+                .send(PipeExecLayerEvent::Reorg { from: head_block_number, to: block_number });
+        }
+        if let Err(err) = self.storage.rollback_to(block_number) {
+            self.halt_on_storage_error(block_number, err);
+            return Err(ProcessError::Aborted);
+        }
+        // The blocks of the previous epoch can no longer update their status by now
+        self.block_statuses.rollback_to(block_number);
+        self.execute_block_barrier.notify(block_number, (target.header, target.start_execute_time));
+        self.merklize_barrier.notify(block_number, ());
+        self.seal_barrier.notify(block_number, target.block_hash);
+        self.make_canonical_barrier.notify_all(block_number, target.finish_commit_time);
+        info!(target: "PipeExecService.rollback",
+            block_number=?block_number,
+            block_hash=?target.block_hash,
+            "rolled back executed blocks"
+        );
+        Ok(())
+    }
 
-        let outcome = executor.execute(&recovered_block).unwrap_or_else(|err| {
-            serde_json::to_writer(
-                std::io::BufWriter::new(
-                    std::fs::File::create(format!("{}.json", ordered_block.id)).unwrap(),
-                ),
-                &recovered_block,
-            )
-            .unwrap();
-            panic!("failed to execute block {:?}: {:?}", ordered_block.id, err)
+    /// Halt the pipeline after the storage failed to roll back, since the discarded blocks may
+    /// still be visible in its state. The barriers are left closed, so no block is executed on
+    /// top of it.
+    fn halt_on_storage_error(&self, block_number: u64, error: GravityStorageError) {
+        error!(target: "PipeExecService.rollback",
+            block_number=?block_number,
+            error=%error,
+            "failed to roll back storage, halting the pipeline"
+        );
+        self.close();
+        self.halted.store(true, Ordering::Relaxed);
+        let _ = self.event_tx.send(PipeExecLayerEvent::Halted {
+            reason: format!("failed to roll back storage to block {block_number}: {error}"),
         });
+    }
 
+    /// Prune the bundle states of the canonical blocks out of the retention window below the
+    /// canonical head.
+    fn prune_bundle_states(&self, head_block_number: u64) {
+        let below = head_block_number.saturating_sub(self.config.bundle_state_retention as u64);
+        if below == 0 {
+            return;
+        }
+        let pruned = self.storage.prune_bundle_states(below);
+        self.metrics.pruned_bundle_states.increment(pruned as u64);
+    }
+
+    /// Remember the block made canonical, so that a reorg can roll back to it later.
+    fn record_canonical(&self, target: RollbackTarget) {
+        if self.config.max_reorg_depth == 0 {
+            return;
+        }
+        let mut canonical_blocks = self.canonical_blocks.lock().unwrap();
+        canonical_blocks.insert(target.block_number, target);
+        // Keep the head and `max_reorg_depth` blocks below it
+        while canonical_blocks.len() > self.config.max_reorg_depth + 1 {
+            canonical_blocks.pop_first();
+        }
+    }
+
+    /// Find the block which the ordered block forks from, if it replaces an accepted block, i.e.
+    /// it's not above the latest accepted block, it differs from the executed block of the same
+    /// number, and its parent is an executed block. Returns `None` if reorgs are disabled.
+    fn fork_point(&self, ordered_block: &OrderedBlock) -> Option<u64> {
+        if self.config.max_reorg_depth == 0 ||
+            ordered_block.number > self.epoch.read().unwrap().latest_block_number
+        {
+            return None;
+        }
+        let fork_block_number = ordered_block.number.checked_sub(1)?;
+        let replaced = self
+            .storage
+            .get_block_id(ordered_block.number)
+            .is_some_and(|id| id != ordered_block.id);
+        let forks = self.storage.get_block_id(fork_block_number) == Some(ordered_block.parent_id);
+        (replaced && forks).then_some(fork_block_number)
+    }
+
+    /// Roll back to the fork block, discarding all blocks above it including the canonical ones,
+    /// so that the new branch is executed on top of it. If the fork block is still in flight,
+    /// this waits until it's made canonical, which requires Coordinator to verify it.
+    /// Nothing is rolled back if the fork block is deeper than `max_reorg_depth` or has been
+    /// discarded, in which case the ordered block is discarded as well.
+    async fn reorg(&self, fork_block_number: u64) {
+        let lookup = || self.canonical_blocks.lock().unwrap().get(&fork_block_number).cloned();
+        let target = match lookup() {
+            Some(target) => Some(target),
+            None => {
+                let made_canonical = self.make_canonical_barrier.subscribe(fork_block_number);
+                // The head is read before looking up again, since a block is recorded before the
+                // head moves to it. If the head has reached the fork block, it has been evicted.
+                let head_block_number = self.head.get().block_number;
+                match lookup() {
+                    Some(target) => Some(target),
+                    None if head_block_number >= fork_block_number => None,
+                    None => made_canonical.await.and_then(|_| lookup()),
+                }
+            }
+        };
+        let Some(target) = target else {
+            error!(target: "PipeExecService.reorg",
+                fork_block_number=?fork_block_number,
+                max_reorg_depth=?self.config.max_reorg_depth,
+                "refuse to reorg, the fork block is too deep or has been discarded"
+            );
+            return;
+        };
+
+        let epoch = {
+            let current = self.epoch.read().unwrap();
+            EpochGuard { id: current.id, _alive_rx: current.alive_tx.subscribe() }
+        };
+        warn!(target: "PipeExecService.reorg",
+            fork_block_number=?fork_block_number,
+            fork_block_hash=?target.block_hash,
+            "reorg requested by Coordinator"
+        );
+        // The pipeline has been halted if the storage failed to roll back
+        let _ = self.rollback_to(target, epoch).await;
+    }
+
+    /// Wait until the previous block passes the barrier of the given stage, recording how long it
+    /// took into `wait_duration`.
+    /// Returns `ProcessError::Aborted` if the barrier has been closed or the block has been
+    /// discarded.
+    async fn wait_barrier<V>(
+        &self,
+        barrier: &Channel<u64, V>,
+        stage: &'static str,
+        wait_duration: &Histogram,
+        block_number: u64,
+        epoch: &EpochGuard,
+    ) -> Result<V, ProcessError> {
+        let start_time = Instant::now();
+        let result = self.wait_previous_block(barrier, stage, block_number, epoch).await;
+        wait_duration.record(start_time.elapsed());
+        result
+    }
+
+    async fn wait_previous_block<V>(
+        &self,
+        barrier: &Channel<u64, V>,
+        stage: &'static str,
+        block_number: u64,
+        epoch: &EpochGuard,
+    ) -> Result<V, ProcessError> {
+        // The barriers are seeded with the block the pipeline starts on top of, and blocks at or
+        // below it are never accepted, so the parent of a processed block always exists
+        let parent_block_number =
+            block_number.checked_sub(1).expect("the genesis block is never processed");
+        // Register the waiter only if the block hasn't been discarded, otherwise it may wait
+        // for a block of the next epoch
+        let Some(timeout) = self.config.barrier_timeout else {
+            return self
+                .in_epoch(epoch, || barrier.wait(parent_block_number))?
+                .await
+                .ok_or(ProcessError::Aborted);
+        };
+        loop {
+            match self.in_epoch(epoch, || barrier.wait_timeout(parent_block_number, timeout))?.await
+            {
+                Ok(v) => return v.ok_or(ProcessError::Aborted),
+                Err(ChannelTimeout) => {
+                    error!(target: "PipeExecService.process",
+                        stage=?stage,
+                        block_number=?block_number,
+                        timeout=?timeout,
+                        "timed out waiting for the previous block, the pipeline may be stalled"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Push executed block hash to Coordinator and wait for verification result from Coordinator.
+    /// If `verify_timeout` is set, the hash is pushed again on each timeout in case Coordinator
+    /// has missed it, up to `verify_attempts` times.
+    /// In single-node mode, the block is verified against its own hash without involving
+    /// Coordinator.
+    /// Returns `ProcessError::Aborted` if the channel has been closed or the block has been
+    /// discarded.
+    async fn verify_executed_block_hash(
+        &self,
+        block_id: B256,
+        block_info: ExecutedBlockInfo,
+        epoch: &EpochGuard,
+    ) -> Result<VerifyOutcome, ProcessError> {
+        let block_meta = ExecutedBlockMeta { block_id, block_hash: block_info.block_hash };
+        if self.config.single_node {
+            self.in_epoch(epoch, || ())?;
+            return Ok(Self::verify_outcome(
+                block_meta,
+                Verification::Verified(block_info.block_hash),
+            ));
+        }
+        let Some(timeout) = self.config.verify_timeout else {
+            let verification = self
+                .in_epoch(epoch, || {
+                    self.executed_block_hash_tx.notify(block_id, block_info)?;
+                    Some(self.verified_block_hash_rx.wait(block_meta.block_id))
+                })?
+                .ok_or(ProcessError::Aborted)?;
+            let verification = verification.await.ok_or(ProcessError::Aborted)?;
+            return Ok(Self::verify_outcome(block_meta, verification));
+        };
+
+        for attempt in 1..=self.config.verify_attempts {
+            let verification = self
+                .in_epoch(epoch, || {
+                    if attempt == 1 {
+                        self.executed_block_hash_tx.notify(block_id, block_info)?;
+                    } else {
+                        self.executed_block_hash_tx.renotify(block_id, block_info)?;
+                    }
+                    Some(self.verified_block_hash_rx.wait_timeout(block_meta.block_id, timeout))
+                })?
+                .ok_or(ProcessError::Aborted)?;
+            match verification.await {
+                Ok(verification) => {
+                    let verification = verification.ok_or(ProcessError::Aborted)?;
+                    return Ok(Self::verify_outcome(block_meta, verification));
+                }
+                Err(ChannelTimeout) => {
+                    warn!(target: "PipeExecService.verify",
+                        block_id=?block_meta.block_id,
+                        block_hash=?block_meta.block_hash,
+                        attempt=?attempt,
+                        timeout=?timeout,
+                        "timed out waiting for verification"
+                    );
+                }
+            }
+        }
+        Err(ProcessError::VerificationTimeout {
+            block_id: block_meta.block_id,
+            attempts: self.config.verify_attempts,
+        })
+    }
+
+    fn verify_outcome(block_meta: ExecutedBlockMeta, verification: Verification) -> VerifyOutcome {
+        match verification {
+            Verification::Verified(block_hash) if block_hash == block_meta.block_hash => {
+                VerifyOutcome::Verified
+            }
+            Verification::Verified(block_hash) => {
+                error!(target: "PipeExecService.verify",
+                    block_id=?block_meta.block_id,
+                    executed_block_hash=?block_meta.block_hash,
+                    verified_block_hash=?block_hash,
+                    "block hash mismatch"
+                );
+                VerifyOutcome::Rejected { rolled_back_tx: None }
+            }
+            Verification::Rejected(rolled_back_tx) => {
+                warn!(target: "PipeExecService.verify",
+                    block_id=?block_meta.block_id,
+                    block_hash=?block_meta.block_hash,
+                    "block rejected by Coordinator"
+                );
+                VerifyOutcome::Rejected { rolled_back_tx: Some(rolled_back_tx) }
+            }
+        }
+    }
+
+    /// Execute the ordered block on top of the state view of its parent block. The state view is
+    /// borrowed by the filter and then moved into the executor, so that it's fetched once per
+    /// block, unless a presumed valid block fails to execute and is executed again.
+    fn execute_ordered_block(
+        &self,
+        mut ordered_block: OrderedBlock,
+        parent_header: &Header,
+        state: Storage::StateView,
+    ) -> Result<ExecuteOrderedBlockResult, BlockExecutionError> {
+        assert_eq!(ordered_block.transactions.len(), ordered_block.senders.len());
+
+        debug!(target: "execute_ordered_block",
+            id=?ordered_block.id,
+            parent_id=?ordered_block.parent_id,
+            number=?ordered_block.number,
+            "ready to execute block"
+        );
+        if let Err(reason) =
+            self.config.prev_randao_validator.validate_prev_randao(&ordered_block, parent_header)
+        {
+            return Err(BlockExecutionError::other(InvalidPrevRandao {
+                block_id: ordered_block.id,
+                prev_randao: ordered_block.prev_randao,
+                reason,
+            }));
+        }
+
+        // The EVM pays the beneficiary of the header, so the fee recipient is sealed as the
+        // beneficiary, and any node re-executing the block pays the same account
+        let beneficiary = self.config.fee_recipient_override.unwrap_or(ordered_block.coinbase);
+        let mut evm_env = self
+            .evm_config
+            .next_evm_env(
+                parent_header,
+                NextBlockEnvAttributes {
+                    timestamp: ordered_block.timestamp,
+                    suggested_fee_recipient: beneficiary,
+                    prev_randao: ordered_block.prev_randao,
+                    gas_limit: self.config.block_gas_limit,
+                },
+            )
+            .unwrap();
+        // The gas limit of the block is always the configured one
+        evm_env.block_env.gas_limit = U256::from(self.config.block_gas_limit);
+        let london_active = self.chain_spec.is_london_active_at_block(ordered_block.number);
+        if let Some(base_fee) = self.config.base_fee_override.filter(|_| london_active) {
+            evm_env.block_env.basefee = U256::from(base_fee);
+        }
+
+        let mut block = Block {
+            header: Header {
+                ommers_hash: EMPTY_OMMER_ROOT_HASH,
+                beneficiary,
+                timestamp: ordered_block.timestamp,
+                mix_hash: ordered_block.prev_randao,
+                nonce: self.config.block_nonce.into(),
+                base_fee_per_gas: london_active.then(|| evm_env.block_env.basefee.to::<u64>()),
+                number: ordered_block.number,
+                gas_limit: self.config.block_gas_limit,
+                difficulty: self.config.block_difficulty,
+                ..Default::default()
+            },
+            body: BlockBody::default(),
+        };
+
+        if self.chain_spec.is_shanghai_active_at_timestamp(block.timestamp) {
+            if ordered_block.withdrawals.is_empty() {
+                block.header.withdrawals_root = Some(EMPTY_WITHDRAWALS);
+                block.body.withdrawals = Some(Withdrawals::default());
+            } else {
+                block.header.withdrawals_root =
+                    Some(proofs::calculate_withdrawals_root(&ordered_block.withdrawals));
+                block.body.withdrawals = Some(ordered_block.withdrawals);
+            }
+        }
+
+        // only determine cancun fields when active
+        if self.chain_spec.is_cancun_active_at_timestamp(block.timestamp) {
+            block.header.parent_beacon_block_root = Some(
+                self.config
+                    .parent_beacon_block_root
+                    .parent_beacon_block_root(&ordered_block, parent_header),
+            );
+            block.header.excess_blob_gas =
+                Some(next_block_excess_blob_gas(&self.chain_spec, parent_header, block.timestamp));
+        }
+
+        // Discard the invalid txs. The denylist is held until the transactions are filtered, so
+        // that a concurrent update applies to the whole block or not at all.
+        let sender_denylist = self.sender_denylist.as_ref().map(|senders| senders.read().unwrap());
+        if let Some(sender_denylist) = &sender_denylist {
+            if ordered_block.presumed_valid &&
+                ordered_block.senders.iter().any(|sender| sender_denylist.contains(sender))
+            {
+                ordered_block.presumed_valid = false;
+            }
+        }
+        let (txs, senders, discarded_txs, predicted_accounts) = if ordered_block.presumed_valid {
+            self.metrics.presumed_valid_blocks.increment(1);
+            (ordered_block.transactions, ordered_block.senders, vec![], Default::default())
+        } else {
+            let start_time = Instant::now();
+            let parallel = self.config.parallel_filter(ordered_block.transactions.len());
+            let filtered = filter_invalid_txs(
+                &state,
+                ordered_block.transactions,
+                ordered_block.senders,
+                ordered_block.blob_sidecars.as_ref(),
+                evm_env.block_env.basefee.to::<u64>(),
+                evm_env.block_env.get_blob_gasprice(),
+                self.chain_spec.chain().id(),
+                self.chain_spec.is_prague_active_at_timestamp(block.timestamp),
+                self.config.block_gas_limit,
+                self.config.max_block_size,
+                self.config.max_tx_gas_limit,
+                self.config.max_txs_per_sender,
+                sender_denylist.as_deref(),
+                parallel,
+            );
+            self.metrics.filter_transaction_duration.record(start_time.elapsed());
+            filtered
+        };
+        drop(sender_denylist);
+
+        if block.header.excess_blob_gas.is_some() {
+            block.header.blob_gas_used = Some(blob_gas_used(&txs));
+        }
+        block.body.transactions = txs;
+        if self.is_state_untouched(&block) {
+            debug!(target: "execute_ordered_block",
+                id=?ordered_block.id,
+                number=?ordered_block.number,
+                "empty block, execution skipped"
+            );
+            return Ok(ExecuteOrderedBlockResult {
+                block,
+                senders,
+                execution_output: BlockExecutionOutput {
+                    state: Default::default(),
+                    receipts: vec![],
+                    requests: Default::default(),
+                    gas_used: 0,
+                },
+                discarded_txs,
+            });
+        }
+        let recovered_block = RecoveredBlock::new_unhashed(block, senders);
+
+        let reads = Arc::new(StateReads::default());
+        let state = CountingStateView::new(state, reads.clone());
+        let executor = if self.config.sequential_execution {
+            self.executor_provider.executor(serial_database! { WrapDatabaseRef(state) })
+        } else {
+            self.executor_provider.executor(parallel_database! { state })
+        };
+
+        let outcome = match executor.execute(&recovered_block) {
+            Ok(outcome) => outcome,
+            Err(error) if ordered_block.presumed_valid => {
+                // The trust assumption of Coordinator is broken, so the transactions must be
+                // filtered against a fresh state view, since the executor has consumed this one
+                error!(target: "execute_ordered_block",
+                    id=?ordered_block.id,
+                    number=?ordered_block.number,
+                    %error,
+                    "presumed valid block failed to execute, retrying with filtering"
+                );
+                self.metrics.presumed_valid_fallbacks.increment(1);
+                let state = self
+                    .storage
+                    .get_state_view_by_id(parent_header.number, ordered_block.parent_id)
+                    .map_err(BlockExecutionError::other)?;
+                let (block, senders) = recovered_block.split();
+                return self.execute_ordered_block(
+                    OrderedBlock {
+                        parent_id: ordered_block.parent_id,
+                        id: ordered_block.id,
+                        number: ordered_block.number,
+                        timestamp: ordered_block.timestamp,
+                        coinbase: ordered_block.coinbase,
+                        prev_randao: ordered_block.prev_randao,
+                        withdrawals: block.body.withdrawals.unwrap_or_default(),
+                        transactions: block.body.transactions,
+                        senders,
+                        blob_sidecars: ordered_block.blob_sidecars,
+                        presumed_valid: false,
+                    },
+                    parent_header,
+                    state,
+                );
+            }
+            Err(error) => {
+                if *PIPE_DUMP_FAILED_BLOCK {
+                    dump_failed_block(
+                        &self.config.failed_block_dump_dir(),
+                        ordered_block.id,
+                        &recovered_block,
+                    );
+                }
+                return Err(error);
+            }
+        };
+
+        self.metrics.account_reads_per_block.record(reads.accounts() as f64);
+        self.metrics.storage_reads_per_block.record(reads.storage_slots() as f64);
+
+        // The receipts root would silently be wrong otherwise
+        let logs_bloom =
+            validate_receipts(recovered_block.body().transactions.len(), &outcome.receipts)
+                .map_err(BlockExecutionError::other)?;
+        // Only checked with debug assertions, since it catches bugs of the filter rather than
+        // invalid blocks. An EIP-7702 authorization lets its authority run code spending its
+        // balance, so the blocks carrying one are not checked.
+        if cfg!(debug_assertions) &&
+            !predicted_accounts.is_empty() &&
+            recovered_block
+                .body()
+                .transactions
+                .iter()
+                .all(|tx| tx.transaction().authorization_list().is_none())
+        {
+            let drifts = account_drifts(&predicted_accounts, |sender| {
+                outcome.state.account(sender).and_then(|account| account.info.clone())
+            });
+            if !drifts.is_empty() {
+                error!(target: "execute_ordered_block",
+                    id=?ordered_block.id,
+                    number=?ordered_block.number,
+                    drifted_senders=?drifts.len(),
+                    "sender accounts drifted from the filter prediction"
+                );
+            }
+        }
         debug!(target: "execute_ordered_block",
             id=?ordered_block.id,
             parent_id=?ordered_block.parent_id,
@@ -340,11 +1987,22 @@ impl<Storage: GravityStorage> Core<Storage> {
 
         let (mut block, senders) = recovered_block.split();
         block.header.gas_used = outcome.gas_used;
-        (block, senders, outcome)
+        block.header.logs_bloom = logs_bloom;
+        Ok(ExecuteOrderedBlockResult { block, senders, execution_output: outcome, discarded_txs })
+    }
+
+    /// Whether executing the block changes no state at all, so that the executor can be skipped.
+    /// That's the case for a block without transactions or withdrawals, unless the active
+    /// hardforks require system calls (since Cancun) or block rewards (before Paris).
+    fn is_state_untouched(&self, block: &Block) -> bool {
+        block.body.transactions.is_empty() &&
+            block.body.withdrawals.as_ref().is_none_or(|withdrawals| withdrawals.is_empty()) &&
+            self.chain_spec.is_paris_active_at_block(block.number) == Some(true) &&
+            !self.chain_spec.is_cancun_active_at_timestamp(block.timestamp)
     }
 
-    /// Calculate the receipts root, logs bloom, and transactions root, etc. and fill them into the
-    /// block header.
+    /// Calculate the receipts root and transactions root, etc. and fill them into the block header.
+    /// The logs bloom is already filled by `execute_ordered_block`.
     fn calculate_roots(
         &self,
         block: &mut Block,
@@ -364,28 +2022,24 @@ impl<Storage: GravityStorage> Core<Storage> {
 
         let receipts_root =
             execution_outcome.ethereum_receipts_root(block.number).expect("Number is in range");
-        let logs_bloom =
-            execution_outcome.block_logs_bloom(block.number).expect("Number is in range");
 
         let transactions_root = proofs::calculate_transaction_root(&block.body.transactions);
 
         // Fill the block header with the calculated values
         block.header.transactions_root = transactions_root;
         block.header.receipts_root = receipts_root;
-        block.header.logs_bloom = logs_bloom;
 
         execution_outcome
     }
 
-    async fn make_canonical(&self, executed_block: ExecutedBlockWithTrieUpdates) {
+    async fn make_canonical(
+        &self,
+        executed_block: ExecutedBlockWithTrieUpdates,
+    ) -> Result<(), MakeCanonicalError> {
         let block_number = executed_block.recovered_block.number();
-
-        // Make executed block canonical
-        let (tx, rx) = oneshot::channel();
-        self.event_tx.send(PipeExecLayerEvent::MakeCanonical(executed_block, tx)).unwrap();
-        rx.await.unwrap();
-
+        send_make_canonical(&self.event_tx, executed_block).await?;
         debug!(target: "make_canonical", block_number=?block_number, "block made canonical");
+        Ok(())
     }
 
     fn init_storage(&self, execution_args: ExecutionArgs) {
@@ -393,159 +2047,825 @@ impl<Storage: GravityStorage> Core<Storage> {
             self.storage.insert_block_id(block_number, block_id);
         });
     }
-}
 
-/// Return the filtered valid transactions with sender without changing the relative order of
-/// the transactions.
-fn filter_invalid_txs<DB: ParallelDatabase>(
-    db: DB,
-    txs: Vec<TransactionSigned>,
-    senders: Vec<Address>,
-    base_fee_per_gas: U256,
-) -> (Vec<TransactionSigned>, Vec<Address>) {
-    let mut sender_idx: HashMap<&Address, Vec<usize>> = HashMap::default();
-    for (i, sender) in senders.iter().enumerate() {
-        sender_idx.entry(sender).or_insert_with(Vec::new).push(i);
-    }
-
-    let is_tx_valid = |tx: &TransactionSigned, sender: &Address, account: &mut AccountInfo| {
-        if account.nonce != tx.transaction().nonce() {
-            debug!(target: "filter_invalid_txs",
-                tx_hash=?tx.hash(),
-                sender=?sender,
-                nonce=?tx.transaction().nonce(),
-                account_nonce=?account.nonce,
-                "nonce mismatch"
-            );
-            return false;
+    /// Replay the latest `startup_replay_blocks` canonical blocks on top of the historical states
+    /// of their parents, and check that each replayed block hashes to the stored one. Returns the
+    /// number of replayed blocks.
+    fn replay_canonical_blocks(&self) -> Result<u64, StartupReplayError> {
+        let head = self.epoch.read().unwrap().latest_block_number;
+        let first = (head + 1).saturating_sub(self.config.startup_replay_blocks).max(1);
+        if first > head {
+            return Ok(0);
         }
-        let gas_spent = U256::from(tx.transaction().gas_limit()) *
-            (U256::from(tx.transaction().priority_fee_or_price()) + base_fee_per_gas);
-        if account.balance < gas_spent {
-            debug!(target: "filter_invalid_txs",
-                tx_hash=?tx.hash(),
-                sender=?sender,
-                balance=?account.balance,
-                gas_spent=?gas_spent,
-                "insufficient balance"
+        let canonical_block = |block_number| {
+            self.storage
+                .get_canonical_block(block_number)
+                .ok_or(StartupReplayError::BlockUnavailable(block_number))
+        };
+        let block_id = |block_number| {
+            self.storage
+                .get_block_id(block_number)
+                .ok_or(StartupReplayError::BlockIdUnavailable(block_number))
+        };
+        let mut parent = canonical_block(first - 1)?;
+        for block_number in first..=head {
+            let stored = canonical_block(block_number)?;
+            // Filtered again rather than presumed valid, so that a transaction made invalid by a
+            // corrupted state shows up as a hash mismatch instead of an execution failure
+            let ordered_block = canonical_ordered_block(
+                &stored,
+                block_id(block_number - 1)?,
+                block_id(block_number)?,
+                false,
             );
-            return false;
+            let state = self.storage.get_historical_state_view(block_number - 1)?;
+            let result = self
+                .install(|| self.execute_ordered_block(ordered_block, parent.header(), state))
+                .map_err(|error| StartupReplayError::ExecutionFailed { block_number, error })?;
+            let mut block = result.block;
+            let execution_outcome = self.calculate_roots(&mut block, result.execution_output);
+            block.header.state_root =
+                self.storage.historical_state_root(block_number - 1, &execution_outcome.bundle)?;
+            block.header.parent_hash = parent.hash();
+            let block_hash = block.header.hash_slow();
+            if block_hash != stored.hash() {
+                return Err(StartupReplayError::HashMismatch {
+                    block_number,
+                    expected: stored.hash(),
+                    got: block_hash,
+                });
+            }
+            self.metrics.startup_replayed_blocks.increment(1);
+            parent = stored;
         }
-        account.balance -= gas_spent;
-        account.nonce += 1;
-        true
-    };
+        Ok(head + 1 - first)
+    }
+}
 
-    let invalid_idxs = sender_idx
-        .into_par_iter()
-        .flat_map(|(sender, idxs)| {
-            if let Some(mut account) = db.basic_ref(*sender).unwrap() {
-                idxs.into_iter()
-                    .filter(|&idx| !is_tx_valid(&txs[idx], sender, &mut account))
-                    .collect()
-            } else {
-                // Sender should exist in the state
-                debug!(target: "filter_invalid_txs",
-                    tx_hash=?txs[idxs[0]].hash(),
-                    sender=?sender,
-                    "sender not found"
-                );
-                idxs
-            }
-        })
-        .collect::<HashSet<_>>();
+/// The ordered block to execute the canonical block again.
+fn canonical_ordered_block(
+    block: &RecoveredBlock<Block>,
+    parent_id: B256,
+    id: B256,
+    presumed_valid: bool,
+) -> OrderedBlock {
+    OrderedBlock {
+        parent_id,
+        id,
+        number: block.header().number,
+        timestamp: block.header().timestamp,
+        coinbase: block.header().beneficiary,
+        prev_randao: block.header().mix_hash,
+        withdrawals: block.body().withdrawals.clone().unwrap_or_default(),
+        transactions: block.body().transactions.clone(),
+        senders: block.senders().to_vec(),
+        blob_sidecars: None,
+        presumed_valid,
+    }
+}
 
-    if !invalid_idxs.is_empty() {
-        let mut filtered_txs = Vec::with_capacity(txs.len() - invalid_idxs.len());
-        let mut filtered_senders = Vec::with_capacity(filtered_txs.capacity());
-        for (i, (tx, sender)) in txs.into_iter().zip(senders.into_iter()).enumerate() {
-            if invalid_idxs.contains(&i) {
-                continue;
-            }
-            filtered_txs.push(tx);
-            filtered_senders.push(sender);
+/// Build the dedicated thread pool for execution. Returns `None` (falling back to the global pool)
+/// if the pool can't be built.
+fn build_executor_pool(num_threads: usize) -> Option<rayon::ThreadPool> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .thread_name(|i| format!("pipe-exec-{i}"))
+        .build()
+        .inspect_err(
+            |err| error!(target: "PipeExecService", %err, "Failed to build executor thread pool"),
+        )
+        .ok()
+}
+
+/// Send the executed block to EL and wait until it's made canonical.
+async fn send_make_canonical<N: NodePrimitives>(
+    event_tx: &std::sync::mpsc::Sender<PipeExecLayerEvent<N>>,
+    executed_block: ExecutedBlockWithTrieUpdates<N>,
+) -> Result<(), MakeCanonicalError> {
+    let (tx, rx) = oneshot::channel();
+    event_tx
+        .send(PipeExecLayerEvent::MakeCanonical(executed_block, tx))
+        .map_err(|_| MakeCanonicalError::EventChannelClosed)?;
+    rx.await.map_err(|_| MakeCanonicalError::Cancelled)
+}
+
+/// Calculate the `excess_blob_gas` of the Cancun block following `parent_header` according to the
+/// EIP-4844 update rule. The excess blob gas of the first Cancun block is zero.
+fn next_block_excess_blob_gas(
+    chain_spec: &ChainSpec,
+    parent_header: &Header,
+    timestamp: u64,
+) -> u64 {
+    parent_header
+        .maybe_next_block_excess_blob_gas(chain_spec.blob_params_at_timestamp(timestamp))
+        .unwrap_or_default()
+}
+
+/// Path of the dump of a failed block. The timestamp keeps the dumps of the same block, e.g. failed
+/// again after a restart, from overwriting each other.
+fn failed_block_dump_path(dir: &Path, block_id: B256, timestamp: SystemTime) -> PathBuf {
+    let millis = timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis();
+    dir.join(format!("{block_id}-{millis}.json"))
+}
+
+/// Write the block which failed to execute into `<block id>-<unix millis>.json` under `dir` for
+/// debugging. Errors are only logged, so that a failed dump never fails the caller further.
+fn dump_failed_block(dir: &Path, block_id: B256, block: &RecoveredBlock<Block>) {
+    let path = failed_block_dump_path(dir, block_id, SystemTime::now());
+    let result = std::fs::create_dir_all(dir)
+        .map_err(serde_json::Error::io)
+        .and_then(|()| write_failed_block(&path, block));
+    match result {
+        Ok(()) => warn!(target: "execute_ordered_block", path=?path, "failed block dumped"),
+        Err(err) => {
+            error!(target: "execute_ordered_block", path=?path, error=?err, "failed to dump block")
+        }
+    }
+}
+
+fn write_failed_block(path: &Path, block: &RecoveredBlock<Block>) -> Result<(), serde_json::Error> {
+    let file = std::fs::File::create(path).map_err(serde_json::Error::io)?;
+    serde_json::to_writer(std::io::BufWriter::new(file), block)
+}
+
+/// Load a block dumped when it failed to execute, see `PIPE_DUMP_FAILED_BLOCK`.
+pub fn load_failed_block(path: &Path) -> Result<RecoveredBlock<Block>, serde_json::Error> {
+    let file = std::fs::File::open(path).map_err(serde_json::Error::io)?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+}
+
+/// Errors of replaying a dumped block with [`replay_failed_block`]
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError {
+    /// The dump can't be read or decoded
+    #[error("failed to load dumped block: {0}")]
+    Load(#[from] serde_json::Error),
+    /// The block failed to execute again
+    #[error(transparent)]
+    Execution(#[from] BlockExecutionError),
+}
+
+/// Execute a block dumped when it failed to execute again, e.g. to reproduce the failure under a
+/// debugger. The block is executed by `EthExecutorProvider` as the pipeline does by default, on
+/// top of `state`, which should be the state view of its parent block as returned by
+/// `GravityStorage::get_state_view`.
+pub fn replay_failed_block<StateView: ParallelDatabase>(
+    path: &Path,
+    chain_spec: Arc<ChainSpec>,
+    state: StateView,
+) -> Result<BlockExecutionOutput<Receipt>, ReplayError> {
+    let block = load_failed_block(path)?;
+    let executor = EthExecutorProvider::ethereum(chain_spec).executor(parallel_database! { state });
+    Ok(executor.execute(&block)?)
+}
+
+/// Sum the blob gas used by the EIP-4844 transactions.
+fn blob_gas_used(txs: &[TransactionSigned]) -> u64 {
+    txs.iter().filter_map(|tx| tx.blob_gas_used()).sum()
+}
+
+/// Errors returned by [`PipeExecLayerApi`] to Coordinator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum PipeExecLayerError {
+    /// The pipeline has been shut down or halted after a failure
+    #[error("pipeline closed")]
+    Closed,
+    /// The block has been discarded by a rollback before the request was served
+    #[error("block {0} discarded by rollback")]
+    Discarded(B256),
+    /// The ordered block doesn't carry exactly one sender per transaction
+    #[error("block {block_id} has {transactions} transactions but {senders} senders")]
+    SendersMismatch { block_id: B256, transactions: usize, senders: usize },
+    /// A withdrawal of the ordered block is malformed, so the withdrawals root would fail
+    /// consensus
+    #[error("block {block_id} has invalid withdrawal at position {position}: {reason}")]
+    InvalidWithdrawal { block_id: B256, position: usize, reason: InvalidWithdrawalReason },
+    /// A block of a batch doesn't follow the previous block of the batch, by number or by parent
+    /// id
+    #[error("block {block_id} at position {position} doesn't follow the previous block")]
+    NonContiguousBlock { block_id: B256, position: usize },
+    /// The coinbase of the ordered block is the zero address, which
+    /// `PipeExecLayerConfig::zero_coinbase` rejects
+    #[error("block {block_id} has zero coinbase")]
+    ZeroCoinbase { block_id: B256 },
+    /// `force_make_canonical` is called without `PipeExecLayerConfig::allow_force_canonical`
+    #[error("forcing blocks canonical is disabled")]
+    ForceCanonicalDisabled,
+    /// `set_sender_denylist` is called without `PipeExecLayerConfig::sender_denylist`
+    #[error("sender denylist is disabled")]
+    SenderDenylistDisabled,
+    /// No executed block with the number and hash is waiting for verification
+    #[error("block {block_number} with hash {block_hash} is not waiting for verification")]
+    NotAwaitingVerification { block_number: u64, block_hash: B256 },
+    /// The block has been verified, so it's made canonical and can no longer be cancelled
+    #[error("block {0} has been verified and can no longer be cancelled")]
+    NotCancellable(B256),
+}
+
+/// Check that the ordered block carries one sender per transaction and valid withdrawals, and
+/// that its coinbase is not the zero address if rejected by `zero_coinbase`.
+fn validate_ordered_block(
+    block: &OrderedBlock,
+    zero_coinbase: ZeroCoinbasePolicy,
+) -> Result<(), PipeExecLayerError> {
+    if zero_coinbase == ZeroCoinbasePolicy::Reject && block.coinbase.is_zero() {
+        return Err(PipeExecLayerError::ZeroCoinbase { block_id: block.id });
+    }
+    if block.transactions.len() != block.senders.len() {
+        return Err(PipeExecLayerError::SendersMismatch {
+            block_id: block.id,
+            transactions: block.transactions.len(),
+            senders: block.senders.len(),
+        });
+    }
+    validate_withdrawals(&block.withdrawals).map_err(|(position, reason)| {
+        PipeExecLayerError::InvalidWithdrawal { block_id: block.id, position, reason }
+    })
+}
+
+/// Why a withdrawal of an ordered block is rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidWithdrawalReason {
+    /// The index is not greater than the index of the previous withdrawal
+    #[error("index {index} does not follow previous index {previous}")]
+    NonMonotonicIndex { index: u64, previous: u64 },
+    /// The amount is zero, which the consensus layer never withdraws
+    #[error("zero amount")]
+    ZeroAmount,
+    /// The validator index is beyond the validator registry limit of the consensus layer
+    #[error("validator index {validator_index} out of range")]
+    ValidatorIndexOutOfRange { validator_index: u64 },
+    /// The address is zero, i.e. the withdrawal credentials of the validator are unset
+    #[error("zero address")]
+    ZeroAddress,
+}
+
+/// Maximum number of validators in the registry of the consensus layer, i.e.
+/// `VALIDATOR_REGISTRY_LIMIT`, which bounds the validator indices.
+const VALIDATOR_REGISTRY_LIMIT: u64 = 1 << 40;
+
+/// Check that the withdrawal indices are strictly increasing, the amounts are nonzero, the
+/// validator indices are within the validator registry limit and the addresses are nonzero.
+/// Returns the position of the first invalid withdrawal otherwise.
+fn validate_withdrawals(
+    withdrawals: &Withdrawals,
+) -> Result<(), (usize /* position */, InvalidWithdrawalReason)> {
+    let mut previous: Option<u64> = None;
+    for (position, withdrawal) in withdrawals.iter().enumerate() {
+        if let Some(previous) = previous.filter(|previous| withdrawal.index <= *previous) {
+            return Err((
+                position,
+                InvalidWithdrawalReason::NonMonotonicIndex { index: withdrawal.index, previous },
+            ));
+        }
+        if withdrawal.amount == 0 {
+            return Err((position, InvalidWithdrawalReason::ZeroAmount));
+        }
+        if withdrawal.validator_index >= VALIDATOR_REGISTRY_LIMIT {
+            return Err((
+                position,
+                InvalidWithdrawalReason::ValidatorIndexOutOfRange {
+                    validator_index: withdrawal.validator_index,
+                },
+            ));
+        }
+        if withdrawal.address.is_zero() {
+            return Err((position, InvalidWithdrawalReason::ZeroAddress));
+        }
+        previous = Some(withdrawal.index);
+    }
+    Ok(())
+}
+
+/// Why the receipts returned by the executor don't match the executed block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+enum InvalidReceiptsError {
+    /// There isn't exactly one receipt for each transaction
+    #[error("{receipts} receipts for {transactions} transactions")]
+    CountMismatch { receipts: usize, transactions: usize },
+    /// The cumulative gas used decreases from the previous receipt
+    #[error("cumulative gas used {cumulative_gas_used} of receipt {index} is below {previous}")]
+    DecreasingCumulativeGasUsed { index: usize, cumulative_gas_used: u64, previous: u64 },
+}
+
+/// Check that there's one receipt for each of the `tx_count` transactions of the block, and that
+/// the cumulative gas used never decreases across the receipts. Returns the logs bloom of the
+/// block, folded from the bloom of each receipt while walking them, so that sealing the block
+/// doesn't take another pass over the receipts.
+fn validate_receipts(tx_count: usize, receipts: &[Receipt]) -> Result<Bloom, InvalidReceiptsError> {
+    if receipts.len() != tx_count {
+        return Err(InvalidReceiptsError::CountMismatch {
+            receipts: receipts.len(),
+            transactions: tx_count,
+        });
+    }
+    let mut logs_bloom = Bloom::ZERO;
+    let mut previous = 0;
+    for (index, receipt) in receipts.iter().enumerate() {
+        if receipt.cumulative_gas_used < previous {
+            return Err(InvalidReceiptsError::DecreasingCumulativeGasUsed {
+                index,
+                cumulative_gas_used: receipt.cumulative_gas_used,
+                previous,
+            });
         }
-        (filtered_txs, filtered_senders)
-    } else {
-        (txs, senders)
+        previous = receipt.cumulative_gas_used;
+        logs_bloom.accrue_bloom(&receipt.bloom());
     }
+    Ok(logs_bloom)
 }
 
 /// Called by Coordinator
 #[derive(Debug)]
 pub struct PipeExecLayerApi {
-    ordered_block_tx: UnboundedSender<OrderedBlock>,
-    executed_block_hash_rx: Arc<Channel<B256 /* block id */, B256 /* block hash */>>,
-    verified_block_hash_tx: Arc<Channel<B256 /* block id */, B256 /* block hash */>>,
+    ordered_block_tx: Sender<OrderedBlock>,
+    /// Held while pushing ordered blocks, so that the blocks of a batch are queued back to back
+    push_lock: tokio::sync::Mutex<()>,
+    executed_block_hash_rx: Arc<Channel<B256 /* block id */, ExecutedBlockInfo>>,
+    verified_block_hash_tx: Arc<Channel<B256 /* block id */, Verification>>,
+    simulator: Arc<dyn Simulator>,
+    shutdown_tx: watch::Sender<bool>,
+    stopped_rx: watch::Receiver<bool>,
+    pause_at_tx: watch::Sender<Option<u64>>,
+    head: Arc<HeadCell>,
+    awaiting_verification: Arc<Mutex<BTreeMap<u64 /* block number */, ExecutedBlockMeta>>>,
+    cancellations: Arc<Cancellations>,
+    allow_force_canonical: bool,
+    execution_outcomes: Arc<ExecutionOutcomeCache>,
+    block_statuses: Arc<BlockStatusMap>,
+    sender_denylist: Option<Arc<RwLock<HashSet<Address>>>>,
+    zero_coinbase: ZeroCoinbasePolicy,
 }
 
 impl PipeExecLayerApi {
-    /// Push ordered block to EL for execution.
-    /// Returns `None` if the channel has been closed.
-    pub fn push_ordered_block(&self, block: OrderedBlock) -> Option<()> {
-        self.ordered_block_tx.send(block).ok()
+    /// Execute the ordered block on top of the latest executed block without committing
+    /// anything, for simulation. The pipeline is not affected.
+    pub fn simulate(&self, block: OrderedBlock) -> Result<SimulationResult, SimulationError> {
+        self.simulator.simulate_ordered_block(block)
     }
 
-    /// Pull executed block hash from EL for verification.
-    /// Returns `None` if the channel has been closed.
-    pub async fn pull_executed_block_hash(&self, block_id: B256) -> Option<B256> {
-        self.executed_block_hash_rx.wait(block_id).await
+    /// Execute and seal the ordered block on top of the latest executed block without committing
+    /// anything, returning the hash which the pipeline would seal it with. Lighter than
+    /// `simulate`, but the latest executed block must have been made canonical, since the state
+    /// root is calculated against its state. The pipeline is not affected.
+    pub fn compute_block_hash(
+        &self,
+        block: OrderedBlock,
+    ) -> Result<ComputedBlockHash, SimulationError> {
+        self.simulator.compute_block_hash(block)
     }
 
-    /// Push verified block hash to EL for commit.
-    /// Returns `None` if the channel has been closed.
-    pub fn commit_executed_block_hash(&self, block_meta: ExecutedBlockMeta) -> Option<()> {
-        self.verified_block_hash_tx.notify(block_meta.block_id, block_meta.block_hash)
+    /// Write the canonical block along with its execution outcome into the file at `path` in the
+    /// versioned binary layout of [`BlockDump`], e.g. to diff the dumps of two diverged nodes
+    /// byte for byte. The outcome is taken from the cache of recent execution outcomes, or the
+    /// block is executed again on top of the historical state of its parent. Read the dump back
+    /// with [`load_block_dump`].
+    pub fn dump_block(&self, block_number: u64, path: &Path) -> Result<(), BlockDumpError> {
+        self.simulator.block_dump(block_number)?.write(path)
     }
-}
 
-impl Drop for PipeExecLayerApi {
-    fn drop(&mut self) {
-        self.verified_block_hash_tx.close();
+    /// Push ordered block to EL for execution, waiting while
+    /// `PipeExecLayerConfig::ordered_block_channel_capacity` blocks are already queued.
+    /// Malformed blocks are rejected without reaching the pipeline.
+    pub async fn push_ordered_block(&self, block: OrderedBlock) -> Result<(), PipeExecLayerError> {
+        validate_ordered_block(&block, self.zero_coinbase)?;
+        let _guard = self.push_lock.lock().await;
+        self.ordered_block_tx.send(block).await.map_err(|_| PipeExecLayerError::Closed)
     }
-}
 
-/// Called by EL.
-#[derive(Debug)]
-pub struct PipeExecLayerExt<N: NodePrimitives> {
-    /// Receive events from PipeExecService
-    pub event_rx: std::sync::Mutex<std::sync::mpsc::Receiver<PipeExecLayerEvent<N>>>,
-}
+    /// Push a batch of consecutive ordered blocks to EL for execution, e.g. when catching up from
+    /// a backlog. The whole batch is checked up front, so that nothing is pushed if a block is
+    /// malformed or doesn't follow the previous block of the batch by number and parent id.
+    /// The blocks are queued back to back, without the blocks pushed concurrently in between.
+    /// Like `push_ordered_block`, each block waits while the queue is full, so a batch larger
+    /// than `PipeExecLayerConfig::ordered_block_channel_capacity` is queued as the pipeline
+    /// drains the queue.
+    pub async fn push_ordered_blocks(
+        &self,
+        blocks: Vec<OrderedBlock>,
+    ) -> Result<(), PipeExecLayerError> {
+        for (position, block) in blocks.iter().enumerate() {
+            validate_ordered_block(block, self.zero_coinbase)?;
+            if position > 0 {
+                let previous = &blocks[position - 1];
+                if block.number != previous.number + 1 || block.parent_id != previous.id {
+                    return Err(PipeExecLayerError::NonContiguousBlock {
+                        block_id: block.id,
+                        position,
+                    });
+                }
+            }
+        }
+        let _guard = self.push_lock.lock().await;
+        for block in blocks {
+            self.ordered_block_tx.send(block).await.map_err(|_| PipeExecLayerError::Closed)?;
+        }
+        Ok(())
+    }
 
-/// A static instance of `PipeExecLayerExt` used for dispatching events.
-pub static PIPE_EXEC_LAYER_EXT: OnceCell<Box<dyn Any + Send + Sync>> = OnceCell::new();
+    /// Pull executed block hash from EL for verification.
+    pub async fn pull_executed_block_hash(
+        &self,
+        block_id: B256,
+    ) -> Result<B256, PipeExecLayerError> {
+        self.pull_executed_block(block_id).await.map(|block_info| block_info.block_hash)
+    }
 
-pub fn get_pipe_exec_layer_ext<N: NodePrimitives>() -> Option<&'static PipeExecLayerExt<N>> {
-    PIPE_EXEC_LAYER_EXT.get().map(|ext| ext.downcast_ref::<PipeExecLayerExt<N>>().unwrap())
-}
+    /// Pull executed block hash from EL for verification, along with the gas used and the number
+    /// of transactions of the block. Either this or `pull_executed_block_hash` takes the block,
+    /// not both.
+    pub async fn pull_executed_block(
+        &self,
+        block_id: B256,
+    ) -> Result<ExecutedBlockInfo, PipeExecLayerError> {
+        self.executed_block_hash_rx
+            .wait(block_id)
+            .await
+            .ok_or_else(|| self.closed_or_discarded(block_id))
+    }
 
-/// Whether to validate the block before inserting it into `TreeState`.
-pub static PIPE_VALIDATE_BLOCK_BEFORE_INSERT: Lazy<bool> =
-    Lazy::new(|| std::env::var("PIPE_VALIDATE_BLOCK_BEFORE_INSERT").is_ok());
+    /// Push verified block hash to EL for commit.
+    pub fn commit_executed_block_hash(
+        &self,
+        block_meta: ExecutedBlockMeta,
+    ) -> Result<(), PipeExecLayerError> {
+        self.verified_block_hash_tx
+            .notify(block_meta.block_id, Verification::Verified(block_meta.block_hash))
+            .ok_or(PipeExecLayerError::Closed)
+    }
 
-/// Create a new `PipeExecLayerApi` instance and launch a `PipeExecService`.
-pub fn new_pipe_exec_layer_api<Storage: GravityStorage>(
-    chain_spec: Arc<ChainSpec>,
-    storage: Storage,
-    latest_block_header: Header,
-    latest_block_hash: B256,
-    execution_args_rx: oneshot::Receiver<ExecutionArgs>,
-) -> PipeExecLayerApi {
-    let (ordered_block_tx, ordered_block_rx) = tokio::sync::mpsc::unbounded_channel();
-    let executed_block_hash_ch = Arc::new(Channel::new());
-    let verified_block_hash_ch = Arc::new(Channel::new());
-    let (event_tx, event_rx) = std::sync::mpsc::channel();
+    /// Reject the executed block, so that the block and all blocks after it are discarded, and
+    /// the pipeline rolls back to the parent of the block once the parent is made canonical.
+    /// The blocks replacing the discarded ones can be pushed after this returns.
+    /// Returns `PipeExecLayerError::Discarded` if the block has already been discarded by another
+    /// rollback.
+    pub async fn reject_executed_block(&self, block_id: B256) -> Result<(), PipeExecLayerError> {
+        let (rolled_back_tx, rolled_back_rx) = oneshot::channel();
+        self.verified_block_hash_tx
+            .notify(block_id, Verification::Rejected(rolled_back_tx))
+            .ok_or(PipeExecLayerError::Closed)?;
+        rolled_back_rx.await.map_err(|_| self.closed_or_discarded(block_id))
+    }
 
-    let latest_block_number = latest_block_header.number;
+    /// Cancel the block, e.g. when Coordinator learns that it's invalid while it's in flight, so
+    /// that it's discarded at its next stage boundary along with the blocks after it, and the
+    /// pipeline rolls back to its parent once the parent is made canonical. A block not received
+    /// yet is discarded as soon as it's processed. The returned future resolves once rolled
+    /// back, and the blocks replacing the discarded ones can be pushed after that.
+    /// Returns `PipeExecLayerError::NotCancellable` if the block has already been verified, or
+    /// `PipeExecLayerError::Discarded` if the block is discarded by another rollback first.
+    pub fn cancel(&self, block_id: B256) -> impl Future<Output = Result<(), PipeExecLayerError>> {
+        let (rolled_back_tx, rolled_back_rx) = oneshot::channel();
+        let cancelled = self.cancellations.cancel(block_id, rolled_back_tx, || {
+            matches!(
+                self.block_statuses.get(block_id),
+                BlockStatus::Verified | BlockStatus::Canonical
+            )
+        });
+        let executed_block_hash_rx = self.executed_block_hash_rx.clone();
+        async move {
+            cancelled?;
+            rolled_back_rx.await.map_err(|_| {
+                if executed_block_hash_rx.is_closed() {
+                    PipeExecLayerError::Closed
+                } else {
+                    PipeExecLayerError::Discarded(block_id)
+                }
+            })
+        }
+    }
+
+    /// Why a request about the block was dropped by the pipeline. The channel of executed block
+    /// hashes is closed along with the pipeline, while a rollback only discards its entries.
+    fn closed_or_discarded(&self, block_id: B256) -> PipeExecLayerError {
+        if self.executed_block_hash_rx.is_closed() {
+            PipeExecLayerError::Closed
+        } else {
+            PipeExecLayerError::Discarded(block_id)
+        }
+    }
+
+    /// Break-glass recovery tool: verify the executed block in place of Coordinator, e.g. when
+    /// Coordinator is offline, so that the block proceeds to be made canonical. Requires
+    /// `PipeExecLayerConfig::allow_force_canonical`. The block must have been executed with the
+    /// given hash and be waiting for verification, otherwise
+    /// `PipeExecLayerError::NotAwaitingVerification` is returned.
+    pub fn force_make_canonical(
+        &self,
+        block_number: u64,
+        block_hash: B256,
+    ) -> Result<(), PipeExecLayerError> {
+        if !self.allow_force_canonical {
+            return Err(PipeExecLayerError::ForceCanonicalDisabled);
+        }
+        let block_meta = self
+            .awaiting_verification
+            .lock()
+            .unwrap()
+            .get(&block_number)
+            .copied()
+            .filter(|meta| meta.block_hash == block_hash)
+            .ok_or(PipeExecLayerError::NotAwaitingVerification { block_number, block_hash })?;
+        warn!(target: "PipeExecLayerApi",
+            block_number=?block_number,
+            block_id=?block_meta.block_id,
+            block_hash=?block_hash,
+            "VERIFICATION BYPASSED: forcing block canonical without Coordinator"
+        );
+        // Replaces the verification of Coordinator if it has arrived meanwhile but not been taken
+        self.verified_block_hash_tx
+            .renotify(block_meta.block_id, Verification::Verified(block_hash))
+            .ok_or(PipeExecLayerError::Closed)
+    }
+
+    /// Replace the sender denylist, which applies to the blocks executed from now on. Requires
+    /// `PipeExecLayerConfig::sender_denylist` to be set.
+    pub fn set_sender_denylist(
+        &self,
+        senders: impl IntoIterator<Item = Address>,
+    ) -> Result<(), PipeExecLayerError> {
+        let sender_denylist =
+            self.sender_denylist.as_ref().ok_or(PipeExecLayerError::SenderDenylistDisabled)?;
+        let senders = senders.into_iter().collect::<HashSet<_>>();
+        warn!(target: "PipeExecLayerApi",
+            denied_senders=?senders.len(),
+            "sender denylist updated"
+        );
+        *sender_denylist.write().unwrap() = senders;
+        Ok(())
+    }
+
+    /// The latest block made canonical, without reading the database. `last_commit_elapsed`
+    /// keeps growing if the pipeline is stuck, which can be used for liveness checks.
+    pub fn head(&self) -> PipeHead {
+        self.head.get()
+    }
+
+    /// Get the execution outcome, i.e. the receipts and the state changes, of a recently
+    /// committed block without reading the database or executing it again, e.g. for log queries
+    /// on the tail of the chain. Returns `None` if the block is not committed yet or is older than
+    /// the last `PipeExecLayerConfig::execution_outcome_cache_capacity` committed blocks.
+    pub fn execution_outcome(&self, block_number: u64) -> Option<Arc<ExecutionOutcome>> {
+        self.execution_outcomes.get(block_number)
+    }
+
+    /// How far the block has gone through the pipeline, e.g. for Coordinator to reconcile with
+    /// the pipeline after a restart or a missed notification. Blocks committed before the
+    /// pipeline was built, or older than the last `PipeExecLayerConfig::block_status_capacity`
+    /// committed blocks, are `BlockStatus::Unknown` like the blocks not executed yet, and can be
+    /// told apart by comparing their numbers with `head`.
+    pub fn status(&self, block_id: B256) -> BlockStatus {
+        self.block_statuses.get(block_id)
+    }
+
+    /// Stop accepting ordered blocks above `block_number`, so that the pipeline halts once it has
+    /// been made canonical. The ordered blocks pushed meanwhile are kept queued until `resume`.
+    /// Replaces the previous target, if any.
+    pub fn pause_at(&self, block_number: u64) {
+        self.pause_at_tx.send_replace(Some(block_number));
+    }
+
+    /// Resume accepting ordered blocks after `pause_at`.
+    pub fn resume(&self) {
+        self.pause_at_tx.send_replace(None);
+    }
+
+    /// Stop accepting ordered blocks, and close the channels once the blocks already accepted
+    /// have been made canonical (or aborted). Blocks pushed but not yet accepted are discarded.
+    /// The returned future resolves when the service has stopped. Coordinator must keep
+    /// verifying the in-flight blocks until then.
+    pub fn shutdown(&self) -> impl Future<Output = ()> {
+        self.shutdown_tx.send_replace(true);
+        let mut stopped_rx = self.stopped_rx.clone();
+        async move {
+            // Err means the service has been dropped, which has stopped as well
+            let _ = stopped_rx.wait_for(|stopped| *stopped).await;
+        }
+    }
+}
+
+impl Drop for PipeExecLayerApi {
+    fn drop(&mut self) {
+        self.verified_block_hash_tx.close();
+    }
+}
+
+/// Called by EL.
+#[derive(Debug)]
+pub struct PipeExecLayerExt<N: NodePrimitives> {
+    /// Receive events from PipeExecService
+    pub event_rx: std::sync::Mutex<std::sync::mpsc::Receiver<PipeExecLayerEvent<N>>>,
+    stage_timings: Arc<StageTimingsBuffer>,
+    canonical_tx: broadcast::Sender<Canonicalized>,
+}
+
+impl<N: NodePrimitives> PipeExecLayerExt<N> {
+    /// Get the stage timings of a recently committed block.
+    /// Returns `None` if the block is not committed yet or is not recent enough.
+    pub fn stage_timings(&self, block_number: u64) -> Option<StageTimings> {
+        self.stage_timings.get(block_number)
+    }
+
+    /// Get the stage timings of the most recently committed blocks (at most
+    /// `PipeExecLayerConfig::stage_timings_capacity`), in ascending order of block number.
+    pub fn recent_stage_timings(&self) -> Vec<(u64 /* block number */, StageTimings)> {
+        self.stage_timings.recent()
+    }
+
+    /// Subscribe to the blocks made canonical from now on, e.g. for RPC caches. A subscriber
+    /// falling behind by more than `PipeExecLayerConfig::canonical_notification_capacity` blocks
+    /// misses the oldest ones and gets `RecvError::Lagged`, without slowing down the pipeline.
+    pub fn subscribe_canonical(&self) -> broadcast::Receiver<Canonicalized> {
+        self.canonical_tx.subscribe()
+    }
+}
+
+/// A static instance of `PipeExecLayerExt` used for dispatching events.
+pub static PIPE_EXEC_LAYER_EXT: OnceCell<Box<dyn Any + Send + Sync>> = OnceCell::new();
+
+pub fn get_pipe_exec_layer_ext<N: NodePrimitives>() -> Option<&'static PipeExecLayerExt<N>> {
+    PIPE_EXEC_LAYER_EXT.get().map(|ext| ext.downcast_ref::<PipeExecLayerExt<N>>().unwrap())
+}
+
+/// Whether to validate the block before inserting it into `TreeState`.
+pub static PIPE_VALIDATE_BLOCK_BEFORE_INSERT: Lazy<bool> =
+    Lazy::new(|| std::env::var("PIPE_VALIDATE_BLOCK_BEFORE_INSERT").is_ok());
+
+/// Whether to recalculate the state root of each sealed block from scratch, and panic if it
+/// diverges from the merklized one. Slow, for development only.
+pub static PIPE_DOUBLE_CHECK_STATE_ROOT: Lazy<bool> =
+    Lazy::new(|| std::env::var("PIPE_DOUBLE_CHECK_STATE_ROOT").is_ok());
+
+/// Whether to dump the block which failed to execute into a JSON file under
+/// [`PipeExecLayerConfig::failed_block_dump_dir`].
+pub static PIPE_DUMP_FAILED_BLOCK: Lazy<bool> =
+    Lazy::new(|| std::env::var("PIPE_DUMP_FAILED_BLOCK").is_ok());
+
+/// Create a new `PipeExecLayerApi` instance and launch a `PipeExecService` with the default
+/// config.
+pub fn new_pipe_exec_layer_api<Storage: GravityStorage>(
+    chain_spec: Arc<ChainSpec>,
+    storage: Storage,
+    latest_block_header: Header,
+    latest_block_hash: B256,
+    execution_args_rx: oneshot::Receiver<ExecutionArgs>,
+) -> PipeExecLayerApi {
+    new_pipe_exec_layer_api_with_config(
+        chain_spec,
+        storage,
+        latest_block_header,
+        latest_block_hash,
+        execution_args_rx,
+        PipeExecLayerConfig::default(),
+    )
+    .expect("default config is valid")
+}
+
+/// Create a new `PipeExecLayerApi` instance and launch a `PipeExecService`.
+/// Returns an error if the config is invalid.
+pub fn new_pipe_exec_layer_api_with_config<Storage: GravityStorage>(
+    chain_spec: Arc<ChainSpec>,
+    storage: Storage,
+    latest_block_header: Header,
+    latest_block_hash: B256,
+    execution_args_rx: oneshot::Receiver<ExecutionArgs>,
+    config: PipeExecLayerConfig,
+) -> Result<PipeExecLayerApi, PipeExecLayerConfigError> {
+    new_pipe_exec_layer_api_with_executor(
+        EthExecutorProvider::ethereum(chain_spec.clone()),
+        chain_spec,
+        storage,
+        latest_block_header,
+        latest_block_hash,
+        execution_args_rx,
+        config,
+    )
+}
+
+/// Create a new `PipeExecLayerApi` instance and launch a `PipeExecService`, which executes the
+/// ordered blocks with the given executor provider instead of the Ethereum one, e.g. for custom
+/// precompiles or instrumented execution. Returns an error if the config is invalid.
+pub fn new_pipe_exec_layer_api_with_executor<
+    Storage: GravityStorage,
+    ExecutorProvider: BlockExecutorProvider<Primitives = EthPrimitives>,
+>(
+    executor_provider: ExecutorProvider,
+    chain_spec: Arc<ChainSpec>,
+    storage: Storage,
+    latest_block_header: Header,
+    latest_block_hash: B256,
+    execution_args_rx: oneshot::Receiver<ExecutionArgs>,
+    config: PipeExecLayerConfig,
+) -> Result<PipeExecLayerApi, PipeExecLayerConfigError> {
+    new_pipe_exec_layer_api_with_evm_config(
+        executor_provider,
+        EthEvmConfig::new(chain_spec.clone()),
+        chain_spec,
+        storage,
+        latest_block_header,
+        latest_block_hash,
+        execution_args_rx,
+        config,
+    )
+}
+
+/// Create a new `PipeExecLayerApi` instance and launch a `PipeExecService`, which derives the
+/// block env of each ordered block with the given EVM config instead of `EthEvmConfig`.
+///
+/// The pipeline calls `evm_config.next_evm_env` with the parent header and the
+/// `NextBlockEnvAttributes` taken from the ordered block, i.e. its timestamp, coinbase and
+/// prev randao, with the configured `block_gas_limit` as the gas limit. The base fee of the
+/// returned env is written into the block header and used to filter the transactions, while its
+/// gas limit is always overridden by `block_gas_limit`. The rest of the env is only used by the
+/// executor provider, which builds its own EVM from the block header, so precompiles and
+/// inspectors are registered through the `ConfigureEvm` of the executor provider, which should
+/// usually wrap the same config.
+/// Returns an error if the config is invalid.
+pub fn new_pipe_exec_layer_api_with_evm_config<
+    Storage: GravityStorage,
+    ExecutorProvider: BlockExecutorProvider<Primitives = EthPrimitives>,
+    EvmConfig: ConfigureEvmEnv<Header = Header>,
+>(
+    executor_provider: ExecutorProvider,
+    evm_config: EvmConfig,
+    chain_spec: Arc<ChainSpec>,
+    storage: Storage,
+    latest_block_header: Header,
+    latest_block_hash: B256,
+    execution_args_rx: oneshot::Receiver<ExecutionArgs>,
+    config: PipeExecLayerConfig,
+) -> Result<PipeExecLayerApi, PipeExecLayerConfigError> {
+    let (api, ext) = build_pipe_exec_layer(
+        executor_provider,
+        evm_config,
+        chain_spec,
+        storage,
+        latest_block_header,
+        latest_block_hash,
+        execution_args_rx,
+        config,
+    )?;
+    PIPE_EXEC_LAYER_EXT.get_or_init(|| Box::new(ext));
+    Ok(api)
+}
+
+/// Launch a `PipeExecService`, returning the api for Coordinator and the extension for EL.
+fn build_pipe_exec_layer<
+    Storage: GravityStorage,
+    ExecutorProvider: BlockExecutorProvider<Primitives = EthPrimitives>,
+    EvmConfig: ConfigureEvmEnv<Header = Header>,
+>(
+    executor_provider: ExecutorProvider,
+    evm_config: EvmConfig,
+    chain_spec: Arc<ChainSpec>,
+    storage: Storage,
+    latest_block_header: Header,
+    latest_block_hash: B256,
+    execution_args_rx: oneshot::Receiver<ExecutionArgs>,
+    config: PipeExecLayerConfig,
+) -> Result<(PipeExecLayerApi, PipeExecLayerExt<EthPrimitives>), PipeExecLayerConfigError> {
+    config.validate()?;
+    config.validate_chain(&chain_spec)?;
+
+    let (ordered_block_tx, ordered_block_rx) =
+        tokio::sync::mpsc::channel(config.ordered_block_channel_capacity);
+    let executed_block_hash_ch = Arc::new(Channel::new());
+    let verified_block_hash_ch = Arc::new(Channel::new());
+    let (event_tx, event_rx) = std::sync::mpsc::channel();
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let (stopped_tx, stopped_rx) = watch::channel(false);
+    let (pause_at_tx, pause_at_rx) = watch::channel(None);
+
+    let latest_block_number = latest_block_header.number;
+    let stage_timings = Arc::new(StageTimingsBuffer::new(config.stage_timings_capacity));
+    let executed_hashes = ExecutedHashCache::new(config.executed_block_cache_capacity);
+    let execution_outcomes =
+        Arc::new(ExecutionOutcomeCache::new(config.execution_outcome_cache_capacity));
+    let block_statuses = Arc::new(BlockStatusMap::new(config.block_status_capacity));
+    let awaiting_verification = Arc::new(Mutex::default());
+    let cancellations = Arc::new(Cancellations::default());
+    let allow_force_canonical = config.allow_force_canonical;
+    let sender_denylist =
+        config.sender_denylist.clone().map(|senders| Arc::new(RwLock::new(senders)));
+    let zero_coinbase = config.zero_coinbase;
+    let max_in_flight_blocks = config.max_in_flight_blocks;
+    let (canonical_tx, _) = broadcast::channel(config.canonical_notification_capacity);
+    let executor_pool = config.executor_threads.and_then(build_executor_pool);
+    let metrics = PipeExecLayerMetrics::new(config.metrics_sink.as_ref());
     let start_time = Instant::now();
+    let head = Arc::new(HeadCell::new(latest_block_number, latest_block_hash, start_time));
     let service = PipeExecService {
         core: Arc::new(Core {
             executed_block_hash_tx: executed_block_hash_ch.clone(),
             verified_block_hash_rx: verified_block_hash_ch.clone(),
             storage,
-            evm_config: EthEvmConfig::new(chain_spec.clone()),
+            evm_config,
+            executor_provider,
             chain_spec,
             event_tx,
+            canonical_tx: canonical_tx.clone(),
+            latest_executed_header: Mutex::new(latest_block_header.clone()),
             execute_block_barrier: Channel::new_with_states([(
                 latest_block_number,
                 (latest_block_header, start_time),
@@ -553,18 +2873,2493 @@ pub fn new_pipe_exec_layer_api<Storage: GravityStorage>(
             merklize_barrier: Channel::new_with_states([(latest_block_number, ())]),
             seal_barrier: Channel::new_with_states([(latest_block_number, latest_block_hash)]),
             make_canonical_barrier: Channel::new_with_states([(latest_block_number, start_time)]),
-            metrics: PipeExecLayerMetrics::default(),
+            epoch: RwLock::new(Epoch {
+                id: 0,
+                latest_block_number,
+                latest_block_id: None,
+                reorder_buffer: ReorderBuffer::new(config.reorder_buffer_capacity),
+                alive_tx: watch::channel(()).0,
+            }),
+            config,
+            executor_pool,
+            metrics,
+            throughput: Mutex::default(),
+            in_flight_bundle_state_bytes: AtomicUsize::new(0),
+            consecutive_execution_failures: AtomicUsize::new(0),
+            halted: AtomicBool::new(false),
+            head: head.clone(),
+            canonical_blocks: Mutex::default(),
+            stage_timings: stage_timings.clone(),
+            executed_hashes,
+            execution_outcomes: execution_outcomes.clone(),
+            block_statuses: block_statuses.clone(),
+            sender_denylist: sender_denylist.clone(),
+            awaiting_verification: awaiting_verification.clone(),
+            cancellations: cancellations.clone(),
         }),
         ordered_block_rx,
         execution_args_rx,
+        shutdown_rx,
+        stopped_tx,
+        pause_at_rx,
+        in_flight_permits: Arc::new(Semaphore::new(max_in_flight_blocks)),
+    };
+    let simulator = service.core.clone();
+    tokio::spawn(service.run());
+
+    Ok((
+        PipeExecLayerApi {
+            ordered_block_tx,
+            push_lock: tokio::sync::Mutex::default(),
+            executed_block_hash_rx: executed_block_hash_ch,
+            verified_block_hash_tx: verified_block_hash_ch,
+            simulator,
+            shutdown_tx,
+            stopped_rx,
+            pause_at_tx,
+            head,
+            awaiting_verification,
+            cancellations,
+            allow_force_canonical,
+            execution_outcomes,
+            block_statuses,
+            sender_denylist,
+            zero_coinbase,
+        },
+        PipeExecLayerExt { event_rx: event_rx.into(), stage_timings, canonical_tx },
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloy_consensus::{TxEip4844, TxLegacy};
+    use alloy_eips::{
+        eip4788::{BEACON_ROOTS_ADDRESS, BEACON_ROOTS_CODE},
+        eip4844::{calc_excess_blob_gas, DATA_GAS_PER_BLOB},
+        eip4895::Withdrawal,
+        merge::BEACON_NONCE,
+    };
+    use alloy_primitives::{keccak256, PrimitiveSignature as Signature, TxKind};
+    use reth_chainspec::{ChainSpecBuilder, EthereumHardfork, ForkCondition, MAINNET};
+    use reth_ethereum_primitives::Transaction as EthTransaction;
+    use reth_evm::{system_calls::OnStateHook, State};
+    use reth_execution_types::BlockExecutionResult;
+    use revm::{
+        db::{BundleState, CacheDB, EmptyDB},
+        primitives::{AccountInfo, Bytecode},
+    };
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
     };
-    tokio::spawn(service.run(latest_block_number));
+    use test_utils::InMemoryStorage;
+
+    fn blob_tx(nonce: u64, blob_count: usize) -> TransactionSigned {
+        TransactionSigned::new_unhashed(
+            EthTransaction::Eip4844(TxEip4844 {
+                nonce,
+                blob_versioned_hashes: vec![B256::ZERO; blob_count],
+                ..Default::default()
+            }),
+            Signature::test_signature(),
+        )
+    }
+
+    #[test]
+    fn test_blob_gas_fields() {
+        let cancun_timestamp = 1710338135;
+        let parent_header = Header {
+            timestamp: cancun_timestamp,
+            excess_blob_gas: Some(4 * DATA_GAS_PER_BLOB),
+            blob_gas_used: Some(5 * DATA_GAS_PER_BLOB),
+            ..Default::default()
+        };
+        let txs = vec![blob_tx(0, 1), TransactionSigned::default(), blob_tx(1, 2)];
+
+        assert_eq!(blob_gas_used(&txs), 3 * DATA_GAS_PER_BLOB);
+        assert_eq!(
+            next_block_excess_blob_gas(&MAINNET, &parent_header, cancun_timestamp + 12),
+            calc_excess_blob_gas(4 * DATA_GAS_PER_BLOB, 5 * DATA_GAS_PER_BLOB)
+        );
+
+        // The parent of the first Cancun block has no blob gas fields
+        let pre_cancun_header = Header { timestamp: cancun_timestamp - 12, ..Default::default() };
+        assert_eq!(next_block_excess_blob_gas(&MAINNET, &pre_cancun_header, cancun_timestamp), 0);
+    }
+
+    fn empty_executed_block() -> ExecutedBlockWithTrieUpdates {
+        ExecutedBlockWithTrieUpdates::new(
+            Arc::new(RecoveredBlock::default()),
+            Arc::new(ExecutionOutcome::default()),
+            Arc::new(HashedPostState::default()),
+            Arc::new(TrieUpdates::default()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_make_canonical_event_receiver_dropped() {
+        let (event_tx, event_rx) = std::sync::mpsc::channel::<PipeExecLayerEvent<EthPrimitives>>();
+        drop(event_rx);
+        assert_eq!(
+            send_make_canonical(&event_tx, empty_executed_block()).await,
+            Err(MakeCanonicalError::EventChannelClosed)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_make_canonical_cancelled() {
+        let (event_tx, event_rx) = std::sync::mpsc::channel::<PipeExecLayerEvent<EthPrimitives>>();
+        let consumer = std::thread::spawn(move || {
+            // Drop the event without replying
+            let event = event_rx.recv().unwrap();
+            assert!(matches!(event, PipeExecLayerEvent::MakeCanonical(..)));
+        });
+        assert_eq!(
+            send_make_canonical(&event_tx, empty_executed_block()).await,
+            Err(MakeCanonicalError::Cancelled)
+        );
+        consumer.join().unwrap();
+    }
+
+    /// Storage serving the same state for every block, without committing anything but the
+    /// block ids. The state root is always zero.
+    struct MockStorage {
+        state: CacheDB<EmptyDB>,
+        block_ids: Mutex<BTreeMap<u64, B256>>,
+        /// Number of state views served
+        state_views: Arc<AtomicUsize>,
+        /// Receiver which each merklization waits on, if set, until a value is sent or the sender
+        /// is dropped
+        merklize_gate: Option<Mutex<std::sync::mpsc::Receiver<()>>>,
+        /// Whether rolling back fails
+        failing_rollback: bool,
+        /// Number of the next state views which fail to be served
+        state_view_failures: Arc<AtomicUsize>,
+    }
+
+    impl MockStorage {
+        fn new(state: CacheDB<EmptyDB>) -> Self {
+            Self {
+                state,
+                block_ids: Mutex::default(),
+                state_views: Arc::default(),
+                merklize_gate: None,
+                failing_rollback: false,
+                state_view_failures: Arc::default(),
+            }
+        }
+
+        fn with_failing_rollback(mut self) -> Self {
+            self.failing_rollback = true;
+            self
+        }
+
+        fn with_merklize_gate(mut self, merklize_gate: std::sync::mpsc::Receiver<()>) -> Self {
+            self.merklize_gate = Some(Mutex::new(merklize_gate));
+            self
+        }
+    }
+
+    impl GravityStorage for MockStorage {
+        type StateView = CacheDB<EmptyDB>;
+
+        fn get_state_view(
+            &self,
+            block_number: u64,
+        ) -> Result<(B256, Self::StateView), GravityStorageError> {
+            if self
+                .state_view_failures
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .is_ok()
+            {
+                return Err(GravityStorageError::TooNew(block_number));
+            }
+            self.state_views.fetch_add(1, Ordering::Relaxed);
+            Ok((self.get_block_id(block_number).unwrap_or_default(), self.state.clone()))
+        }
+
+        fn get_block_id(&self, block_number: u64) -> Option<B256> {
+            self.block_ids.lock().unwrap().get(&block_number).copied()
+        }
+
+        fn insert_block_id(&self, block_number: u64, block_id: B256) {
+            self.block_ids.lock().unwrap().insert(block_number, block_id);
+        }
+
+        fn insert_bundle_state(&self, _block_number: u64, _bundle_state: &BundleState) {}
+
+        fn update_canonical(&self, _block_number: u64, _block_hash: B256) {}
+
+        fn rollback_to(&self, block_number: u64) -> Result<(), GravityStorageError> {
+            if self.failing_rollback {
+                return Err(GravityStorageError::Unsupported("rollback_to"));
+            }
+            self.block_ids.lock().unwrap().retain(|number, _| *number <= block_number);
+            Ok(())
+        }
+
+        fn unwind_canonical(
+            &self,
+            block_number: u64,
+            _block_hash: B256,
+        ) -> Result<(), GravityStorageError> {
+            self.rollback_to(block_number)
+        }
+
+        fn state_root_with_updates(
+            &self,
+            _block_number: u64,
+        ) -> Result<(B256, Arc<HashedPostState>, Arc<TrieUpdates>), GravityStorageError> {
+            if let Some(merklize_gate) = &self.merklize_gate {
+                let _ = merklize_gate.lock().unwrap().recv();
+            }
+            Ok((B256::ZERO, Default::default(), Default::default()))
+        }
+
+        fn state_root(&self, _block_number: u64) -> Result<B256, GravityStorageError> {
+            Ok(B256::ZERO)
+        }
+    }
+
+    /// Executor provider which counts the executed blocks and transactions without executing them.
+    /// The gas limit of each transaction is reported as used.
+    #[derive(Debug, Clone, Default)]
+    struct CountingExecutorProvider {
+        executed_blocks: Arc<AtomicUsize>,
+        executed_txs: Arc<AtomicUsize>,
+        /// Number of the next blocks which fail to execute, without being counted
+        failures: Arc<AtomicUsize>,
+    }
+
+    impl BlockExecutorProvider for CountingExecutorProvider {
+        type Primitives = EthPrimitives;
+
+        type Executor<'db> = Self;
+
+        fn executor<'db, DB, PDB>(&self, _: DatabaseEnum<DB, PDB>) -> Self::Executor<'db>
+        where
+            DB: Database + 'db,
+            PDB: ParallelDatabase + 'db,
+        {
+            self.clone()
+        }
+    }
+
+    impl<'db> Executor<'db> for CountingExecutorProvider {
+        type Primitives = EthPrimitives;
+        type Error = BlockExecutionError;
+
+        fn execute_one(
+            &mut self,
+            block: &RecoveredBlock<Block>,
+        ) -> Result<BlockExecutionResult<Receipt>, Self::Error> {
+            if self
+                .failures
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .is_ok()
+            {
+                return Err(BlockExecutionError::msg("injected failure"));
+            }
+            let tx_count = block.body().transactions.len();
+            self.executed_blocks.fetch_add(1, Ordering::Relaxed);
+            self.executed_txs.fetch_add(tx_count, Ordering::Relaxed);
+            Ok(BlockExecutionResult {
+                receipts: vec![Receipt::default(); tx_count],
+                requests: Default::default(),
+                gas_used: block.body().transactions.iter().map(|tx| tx.gas_limit()).sum(),
+            })
+        }
+
+        fn execute_one_with_state_hook<F>(
+            &mut self,
+            block: &RecoveredBlock<Block>,
+            _state_hook: F,
+        ) -> Result<BlockExecutionResult<Receipt>, Self::Error>
+        where
+            F: OnStateHook + 'static,
+        {
+            self.execute_one(block)
+        }
+
+        fn into_state(self) -> Box<dyn State + 'db> {
+            Box::new(revm::db::State::builder().with_bundle_update().build())
+        }
+
+        fn size_hint(&self) -> usize {
+            0
+        }
+    }
+
+    fn transfer_tx(nonce: u64) -> TransactionSigned {
+        TransactionSigned::new_unhashed(
+            EthTransaction::Legacy(TxLegacy {
+                chain_id: Some(MAINNET.chain().id()),
+                nonce,
+                gas_price: 1,
+                gas_limit: 21_000,
+                to: TxKind::Call(Address::ZERO),
+                ..Default::default()
+            }),
+            Signature::test_signature(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_custom_executor_provider() {
+        let sender = Address::with_last_byte(1);
+        let executor_provider = CountingExecutorProvider::default();
+        let (api, _ext) = build_test_pipeline_on(
+            executor_provider.clone(),
+            MAINNET.clone(),
+            MockStorage::new(funded_state(sender)),
+            PipeExecLayerConfig::default(),
+        );
 
-    PIPE_EXEC_LAYER_EXT.get_or_init(|| Box::new(PipeExecLayerExt { event_rx: event_rx.into() }));
+        let result = api
+            .simulate(OrderedBlock {
+                parent_id: B256::ZERO,
+                id: B256::with_last_byte(1),
+                number: 1,
+                timestamp: 1,
+                coinbase: Address::ZERO,
+                prev_randao: B256::ZERO,
+                withdrawals: Default::default(),
+                // The last transaction is discarded for its nonce gap
+                transactions: vec![transfer_tx(0), transfer_tx(1), transfer_tx(3)],
+                senders: vec![sender; 3],
+                blob_sidecars: None,
+                presumed_valid: false,
+            })
+            .unwrap();
+        assert_eq!(result.receipts.len(), 2);
+        assert_eq!(result.discarded_txs.len(), 1);
+        assert_eq!(executor_provider.executed_txs.load(Ordering::Relaxed), 2);
+    }
+
+    /// Core of a pipeline whose latest block is the genesis, without launching the service
+    fn test_core(config: PipeExecLayerConfig) -> Arc<Core<MockStorage, CountingExecutorProvider>> {
+        test_core_with_chain_spec(MAINNET.clone(), config)
+    }
+
+    fn test_core_with_chain_spec(
+        chain_spec: Arc<ChainSpec>,
+        config: PipeExecLayerConfig,
+    ) -> Arc<Core<MockStorage, CountingExecutorProvider>> {
+        Arc::new(Core::standalone(
+            CountingExecutorProvider::default(),
+            EthEvmConfig::new(chain_spec.clone()),
+            chain_spec,
+            MockStorage::new(CacheDB::new(EmptyDB::default())),
+            config,
+        ))
+    }
+
+    fn empty_ordered_block(number: u64) -> OrderedBlock {
+        OrderedBlock {
+            parent_id: B256::with_last_byte(number as u8 - 1),
+            id: B256::with_last_byte(number as u8),
+            number,
+            timestamp: number,
+            coinbase: Address::ZERO,
+            prev_randao: B256::ZERO,
+            withdrawals: Default::default(),
+            transactions: vec![],
+            senders: vec![],
+            blob_sidecars: None,
+            presumed_valid: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_retry() {
+        let core = test_core(
+            PipeExecLayerConfig::default()
+                .with_verify_timeout(Duration::from_millis(50))
+                .with_verify_attempts(3),
+        );
+        let (ordered_block, epoch) = core.accept(empty_ordered_block(1)).pop().unwrap();
+        let block_id = ordered_block.id;
+        let block_info =
+            ExecutedBlockInfo { block_hash: B256::with_last_byte(10), gas_used: 0, tx_count: 0 };
+
+        // Coordinator misses the first push, and verifies the block once pushed again
+        let coordinator = {
+            let core = core.clone();
+            tokio::spawn(async move {
+                core.executed_block_hash_tx.wait(block_id).await.unwrap();
+                let block_info = core.executed_block_hash_tx.wait(block_id).await.unwrap();
+                core.verified_block_hash_rx
+                    .notify(block_id, Verification::Verified(block_info.block_hash))
+                    .unwrap();
+            })
+        };
+        let outcome = core.verify_executed_block_hash(block_id, block_info, &epoch).await.unwrap();
+        assert!(matches!(outcome, VerifyOutcome::Verified));
+        coordinator.await.unwrap();
+
+        // Coordinator never verifies the block
+        let (ordered_block, epoch) = core.accept(empty_ordered_block(2)).pop().unwrap();
+        let block_info =
+            ExecutedBlockInfo { block_hash: B256::with_last_byte(20), gas_used: 0, tx_count: 0 };
+        assert!(matches!(
+            core.verify_executed_block_hash(ordered_block.id, block_info, &epoch).await,
+            Err(ProcessError::VerificationTimeout { attempts: 3, .. })
+        ));
+    }
+
+    /// Verify the executed block as Coordinator, returning its hash.
+    async fn verify_block(api: &PipeExecLayerApi, block_id: B256) -> B256 {
+        let block_hash = api.pull_executed_block_hash(block_id).await.unwrap();
+        api.commit_executed_block_hash(ExecutedBlockMeta { block_id, block_hash }).unwrap();
+        block_hash
+    }
+
+    async fn wait_head(api: &PipeExecLayerApi, block_id: B256) -> PipeHead {
+        loop {
+            let head = api.head();
+            if head.block_id == Some(block_id) {
+                return head;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    /// Launch a pipeline on top of the genesis, whose blocks are made canonical by a mock EL.
+    /// The EL thread returns the reorgs it has seen once the pipeline is dropped.
+    fn spawn_test_pipeline() -> (PipeExecLayerApi, std::thread::JoinHandle<Vec<(u64, u64)>>) {
+        let (api, _, el) = spawn_test_pipeline_with_config(PipeExecLayerConfig::default());
+        (api, el)
+    }
+
+    /// Launch a pipeline like `build_test_pipeline_with_config`, whose blocks are made canonical
+    /// by a mock EL sharing the extension.
+    fn spawn_test_pipeline_with_config(
+        config: PipeExecLayerConfig,
+    ) -> (
+        PipeExecLayerApi,
+        Arc<PipeExecLayerExt<EthPrimitives>>,
+        std::thread::JoinHandle<Vec<(u64, u64)>>,
+    ) {
+        let (api, ext) = build_test_pipeline_with_config(config);
+        let ext = Arc::new(ext);
+        let el = spawn_test_el(ext.clone());
+        (api, ext, el)
+    }
+
+    fn build_test_pipeline_with_config(
+        config: PipeExecLayerConfig,
+    ) -> (PipeExecLayerApi, PipeExecLayerExt<EthPrimitives>) {
+        build_test_pipeline_on(
+            CountingExecutorProvider::default(),
+            MAINNET.clone(),
+            MockStorage::new(CacheDB::new(EmptyDB::default())),
+            config,
+        )
+    }
 
-    PipeExecLayerApi {
-        ordered_block_tx,
-        executed_block_hash_rx: executed_block_hash_ch,
-        verified_block_hash_tx: verified_block_hash_ch,
+    /// Launch a pipeline on top of the genesis, executing the blocks with `executor_provider` on
+    /// top of `storage`.
+    fn build_test_pipeline_on<
+        Storage: GravityStorage,
+        ExecutorProvider: BlockExecutorProvider<Primitives = EthPrimitives>,
+    >(
+        executor_provider: ExecutorProvider,
+        chain_spec: Arc<ChainSpec>,
+        storage: Storage,
+        config: PipeExecLayerConfig,
+    ) -> (PipeExecLayerApi, PipeExecLayerExt<EthPrimitives>) {
+        let (execution_args_tx, execution_args_rx) = oneshot::channel();
+        let (api, ext) = build_pipe_exec_layer(
+            executor_provider,
+            EthEvmConfig::new(chain_spec.clone()),
+            chain_spec,
+            storage,
+            Header::default(),
+            B256::ZERO,
+            execution_args_rx,
+            config,
+        )
+        .unwrap();
+        execution_args_tx
+            .send(ExecutionArgs { block_number_to_block_id: BTreeMap::from([(0, B256::ZERO)]) })
+            .unwrap();
+        (api, ext)
+    }
+
+    /// Launch a pipeline on top of the genesis of mainnet, executing the blocks with
+    /// `EthExecutorProvider` on top of `storage`.
+    fn build_eth_test_pipeline<Storage: GravityStorage>(
+        storage: Storage,
+        config: PipeExecLayerConfig,
+    ) -> (PipeExecLayerApi, PipeExecLayerExt<EthPrimitives>) {
+        build_test_pipeline_on(
+            EthExecutorProvider::ethereum(MAINNET.clone()),
+            MAINNET.clone(),
+            storage,
+            config,
+        )
+    }
+
+    /// Launch a pipeline like `build_eth_test_pipeline`, whose blocks are made canonical by a mock
+    /// EL. The extension is shared with the EL thread, which returns the reorgs it has seen once
+    /// the pipeline is dropped.
+    fn spawn_eth_test_pipeline<Storage: GravityStorage>(
+        storage: Storage,
+        config: PipeExecLayerConfig,
+    ) -> (
+        PipeExecLayerApi,
+        Arc<PipeExecLayerExt<EthPrimitives>>,
+        std::thread::JoinHandle<Vec<(u64, u64)>>,
+    ) {
+        let (api, ext) = build_eth_test_pipeline(storage, config);
+        let ext = Arc::new(ext);
+        let el = spawn_test_el(ext.clone());
+        (api, ext, el)
+    }
+
+    fn spawn_test_el(
+        ext: Arc<PipeExecLayerExt<EthPrimitives>>,
+    ) -> std::thread::JoinHandle<Vec<(u64, u64)>> {
+        // EL makes the blocks canonical, and collects the reorgs
+        std::thread::spawn(move || {
+            let mut reorgs = vec![];
+            while let Ok(event) = ext.event_rx.lock().unwrap().recv() {
+                match event {
+                    PipeExecLayerEvent::MakeCanonical(_, tx) => {
+                        let _ = tx.send(());
+                    }
+                    PipeExecLayerEvent::Reorg { from, to } => reorgs.push((from, to)),
+                    _ => {}
+                }
+            }
+            reorgs
+        })
+    }
+
+    /// Shut down the pipeline, and return what its EL thread has collected once the api is
+    /// dropped.
+    async fn stop_test_pipeline<T>(api: PipeExecLayerApi, el: std::thread::JoinHandle<T>) -> T {
+        api.shutdown().await;
+        drop(api);
+        el.join().unwrap()
+    }
+
+    /// State holding the sender funded for a few transfers
+    fn funded_state(sender: Address) -> CacheDB<EmptyDB> {
+        let mut state = CacheDB::new(EmptyDB::default());
+        state.insert_account_info(
+            sender,
+            AccountInfo { balance: U256::from(1_000_000), ..Default::default() },
+        );
+        state
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_subscribe_canonical() {
+        let (api, ext, el) = spawn_test_pipeline_with_config(PipeExecLayerConfig::default());
+        let mut subscribers = [ext.subscribe_canonical(), ext.subscribe_canonical()];
+
+        for number in 1..=2 {
+            api.push_ordered_block(empty_ordered_block(number)).await.unwrap();
+        }
+        let block_hashes = [
+            verify_block(&api, B256::with_last_byte(1)).await,
+            verify_block(&api, B256::with_last_byte(2)).await,
+        ];
+        for subscriber in &mut subscribers {
+            for (number, block_hash) in (1..=2).zip(block_hashes) {
+                let canonicalized = subscriber.recv().await.unwrap();
+                assert_eq!(canonicalized.block_id, B256::with_last_byte(number));
+                assert_eq!(canonicalized.block.header().number, number as u64);
+                assert_eq!(canonicalized.block.hash(), block_hash);
+            }
+        }
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_execution_outcome_cache() {
+        let (api, _, el) = spawn_test_pipeline_with_config(
+            PipeExecLayerConfig::default().with_execution_outcome_cache_capacity(2),
+        );
+
+        api.push_ordered_block(OrderedBlock {
+            transactions: vec![transfer_tx(0), transfer_tx(1)],
+            senders: vec![Address::with_last_byte(1); 2],
+            // The mock storage has no funded sender
+            presumed_valid: true,
+            ..empty_ordered_block(1)
+        })
+        .await
+        .unwrap();
+        verify_block(&api, B256::with_last_byte(1)).await;
+        wait_head(&api, B256::with_last_byte(1)).await;
+        let outcome = api.execution_outcome(1).unwrap();
+        assert_eq!(outcome.first_block, 1);
+        assert_eq!(outcome.receipts, vec![vec![Receipt::default(); 2]]);
+        assert!(api.execution_outcome(2).is_none());
+
+        // The oldest block is evicted
+        for number in 2..=3 {
+            api.push_ordered_block(empty_ordered_block(number)).await.unwrap();
+            verify_block(&api, B256::with_last_byte(number as u8)).await;
+        }
+        wait_head(&api, B256::with_last_byte(3)).await;
+        assert!(api.execution_outcome(1).is_none());
+        assert_eq!(api.execution_outcome(3).unwrap().first_block, 3);
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_status() {
+        let (api, el) = spawn_test_pipeline();
+        let block_id = B256::with_last_byte(1);
+        assert_eq!(api.status(block_id), BlockStatus::Unknown);
+
+        api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+        let block_hash = api.pull_executed_block_hash(block_id).await.unwrap();
+        assert_eq!(api.status(block_id), BlockStatus::Executed);
+        api.commit_executed_block_hash(ExecutedBlockMeta { block_id, block_hash }).unwrap();
+        wait_head(&api, block_id).await;
+        assert_eq!(api.status(block_id), BlockStatus::Canonical);
+        assert_eq!(api.status(B256::with_last_byte(2)), BlockStatus::Unknown);
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reorg() {
+        let (api, el) = spawn_test_pipeline();
+
+        for number in 1..=2 {
+            api.push_ordered_block(empty_ordered_block(number)).await.unwrap();
+        }
+        verify_block(&api, B256::with_last_byte(1)).await;
+        let block_hash = verify_block(&api, B256::with_last_byte(2)).await;
+        wait_head(&api, B256::with_last_byte(2)).await;
+
+        // Coordinator replaces the canonical block 2 with a block forking from block 1
+        let fork_id = B256::with_last_byte(20);
+        api.push_ordered_block(OrderedBlock {
+            id: fork_id,
+            timestamp: 3,
+            ..empty_ordered_block(2)
+        })
+        .await
+        .unwrap();
+        let fork_block_hash = verify_block(&api, fork_id).await;
+        let head = wait_head(&api, fork_id).await;
+        assert_eq!(head.block_number, 2);
+        assert_eq!(head.block_hash, fork_block_hash);
+        assert_ne!(fork_block_hash, block_hash);
+
+        // The pipeline goes on with the new branch
+        api.push_ordered_block(OrderedBlock { parent_id: fork_id, ..empty_ordered_block(3) })
+            .await
+            .unwrap();
+        verify_block(&api, B256::with_last_byte(3)).await;
+        wait_head(&api, B256::with_last_byte(3)).await;
+
+        api.shutdown().await;
+        assert_eq!(
+            api.push_ordered_block(empty_ordered_block(4)).await,
+            Err(PipeExecLayerError::Closed)
+        );
+        assert_eq!(
+            api.pull_executed_block_hash(B256::with_last_byte(4)).await,
+            Err(PipeExecLayerError::Closed)
+        );
+        drop(api);
+        assert_eq!(el.join().unwrap(), vec![(2, 1)]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_push_ordered_blocks() {
+        let (api, el) = spawn_test_pipeline();
+
+        // A gap in the batch rejects the whole batch
+        let gap = vec![empty_ordered_block(1), empty_ordered_block(2), empty_ordered_block(4)];
+        assert_eq!(
+            api.push_ordered_blocks(gap).await,
+            Err(PipeExecLayerError::NonContiguousBlock {
+                block_id: B256::with_last_byte(4),
+                position: 2
+            })
+        );
+        let fork = vec![
+            empty_ordered_block(1),
+            OrderedBlock { parent_id: B256::with_last_byte(10), ..empty_ordered_block(2) },
+        ];
+        assert_eq!(
+            api.push_ordered_blocks(fork).await,
+            Err(PipeExecLayerError::NonContiguousBlock {
+                block_id: B256::with_last_byte(2),
+                position: 1
+            })
+        );
+
+        // A batch larger than the ordered block channel capacity is queued as it's drained
+        let count = DEFAULT_ORDERED_BLOCK_CHANNEL_CAPACITY as u8 + 4;
+        api.push_ordered_blocks((1..=count as u64).map(empty_ordered_block).collect())
+            .await
+            .unwrap();
+        for number in 1..=count {
+            verify_block(&api, B256::with_last_byte(number)).await;
+        }
+        assert_eq!(wait_head(&api, B256::with_last_byte(count)).await.block_number, count as u64);
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_max_canonical_lag() {
+        let max_canonical_lag = 2;
+        let (api, ext) = build_test_pipeline_with_config(
+            PipeExecLayerConfig::default().with_max_canonical_lag(max_canonical_lag),
+        );
+        // EL makes each block canonical slowly
+        let el = std::thread::spawn(move || {
+            while let Ok(event) = ext.event_rx.lock().unwrap().recv() {
+                if let PipeExecLayerEvent::MakeCanonical(_, tx) = event {
+                    std::thread::sleep(Duration::from_millis(50));
+                    let _ = tx.send(());
+                }
+            }
+        });
+        let api = Arc::new(api);
+        let pusher = {
+            let api = api.clone();
+            tokio::spawn(async move {
+                for number in 1..=10 {
+                    api.push_ordered_block(empty_ordered_block(number)).await.unwrap();
+                }
+            })
+        };
+
+        // Coordinator verifies each block as soon as it's executed, when the make canonical stage
+        // must not be more than `max_canonical_lag` blocks behind
+        for number in 1..=10u8 {
+            let block_id = B256::with_last_byte(number);
+            let block_hash = api.pull_executed_block_hash(block_id).await.unwrap();
+            let lag = number as u64 - api.head().block_number;
+            assert!(lag <= max_canonical_lag as u64, "block {number} executed {lag} blocks ahead");
+            api.commit_executed_block_hash(ExecutedBlockMeta { block_id, block_hash }).unwrap();
+        }
+        wait_head(&api, B256::with_last_byte(10)).await;
+        pusher.await.unwrap();
+
+        stop_test_pipeline(api, el).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_max_in_flight_bundle_state_bytes() {
+        let sender = Address::with_last_byte(1);
+        let (api, _, el) = spawn_eth_test_pipeline(
+            MockStorage::new(funded_state(sender)),
+            PipeExecLayerConfig::default().with_max_in_flight_bundle_state_bytes(1),
+        );
+
+        let block_id1 = B256::with_last_byte(1);
+        api.push_ordered_block(OrderedBlock {
+            transactions: vec![transfer_tx(0)],
+            senders: vec![sender],
+            ..empty_ordered_block(1)
+        })
+        .await
+        .unwrap();
+        let block_hash1 = api.pull_executed_block_hash(block_id1).await.unwrap();
+        // Block 1 holds more than the budget until it's verified and made canonical, so block 2
+        // is not executed meanwhile
+        let block_id2 = B256::with_last_byte(2);
+        api.push_ordered_block(empty_ordered_block(2)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(api.status(block_id2), BlockStatus::Unknown);
+
+        api.commit_executed_block_hash(ExecutedBlockMeta {
+            block_id: block_id1,
+            block_hash: block_hash1,
+        })
+        .unwrap();
+        verify_block(&api, block_id2).await;
+        assert_eq!(wait_head(&api, block_id2).await.block_number, 2);
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_max_consecutive_execution_failures() {
+        let executor_provider = CountingExecutorProvider::default();
+        let (api, ext) = build_test_pipeline_on(
+            executor_provider.clone(),
+            MAINNET.clone(),
+            MockStorage::new(CacheDB::new(EmptyDB::default())),
+            PipeExecLayerConfig::default().with_max_consecutive_execution_failures(3),
+        );
+        // EL makes the blocks canonical, and forwards the failures
+        let (failure_tx, mut failure_rx) = tokio::sync::mpsc::unbounded_channel();
+        let el = std::thread::spawn(move || {
+            while let Ok(event) = ext.event_rx.lock().unwrap().recv() {
+                match event {
+                    PipeExecLayerEvent::MakeCanonical(_, tx) => {
+                        let _ = tx.send(());
+                    }
+                    PipeExecLayerEvent::ExecutionFailed { block_id, .. } => {
+                        let _ = failure_tx.send(Ok(block_id));
+                    }
+                    PipeExecLayerEvent::Halted { reason } => {
+                        let _ = failure_tx.send(Err(reason));
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        // A failure below the limit rolls back to the parent, so the block can be delivered again,
+        // and the successful execution resets the count
+        let block_id1 = B256::with_last_byte(1);
+        executor_provider.failures.store(2, Ordering::Relaxed);
+        for _ in 0..2 {
+            api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+            assert_eq!(failure_rx.recv().await, Some(Ok(block_id1)));
+        }
+        api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+        verify_block(&api, block_id1).await;
+        wait_head(&api, block_id1).await;
+
+        // The third consecutive failure halts the pipeline
+        let block_id2 = B256::with_last_byte(2);
+        executor_provider.failures.store(3, Ordering::Relaxed);
+        for _ in 0..2 {
+            api.push_ordered_block(empty_ordered_block(2)).await.unwrap();
+            assert_eq!(failure_rx.recv().await, Some(Ok(block_id2)));
+        }
+        api.push_ordered_block(empty_ordered_block(2)).await.unwrap();
+        assert_eq!(failure_rx.recv().await, Some(Ok(block_id2)));
+        let Some(Err(reason)) = failure_rx.recv().await else { panic!("pipeline not halted") };
+        assert!(reason.contains("3 consecutive failures"), "{reason}");
+        assert_eq!(api.pull_executed_block_hash(block_id2).await, Err(PipeExecLayerError::Closed));
+        // The service stops receiving ordered blocks on its own
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while api.push_ordered_block(empty_ordered_block(3)).await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(executor_provider.executed_blocks.load(Ordering::Relaxed), 1);
+
+        stop_test_pipeline(api, el).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_state_view_failure() {
+        let storage = MockStorage::new(CacheDB::new(EmptyDB::default()));
+        let state_view_failures = storage.state_view_failures.clone();
+        let (api, ext) = build_test_pipeline_on(
+            CountingExecutorProvider::default(),
+            MAINNET.clone(),
+            storage,
+            PipeExecLayerConfig::default(),
+        );
+        let (failure_tx, mut failure_rx) = tokio::sync::mpsc::unbounded_channel();
+        let el = std::thread::spawn(move || {
+            while let Ok(event) = ext.event_rx.lock().unwrap().recv() {
+                match event {
+                    PipeExecLayerEvent::MakeCanonical(_, tx) => {
+                        let _ = tx.send(());
+                    }
+                    PipeExecLayerEvent::ExecutionFailed { block_id, error } => {
+                        let _ = failure_tx.send((block_id, error));
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        // The block whose parent state can't be served is rolled back like a failed execution,
+        // and is processed once delivered again
+        let block_id = B256::with_last_byte(1);
+        state_view_failures.store(1, Ordering::Relaxed);
+        api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+        let (failed_block_id, error) = failure_rx.recv().await.unwrap();
+        assert_eq!(failed_block_id, block_id);
+        assert!(error.contains("too new"), "{error}");
+        api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+        verify_block(&api, block_id).await;
+        wait_head(&api, block_id).await;
+
+        stop_test_pipeline(api, el).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_failing_storage_rollback() {
+        let executor_provider = CountingExecutorProvider::default();
+        let (api, ext) = build_test_pipeline_on(
+            executor_provider.clone(),
+            MAINNET.clone(),
+            MockStorage::new(CacheDB::new(EmptyDB::default())).with_failing_rollback(),
+            PipeExecLayerConfig::default(),
+        );
+        let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel();
+        let el = std::thread::spawn(move || {
+            while let Ok(event) = ext.event_rx.lock().unwrap().recv() {
+                match event {
+                    PipeExecLayerEvent::ExecutionFailed { block_id, .. } => {
+                        let _ = event_tx.send(Ok(block_id));
+                    }
+                    PipeExecLayerEvent::Halted { reason } => {
+                        let _ = event_tx.send(Err(reason));
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        // The failed block can't be rolled back, so the pipeline is halted instead of reporting
+        // the block as delivered again
+        executor_provider.failures.store(1, Ordering::Relaxed);
+        let block_id = B256::with_last_byte(1);
+        api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+        let Some(Err(reason)) = event_rx.recv().await else { panic!("pipeline not halted") };
+        assert!(reason.contains("failed to roll back storage to block 0"), "{reason}");
+        assert_eq!(api.pull_executed_block_hash(block_id).await, Err(PipeExecLayerError::Closed));
+
+        stop_test_pipeline(api, el).await;
+        assert!(event_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_single_node() {
+        let (api, ext, el) =
+            spawn_test_pipeline_with_config(PipeExecLayerConfig::default().with_single_node(true));
+
+        // The blocks are made canonical without anyone verifying their hashes
+        for number in 1..=3 {
+            api.push_ordered_block(empty_ordered_block(number)).await.unwrap();
+        }
+        let block_id3 = B256::with_last_byte(3);
+        tokio::time::timeout(Duration::from_secs(5), wait_head(&api, block_id3)).await.unwrap();
+        for number in 1u8..=3 {
+            assert_eq!(api.status(B256::with_last_byte(number)), BlockStatus::Canonical);
+        }
+        // Nothing is pushed to Coordinator
+        assert!(tokio::time::timeout(
+            Duration::from_millis(100),
+            api.pull_executed_block_hash(block_id3)
+        )
+        .await
+        .is_err());
+
+        // The verification is still timed, once the service has recorded the last block
+        api.shutdown().await;
+        assert_eq!(ext.recent_stage_timings().len(), 3);
+        drop(api);
+        el.join().unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cancel() {
+        let executor_provider = CountingExecutorProvider::default();
+        let (merklize_tx, merklize_rx) = std::sync::mpsc::channel();
+        let (api, ext) = build_test_pipeline_on(
+            executor_provider.clone(),
+            MAINNET.clone(),
+            MockStorage::new(CacheDB::new(EmptyDB::default())).with_merklize_gate(merklize_rx),
+            PipeExecLayerConfig::default(),
+        );
+        let el = spawn_test_el(Arc::new(ext));
+
+        // Block 1 is cancelled once executed, while it's held before merklization
+        let block_id1 = B256::with_last_byte(1);
+        api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+        while executor_provider.executed_blocks.load(Ordering::Relaxed) == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let cancelled = api.cancel(block_id1);
+        merklize_tx.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), cancelled).await.unwrap().unwrap();
+        assert_eq!(api.status(block_id1), BlockStatus::Unknown);
+        assert_eq!(api.head().block_number, 0);
+
+        // The block can be delivered again, and can no longer be cancelled once verified
+        drop(merklize_tx);
+        api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+        verify_block(&api, block_id1).await;
+        wait_head(&api, block_id1).await;
+        assert_eq!(api.cancel(block_id1).await, Err(PipeExecLayerError::NotCancellable(block_id1)));
+        assert_eq!(executor_provider.executed_blocks.load(Ordering::Relaxed), 2);
+
+        api.shutdown().await;
+        assert_eq!(api.cancel(block_id1).await, Err(PipeExecLayerError::Closed));
+        drop(api);
+        assert!(el.join().unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reject_executed_block() {
+        // Coordinator rejects block 1 explicitly, or verifies it with another hash
+        for hash_mismatch in [false, true] {
+            let sender = Address::with_last_byte(1);
+            let storage = InMemoryStorage::new(funded_state(sender));
+            // Block 2 is held once executed, until the release sender is dropped
+            let (held_tx, mut held_rx) = tokio::sync::mpsc::unbounded_channel();
+            let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+            let release_rx = Mutex::new(release_rx);
+            let config = PipeExecLayerConfig::default().with_tx_trace_sink(
+                move |block_number: u64, _: TxTrace| {
+                    if block_number == 2 {
+                        let _ = held_tx.send(());
+                        let _ = release_rx.lock().unwrap().recv();
+                    }
+                },
+            );
+            let (api, ext, el) = spawn_eth_test_pipeline(storage.clone(), config);
+            let mut canonical_rx = ext.subscribe_canonical();
+            let transfer_block = |number: u64| OrderedBlock {
+                transactions: vec![transfer_tx(number - 1)],
+                senders: vec![sender],
+                ..empty_ordered_block(number)
+            };
+
+            let (block_id1, block_id2) = (B256::with_last_byte(1), B256::with_last_byte(2));
+            for number in 1..=2 {
+                api.push_ordered_block(transfer_block(number)).await.unwrap();
+            }
+            let block_hash1 = api.pull_executed_block_hash(block_id1).await.unwrap();
+            held_rx.recv().await.unwrap();
+            let mut pulled2 = Box::pin(api.pull_executed_block(block_id2));
+            assert!(tokio::time::timeout(Duration::from_millis(50), &mut pulled2).await.is_err());
+
+            // Block 2 is discarded along with block 1, and the pipeline rolls back to the genesis
+            let discarded = async move {
+                assert_eq!(pulled2.await, Err(PipeExecLayerError::Discarded(block_id2)));
+                drop(release_tx);
+            };
+            if hash_mismatch {
+                api.commit_executed_block_hash(ExecutedBlockMeta {
+                    block_id: block_id1,
+                    block_hash: B256::with_last_byte(0xff),
+                })
+                .unwrap();
+                discarded.await;
+                while api.status(block_id1) != BlockStatus::Unknown {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            } else {
+                let (rejected, ()) = tokio::join!(api.reject_executed_block(block_id1), discarded);
+                rejected.unwrap();
+            }
+            assert_eq!(api.head().block_number, 0);
+            assert!(storage.bundle_state(1).is_none());
+            assert!(storage.bundle_state(2).is_none());
+
+            // The blocks delivered again are executed on top of the genesis
+            for number in 1..=2 {
+                api.push_ordered_block(transfer_block(number)).await.unwrap();
+            }
+            let block_info1 = api.pull_executed_block(block_id1).await.unwrap();
+            assert_eq!(block_info1.block_hash, block_hash1);
+            let block_info2 = api.pull_executed_block(block_id2).await.unwrap();
+            assert_eq!(block_info2.tx_count, 1);
+            for (block_id, block_info) in [(block_id1, block_info1), (block_id2, block_info2)] {
+                api.commit_executed_block_hash(ExecutedBlockMeta {
+                    block_id,
+                    block_hash: block_info.block_hash,
+                })
+                .unwrap();
+            }
+            wait_head(&api, block_id2).await;
+            for number in 1..=2 {
+                let canonicalized = canonical_rx.recv().await.unwrap();
+                assert_eq!(canonicalized.block.header().number, number);
+                assert_eq!(
+                    canonicalized.block.header().state_root,
+                    storage.state_root(number).unwrap()
+                );
+            }
+
+            assert!(stop_test_pipeline(api, el).await.is_empty());
+        }
+    }
+
+    fn withdrawal(index: u64, amount: u64) -> Withdrawal {
+        Withdrawal { index, validator_index: index, address: Address::with_last_byte(1), amount }
+    }
+
+    #[test]
+    fn test_validate_withdrawals() {
+        let valid = Withdrawals::new(vec![withdrawal(3, 1), withdrawal(4, 10), withdrawal(7, 1)]);
+        assert_eq!(validate_withdrawals(&valid), Ok(()));
+
+        let non_monotonic =
+            Withdrawals::new(vec![withdrawal(3, 1), withdrawal(5, 1), withdrawal(5, 1)]);
+        assert_eq!(
+            validate_withdrawals(&non_monotonic),
+            Err((2, InvalidWithdrawalReason::NonMonotonicIndex { index: 5, previous: 5 }))
+        );
+        let zero_amount = Withdrawals::new(vec![withdrawal(3, 1), withdrawal(4, 0)]);
+        assert_eq!(
+            validate_withdrawals(&zero_amount),
+            Err((1, InvalidWithdrawalReason::ZeroAmount))
+        );
+        let validator_index_out_of_range = Withdrawals::new(vec![
+            Withdrawal { validator_index: VALIDATOR_REGISTRY_LIMIT - 1, ..withdrawal(3, 1) },
+            Withdrawal { validator_index: VALIDATOR_REGISTRY_LIMIT, ..withdrawal(4, 1) },
+        ]);
+        assert_eq!(
+            validate_withdrawals(&validator_index_out_of_range),
+            Err((
+                1,
+                InvalidWithdrawalReason::ValidatorIndexOutOfRange {
+                    validator_index: VALIDATOR_REGISTRY_LIMIT
+                }
+            ))
+        );
+        let zero_address = Withdrawals::new(vec![
+            withdrawal(3, 1),
+            Withdrawal { address: Address::ZERO, ..withdrawal(4, 1) },
+        ]);
+        assert_eq!(
+            validate_withdrawals(&zero_address),
+            Err((1, InvalidWithdrawalReason::ZeroAddress))
+        );
+    }
+
+    #[test]
+    fn test_validate_receipts() {
+        let receipt = |cumulative_gas_used| Receipt { cumulative_gas_used, ..Default::default() };
+        let receipts = [receipt(21_000), receipt(42_000), receipt(42_000)];
+        assert_eq!(validate_receipts(3, &receipts), Ok(Bloom::ZERO));
+        assert_eq!(validate_receipts(0, &[]), Ok(Bloom::ZERO));
+
+        assert_eq!(
+            validate_receipts(4, &receipts),
+            Err(InvalidReceiptsError::CountMismatch { receipts: 3, transactions: 4 })
+        );
+        assert_eq!(
+            validate_receipts(2, &receipts),
+            Err(InvalidReceiptsError::CountMismatch { receipts: 3, transactions: 2 })
+        );
+        let decreasing = [receipt(21_000), receipt(63_000), receipt(42_000)];
+        assert_eq!(
+            validate_receipts(3, &decreasing),
+            Err(InvalidReceiptsError::DecreasingCumulativeGasUsed {
+                index: 2,
+                cumulative_gas_used: 42_000,
+                previous: 63_000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_receipts_logs_bloom() {
+        let log = |address: u8, topic: u8| {
+            alloy_primitives::Log::new_unchecked(
+                Address::with_last_byte(address),
+                vec![B256::with_last_byte(topic)],
+                Default::default(),
+            )
+        };
+        let receipts = vec![
+            Receipt { cumulative_gas_used: 21_000, logs: vec![log(1, 1)], ..Default::default() },
+            Receipt { cumulative_gas_used: 42_000, ..Default::default() },
+            Receipt {
+                cumulative_gas_used: 63_000,
+                logs: vec![log(2, 2), log(1, 3)],
+                ..Default::default()
+            },
+        ];
+        let logs_bloom = validate_receipts(3, &receipts).unwrap();
+        assert_ne!(logs_bloom, Bloom::ZERO);
+        // Same as the bloom computed over all the logs of the block at once
+        let execution_outcome =
+            ExecutionOutcome::new(Default::default(), vec![receipts], 1, vec![]);
+        assert_eq!(Some(logs_bloom), execution_outcome.block_logs_bloom(1));
+    }
+
+    #[test]
+    fn test_empty_withdrawals_root() {
+        assert_eq!(validate_withdrawals(&Withdrawals::default()), Ok(()));
+        assert_eq!(proofs::calculate_withdrawals_root(&[]), EMPTY_WITHDRAWALS);
+
+        let chain_spec = Arc::new(chain_spec_builder().shanghai_activated().build());
+        let core = test_core_with_chain_spec(chain_spec, PipeExecLayerConfig::default());
+        let (_, state) = core.storage.get_state_view(0).unwrap();
+        let result =
+            core.execute_ordered_block(empty_ordered_block(1), &Header::default(), state).unwrap();
+        assert_eq!(result.block.header.withdrawals_root, Some(EMPTY_WITHDRAWALS));
+        assert_eq!(result.block.body.withdrawals, Some(Withdrawals::default()));
+    }
+
+    #[test]
+    fn test_parent_beacon_block_root() {
+        let chain_spec = Arc::new(chain_spec_builder().cancun_activated().build());
+        let core = test_core_with_chain_spec(chain_spec.clone(), PipeExecLayerConfig::default());
+        let (_, state) = core.storage.get_state_view(0).unwrap();
+        let result =
+            core.execute_ordered_block(empty_ordered_block(1), &Header::default(), state).unwrap();
+        assert_eq!(result.block.header.parent_beacon_block_root, Some(B256::with_last_byte(0)));
+
+        let core = test_core_with_chain_spec(
+            chain_spec,
+            PipeExecLayerConfig::default().with_parent_beacon_block_root(
+                |ordered_block: &OrderedBlock, parent_header: &Header| {
+                    B256::with_last_byte(ordered_block.number as u8 + parent_header.number as u8)
+                },
+            ),
+        );
+        let (_, state) = core.storage.get_state_view(0).unwrap();
+        let result =
+            core.execute_ordered_block(empty_ordered_block(1), &Header::default(), state).unwrap();
+        assert_eq!(result.block.header.parent_beacon_block_root, Some(B256::with_last_byte(1)));
+    }
+
+    #[test]
+    fn test_prev_randao_validator() {
+        let chain_spec = Arc::new(chain_spec_builder().cancun_activated().build());
+        let core = test_core_with_chain_spec(
+            chain_spec,
+            PipeExecLayerConfig::default().with_prev_randao_validator(
+                |ordered_block: &OrderedBlock, _: &Header| {
+                    if ordered_block.prev_randao == B256::with_last_byte(ordered_block.number as u8)
+                    {
+                        Ok(())
+                    } else {
+                        Err("not derived from the block number".to_string())
+                    }
+                },
+            ),
+        );
+
+        let block_id = B256::with_last_byte(1);
+        let (_, state) = core.storage.get_state_view(0).unwrap();
+        let error = core
+            .execute_ordered_block(empty_ordered_block(1), &Header::default(), state)
+            .unwrap_err();
+        let BlockExecutionError::Internal(error) = error else { panic!("{error}") };
+        assert_eq!(
+            *error.downcast::<InvalidPrevRandao>().unwrap(),
+            InvalidPrevRandao {
+                block_id,
+                prev_randao: B256::ZERO,
+                reason: "not derived from the block number".to_string(),
+            }
+        );
+        // The rejected block is not executed
+        assert_eq!(core.executor_provider.executed_blocks.load(Ordering::Relaxed), 0);
+
+        let (_, state) = core.storage.get_state_view(0).unwrap();
+        let ordered_block = OrderedBlock { prev_randao: block_id, ..empty_ordered_block(1) };
+        let result = core.execute_ordered_block(ordered_block, &Header::default(), state).unwrap();
+        assert_eq!(result.block.header.mix_hash, block_id);
+        assert_eq!(core.executor_provider.executed_blocks.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_hardfork_header_fields() {
+        let chain_spec = Arc::new(
+            chain_spec_builder()
+                .paris_activated()
+                .with_fork(EthereumHardfork::Shanghai, ForkCondition::Timestamp(10))
+                .with_fork(EthereumHardfork::Cancun, ForkCondition::Timestamp(20))
+                .with_fork(EthereumHardfork::Prague, ForkCondition::Timestamp(30))
+                .build(),
+        );
+        let core = test_core_with_chain_spec(chain_spec, PipeExecLayerConfig::default());
+        for timestamp in [9, 10, 19, 20, 29, 30] {
+            let (_, state) = core.storage.get_state_view(0).unwrap();
+            let result = core
+                .execute_ordered_block(
+                    OrderedBlock { timestamp, ..empty_ordered_block(1) },
+                    &Header::default(),
+                    state,
+                )
+                .unwrap();
+            let mut block = result.block;
+            core.calculate_roots(&mut block, result.execution_output);
+            let header = block.header;
+
+            assert!(header.base_fee_per_gas.is_some());
+            let shanghai = timestamp >= 10;
+            assert_eq!(header.withdrawals_root.is_some(), shanghai, "timestamp {timestamp}");
+            assert_eq!(block.body.withdrawals.is_some(), shanghai, "timestamp {timestamp}");
+            let cancun = timestamp >= 20;
+            assert_eq!(header.parent_beacon_block_root.is_some(), cancun, "timestamp {timestamp}");
+            assert_eq!(header.excess_blob_gas.is_some(), cancun, "timestamp {timestamp}");
+            assert_eq!(header.blob_gas_used.is_some(), cancun, "timestamp {timestamp}");
+            let prague = timestamp >= 30;
+            assert_eq!(header.requests_hash.is_some(), prague, "timestamp {timestamp}");
+            assert_eq!(
+                AppliedForkFeatures::from_header(&header),
+                AppliedForkFeatures {
+                    shanghai_withdrawals: shanghai,
+                    cancun_blob: cancun,
+                    prague_requests: prague,
+                },
+                "timestamp {timestamp}"
+            );
+        }
+
+        // No base fee before London
+        let core = test_core_with_chain_spec(MAINNET.clone(), PipeExecLayerConfig::default());
+        let (_, state) = core.storage.get_state_view(0).unwrap();
+        let result =
+            core.execute_ordered_block(empty_ordered_block(1), &Header::default(), state).unwrap();
+        assert_eq!(result.block.header.base_fee_per_gas, None);
+    }
+
+    #[tokio::test]
+    async fn test_block_executed_fork_features() {
+        let chain_spec = Arc::new(
+            chain_spec_builder()
+                .paris_activated()
+                .with_fork(EthereumHardfork::Shanghai, ForkCondition::Timestamp(2))
+                .build(),
+        );
+        let (api, ext) = build_test_pipeline_on(
+            CountingExecutorProvider::default(),
+            chain_spec,
+            MockStorage::new(CacheDB::new(EmptyDB::default())),
+            PipeExecLayerConfig::default(),
+        );
+        // EL makes the blocks canonical, and forwards the fork features of the executed blocks
+        let (fork_features_tx, mut fork_features_rx) = tokio::sync::mpsc::unbounded_channel();
+        let el = std::thread::spawn(move || {
+            while let Ok(event) = ext.event_rx.lock().unwrap().recv() {
+                match event {
+                    PipeExecLayerEvent::MakeCanonical(_, tx) => {
+                        let _ = tx.send(());
+                    }
+                    PipeExecLayerEvent::BlockExecuted { block_number, fork_features, .. } => {
+                        let _ = fork_features_tx.send((block_number, fork_features));
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        // The timestamp of block 1 is right before Shanghai, and block 2 activates it
+        for number in 1..=2 {
+            api.push_ordered_block(empty_ordered_block(number)).await.unwrap();
+            verify_block(&api, B256::with_last_byte(number as u8)).await;
+            let shanghai_withdrawals = number >= 2;
+            assert_eq!(
+                fork_features_rx.recv().await,
+                Some((number, AppliedForkFeatures { shanghai_withdrawals, ..Default::default() }))
+            );
+        }
+
+        stop_test_pipeline(api, el).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_repushed_block() {
+        let (api, el) = spawn_test_pipeline();
+
+        api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+        let block_hash = api.pull_executed_block_hash(B256::with_last_byte(1)).await.unwrap();
+
+        // Coordinator pushes the block again as if it had missed the hash
+        api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+        let resent = tokio::time::timeout(
+            Duration::from_millis(100),
+            api.pull_executed_block_hash(B256::with_last_byte(1)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resent, Ok(block_hash));
+
+        api.commit_executed_block_hash(ExecutedBlockMeta {
+            block_id: B256::with_last_byte(1),
+            block_hash,
+        })
+        .unwrap();
+        assert_eq!(wait_head(&api, B256::with_last_byte(1)).await.block_hash, block_hash);
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_pull_executed_block() {
+        let sender = Address::with_last_byte(1);
+        let (api, ext) = build_test_pipeline_on(
+            CountingExecutorProvider::default(),
+            MAINNET.clone(),
+            MockStorage::new(funded_state(sender)),
+            PipeExecLayerConfig::default(),
+        );
+        let el = spawn_test_el(Arc::new(ext));
+
+        api.push_ordered_block(OrderedBlock {
+            // The last transaction is discarded for its nonce gap
+            transactions: vec![transfer_tx(0), transfer_tx(1), transfer_tx(3)],
+            senders: vec![sender; 3],
+            ..empty_ordered_block(1)
+        })
+        .await
+        .unwrap();
+        let block_info = api.pull_executed_block(B256::with_last_byte(1)).await.unwrap();
+        assert_eq!(block_info.gas_used, 2 * 21_000);
+        assert_eq!(block_info.tx_count, 2);
+
+        api.commit_executed_block_hash(ExecutedBlockMeta {
+            block_id: B256::with_last_byte(1),
+            block_hash: block_info.block_hash,
+        })
+        .unwrap();
+        let head = wait_head(&api, B256::with_last_byte(1)).await;
+        assert_eq!(head.block_hash, block_info.block_hash);
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_sender_denylist() {
+        let (api, el) = spawn_test_pipeline();
+        assert_eq!(
+            api.set_sender_denylist([Address::with_last_byte(1)]),
+            Err(PipeExecLayerError::SenderDenylistDisabled)
+        );
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+
+        let sender = Address::with_last_byte(1);
+        let (api, ext) = build_test_pipeline_on(
+            CountingExecutorProvider::default(),
+            MAINNET.clone(),
+            MockStorage::new(funded_state(sender)),
+            PipeExecLayerConfig::default().with_sender_denylist([]),
+        );
+        let el = spawn_test_el(Arc::new(ext));
+
+        // The sender is denied for the second block only, which is filtered even though it's
+        // presumed valid
+        for (number, denylist, tx_count) in [(1, vec![], 1), (2, vec![sender], 0), (3, vec![], 1)] {
+            api.set_sender_denylist(denylist).unwrap();
+            api.push_ordered_block(OrderedBlock {
+                transactions: vec![transfer_tx(if number == 3 { 1 } else { 0 })],
+                senders: vec![sender],
+                presumed_valid: true,
+                ..empty_ordered_block(number)
+            })
+            .await
+            .unwrap();
+            let block_id = B256::with_last_byte(number as u8);
+            let block_info = api.pull_executed_block(block_id).await.unwrap();
+            assert_eq!(block_info.tx_count, tx_count);
+            api.commit_executed_block_hash(ExecutedBlockMeta {
+                block_id,
+                block_hash: block_info.block_hash,
+            })
+            .unwrap();
+            wait_head(&api, block_id).await;
+        }
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_state_view_fetched_once() {
+        let sender = Address::with_last_byte(1);
+        let storage = MockStorage::new(funded_state(sender));
+        let state_views = storage.state_views.clone();
+        let executor_provider = CountingExecutorProvider::default();
+        let (api, ext) = build_test_pipeline_on(
+            executor_provider.clone(),
+            MAINNET.clone(),
+            storage,
+            PipeExecLayerConfig::default(),
+        );
+        let el = spawn_test_el(Arc::new(ext));
+
+        // The mock storage serves the same state for every block, so each block carries the
+        // first transaction of the sender, which passes the filter and reaches the executor
+        for number in 1..=3 {
+            api.push_ordered_block(OrderedBlock {
+                transactions: vec![transfer_tx(0)],
+                senders: vec![sender],
+                ..empty_ordered_block(number)
+            })
+            .await
+            .unwrap();
+            verify_block(&api, B256::with_last_byte(number as u8)).await;
+        }
+        wait_head(&api, B256::with_last_byte(3)).await;
+        assert_eq!(executor_provider.executed_txs.load(Ordering::Relaxed), 3);
+        assert_eq!(state_views.load(Ordering::Relaxed), 3);
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[test]
+    fn test_get_state_view_by_id() {
+        let storage = InMemoryStorage::new(CacheDB::new(EmptyDB::default()));
+        storage.insert_block_id(1, B256::with_last_byte(1));
+        assert!(storage.get_state_view_by_id(1, B256::with_last_byte(1)).is_ok());
+        // Another fork's block at the same number
+        assert!(matches!(
+            storage.get_state_view_by_id(1, B256::with_last_byte(2)),
+            Err(GravityStorageError::BlockIdMismatch { block_number: 1, stored_block_id, .. })
+                if stored_block_id == B256::with_last_byte(1)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_end_to_end() {
+        let sender = Address::with_last_byte(1);
+        let genesis_header = Header::default();
+        let genesis_hash = genesis_header.hash_slow();
+        let (execution_args_tx, execution_args_rx) = oneshot::channel();
+        let api = new_pipe_exec_layer_api(
+            MAINNET.clone(),
+            InMemoryStorage::new(funded_state(sender)),
+            genesis_header,
+            genesis_hash,
+            execution_args_rx,
+        );
+        execution_args_tx
+            .send(ExecutionArgs { block_number_to_block_id: BTreeMap::from([(0, B256::ZERO)]) })
+            .unwrap();
+        let ext = get_pipe_exec_layer_ext::<EthPrimitives>().unwrap();
+        let mut canonical_rx = ext.subscribe_canonical();
+        let el = std::thread::spawn(move || {
+            while let Ok(event) = ext.event_rx.lock().unwrap().recv() {
+                if let PipeExecLayerEvent::MakeCanonical(_, tx) = event {
+                    let _ = tx.send(());
+                }
+            }
+        });
+
+        // The transaction of block 2 is valid only on top of the state of block 1
+        for number in 1..=2 {
+            api.push_ordered_block(OrderedBlock {
+                transactions: vec![transfer_tx(number - 1)],
+                senders: vec![sender],
+                ..empty_ordered_block(number)
+            })
+            .await
+            .unwrap();
+        }
+        verify_block(&api, B256::with_last_byte(1)).await;
+        verify_block(&api, B256::with_last_byte(2)).await;
+
+        let mut parent_hash = genesis_hash;
+        for number in 1..=2 {
+            let canonicalized = canonical_rx.recv().await.unwrap();
+            assert_eq!(canonicalized.block_id, B256::with_last_byte(number as u8));
+            assert_eq!(canonicalized.block.header().number, number);
+            assert_eq!(canonicalized.block.header().parent_hash, parent_hash);
+            assert_eq!(canonicalized.block.body().transactions.len(), 1);
+            parent_hash = canonicalized.block.hash();
+        }
+        assert_eq!(api.head().block_hash, parent_hash);
+
+        stop_test_pipeline(api, el).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_tx_trace_sink() {
+        let sender = Address::with_last_byte(1);
+        let traces = Arc::new(Mutex::new(vec![]));
+        let config = PipeExecLayerConfig::default().with_tx_trace_sink({
+            let traces = traces.clone();
+            move |block_number: u64, trace: TxTrace| {
+                traces.lock().unwrap().push((block_number, trace))
+            }
+        });
+        let (api, _, el) =
+            spawn_eth_test_pipeline(InMemoryStorage::new(funded_state(sender)), config);
+
+        let txs = vec![transfer_tx(0), transfer_tx(1)];
+        api.push_ordered_block(OrderedBlock {
+            transactions: txs.clone(),
+            senders: vec![sender; 2],
+            ..empty_ordered_block(1)
+        })
+        .await
+        .unwrap();
+        verify_block(&api, B256::with_last_byte(1)).await;
+        wait_head(&api, B256::with_last_byte(1)).await;
+        assert_eq!(
+            *traces.lock().unwrap(),
+            txs.iter()
+                .map(|tx| (
+                    1,
+                    TxTrace { tx_hash: *tx.hash(), gas_used: 21_000, success: true, logs_count: 0 }
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_compute_block_hash() {
+        let sender = Address::with_last_byte(1);
+        let storage = InMemoryStorage::new(funded_state(sender));
+        let (api, _, el) = spawn_eth_test_pipeline(storage.clone(), PipeExecLayerConfig::default());
+        let block = |number: u64| OrderedBlock {
+            transactions: vec![transfer_tx(number - 1)],
+            senders: vec![sender],
+            ..empty_ordered_block(number)
+        };
+
+        // Nothing is committed, and the pipeline seals the block with the same hash afterwards
+        let computed = api.compute_block_hash(block(1)).unwrap();
+        assert_eq!(computed.gas_used, 21_000);
+        assert_eq!(storage.bundle_state(1), None);
+        api.push_ordered_block(block(1)).await.unwrap();
+        assert_eq!(verify_block(&api, B256::with_last_byte(1)).await, computed.block_hash);
+        wait_head(&api, B256::with_last_byte(1)).await;
+
+        // The parent must be canonical
+        api.push_ordered_block(block(2)).await.unwrap();
+        api.pull_executed_block_hash(B256::with_last_byte(2)).await.unwrap();
+        assert!(matches!(
+            api.compute_block_hash(block(3)),
+            Err(SimulationError::ParentNotCanonical { parent: 2, canonical: 1 })
+        ));
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_beacon_root_system_call() {
+        let chain_spec = Arc::new(chain_spec_builder().cancun_activated().build());
+        let mut state = CacheDB::new(EmptyDB::default());
+        state.insert_account_info(
+            BEACON_ROOTS_ADDRESS,
+            AccountInfo {
+                code_hash: keccak256(&BEACON_ROOTS_CODE),
+                code: Some(Bytecode::new_raw(BEACON_ROOTS_CODE.clone())),
+                ..Default::default()
+            },
+        );
+        let beacon_root = B256::with_last_byte(0x69);
+        let config = PipeExecLayerConfig::default()
+            .with_emit_state_diff(true)
+            .with_parent_beacon_block_root(move |_: &OrderedBlock, _: &Header| beacon_root);
+        let (api, ext) = build_test_pipeline_on(
+            EthExecutorProvider::ethereum(chain_spec.clone()),
+            chain_spec,
+            InMemoryStorage::new(state),
+            config,
+        );
+        // EL makes the blocks canonical, and collects the state diffs
+        let el = std::thread::spawn(move || {
+            let mut diffs = vec![];
+            while let Ok(event) = ext.event_rx.lock().unwrap().recv() {
+                match event {
+                    PipeExecLayerEvent::MakeCanonical(_, tx) => {
+                        let _ = tx.send(());
+                    }
+                    PipeExecLayerEvent::StateDiff { block_number, accounts, storage } => {
+                        diffs.push((block_number, accounts, storage))
+                    }
+                    _ => {}
+                }
+            }
+            diffs
+        });
+
+        let recipient = Address::with_last_byte(0x42);
+        let timestamp = 8191 + 12;
+        api.push_ordered_block(OrderedBlock {
+            timestamp,
+            withdrawals: Withdrawals::new(vec![Withdrawal {
+                index: 0,
+                validator_index: 0,
+                address: recipient,
+                amount: 1,
+            }]),
+            ..empty_ordered_block(1)
+        })
+        .await
+        .unwrap();
+        verify_block(&api, B256::with_last_byte(1)).await;
+        wait_head(&api, B256::with_last_byte(1)).await;
+        let diffs = stop_test_pipeline(api, el).await;
+        assert_eq!(diffs.len(), 1);
+        let (block_number, accounts, storage) = &diffs[0];
+        assert_eq!(*block_number, 1);
+
+        // The ring buffer slot of the timestamp holds the timestamp, and the slot one buffer
+        // length above holds the root
+        let timestamp_index = U256::from(timestamp % 8191);
+        let root_index = timestamp_index + U256::from(8191);
+        assert_eq!(
+            *storage,
+            vec![
+                StorageDiff {
+                    address: BEACON_ROOTS_ADDRESS,
+                    slot: timestamp_index,
+                    previous_value: U256::ZERO,
+                    value: U256::from(timestamp),
+                },
+                StorageDiff {
+                    address: BEACON_ROOTS_ADDRESS,
+                    slot: root_index,
+                    previous_value: U256::ZERO,
+                    value: U256::from_be_bytes(beacon_root.0),
+                },
+            ]
+        );
+        // The withdrawal amount is in gwei
+        let recipient_diff = accounts.iter().find(|diff| diff.address == recipient).unwrap();
+        assert_eq!(
+            recipient_diff.account.map(|account| account.balance),
+            Some(U256::from(1_000_000_000u64))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_prune_bundle_states() {
+        let sender = Address::with_last_byte(1);
+        let storage = InMemoryStorage::new(funded_state(sender));
+        let (api, _, el) = spawn_eth_test_pipeline(
+            storage.clone(),
+            PipeExecLayerConfig::default().with_max_reorg_depth(1).with_bundle_state_retention(1),
+        );
+
+        for number in 1..=4 {
+            let block_id = B256::with_last_byte(number as u8);
+            api.push_ordered_block(OrderedBlock {
+                transactions: vec![transfer_tx(number - 1)],
+                senders: vec![sender],
+                ..empty_ordered_block(number)
+            })
+            .await
+            .unwrap();
+            // Each transaction is valid only on top of the state of the previous blocks, even
+            // after their bundle states are pruned
+            let block_info = api.pull_executed_block(block_id).await.unwrap();
+            assert_eq!(block_info.tx_count, 1);
+            api.commit_executed_block_hash(ExecutedBlockMeta {
+                block_id,
+                block_hash: block_info.block_hash,
+            })
+            .unwrap();
+            wait_head(&api, block_id).await;
+        }
+        // The head and one block below it are kept
+        assert!(storage.bundle_state(2).is_none());
+        assert!(storage.bundle_state(3).is_some());
+        assert!(storage.bundle_state(4).is_some());
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reorg_within_bundle_state_retention() {
+        let sender = Address::with_last_byte(1);
+        let storage = InMemoryStorage::new(funded_state(sender));
+        let (api, _, el) = spawn_eth_test_pipeline(
+            storage.clone(),
+            PipeExecLayerConfig::default().with_max_reorg_depth(2).with_bundle_state_retention(2),
+        );
+
+        let execute = |ordered_block: OrderedBlock| {
+            let api = &api;
+            async move {
+                let block_id = ordered_block.id;
+                api.push_ordered_block(ordered_block).await.unwrap();
+                let block_info = api.pull_executed_block(block_id).await.unwrap();
+                api.commit_executed_block_hash(ExecutedBlockMeta {
+                    block_id,
+                    block_hash: block_info.block_hash,
+                })
+                .unwrap();
+                wait_head(api, block_id).await;
+                block_info.tx_count
+            }
+        };
+        for number in 1..=4 {
+            let ordered_block = OrderedBlock {
+                transactions: vec![transfer_tx(number - 1)],
+                senders: vec![sender],
+                ..empty_ordered_block(number)
+            };
+            assert_eq!(execute(ordered_block).await, 1);
+        }
+        assert!(storage.bundle_state(1).is_none());
+        assert!(storage.bundle_state(2).is_some());
+
+        // Coordinator replaces the canonical block 3 with a block forking from block 2, whose
+        // transaction is valid only on top of the state of block 2
+        let fork_block = OrderedBlock {
+            id: B256::with_last_byte(30),
+            timestamp: 5,
+            transactions: vec![transfer_tx(2)],
+            senders: vec![sender],
+            ..empty_ordered_block(3)
+        };
+        assert_eq!(execute(fork_block).await, 1);
+
+        assert_eq!(stop_test_pipeline(api, el).await, vec![(4, 2)]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_sequential_execution() {
+        let senders: Vec<_> = (1..=4).map(Address::with_last_byte).collect();
+        let mut state = CacheDB::new(EmptyDB::default());
+        for sender in &senders {
+            state.insert_account_info(
+                *sender,
+                AccountInfo { balance: U256::from(1_000_000), ..Default::default() },
+            );
+        }
+        let transfer = |nonce: u64, to: Address, value: u64| {
+            TransactionSigned::new_unhashed(
+                EthTransaction::Legacy(TxLegacy {
+                    chain_id: Some(MAINNET.chain().id()),
+                    nonce,
+                    gas_price: 1,
+                    gas_limit: 21_000,
+                    to: TxKind::Call(to),
+                    value: U256::from(value),
+                    ..Default::default()
+                }),
+                Signature::test_signature(),
+            )
+        };
+        // Senders 1 and 2 pay the same recipient, and sender 3 pays sender 4 which spends from
+        // its balance afterwards, so some of the transactions conflict and some are independent
+        let recipient = Address::with_last_byte(0x42);
+        let (transactions, tx_senders): (Vec<_>, Vec<_>) = [
+            (transfer(0, recipient, 100), senders[0]),
+            (transfer(0, recipient, 200), senders[1]),
+            (transfer(0, senders[3], 50_000), senders[2]),
+            (transfer(1, recipient, 300), senders[0]),
+            (transfer(0, recipient, 970_000), senders[3]),
+            (transfer(1, Address::with_last_byte(0x43), 400), senders[1]),
+        ]
+        .into_iter()
+        .unzip();
+        let ordered_block = || OrderedBlock {
+            transactions: transactions.clone(),
+            senders: tx_senders.clone(),
+            ..empty_ordered_block(1)
+        };
+
+        let mut executed = vec![];
+        for sequential_execution in [false, true] {
+            let storage = InMemoryStorage::new(state.clone());
+            let (api, _, el) = spawn_eth_test_pipeline(
+                storage.clone(),
+                PipeExecLayerConfig::default().with_sequential_execution(sequential_execution),
+            );
+
+            api.push_ordered_block(ordered_block()).await.unwrap();
+            let block_info = api.pull_executed_block(B256::with_last_byte(1)).await.unwrap();
+            assert_eq!(block_info.tx_count, 6);
+            executed.push((block_info, storage.state_root(1).unwrap()));
+
+            assert!(stop_test_pipeline(api, el).await.is_empty());
+        }
+        // The block hash covers the receipts as well as the state root
+        assert_eq!(executed[0], executed[1]);
+        let parent_storage = InMemoryStorage::new(state);
+        parent_storage.insert_block_id(0, B256::ZERO);
+        assert_ne!(executed[0].1, parent_storage.state_root(0).unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_execution_args_dropped() {
+        let (execution_args_tx, execution_args_rx) = oneshot::channel();
+        let (api, _ext) = build_pipe_exec_layer(
+            CountingExecutorProvider::default(),
+            EthEvmConfig::new(MAINNET.clone()),
+            MAINNET.clone(),
+            MockStorage::new(CacheDB::new(EmptyDB::default())),
+            Header::default(),
+            B256::ZERO,
+            execution_args_rx,
+            PipeExecLayerConfig::default(),
+        )
+        .unwrap();
+        drop(execution_args_tx);
+
+        // The service stops by itself instead of panicking, which would drop it without stopping
+        api.shutdown().await;
+        assert!(*api.stopped_rx.borrow());
+        assert_eq!(
+            api.pull_executed_block_hash(B256::with_last_byte(1)).await,
+            Err(PipeExecLayerError::Closed)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_startup_replay() {
+        let sender = Address::with_last_byte(1);
+        let storage = InMemoryStorage::new(funded_state(sender));
+        let genesis = RecoveredBlock::new_unhashed(
+            Block { header: Header::default(), body: BlockBody::default() },
+            vec![],
+        );
+        storage.insert_canonical_block(genesis.clone());
+        // Starts a pipeline on the storage, whose EL persists the canonical blocks into it
+        let start = |head: &RecoveredBlock<Block>, config: PipeExecLayerConfig| {
+            let (execution_args_tx, execution_args_rx) = oneshot::channel();
+            let (api, ext) = build_pipe_exec_layer(
+                EthExecutorProvider::ethereum(MAINNET.clone()),
+                EthEvmConfig::new(MAINNET.clone()),
+                MAINNET.clone(),
+                storage.clone(),
+                head.header().clone(),
+                head.hash(),
+                execution_args_rx,
+                config,
+            )
+            .unwrap();
+            let block_ids = (0..=head.header().number).map(|n| (n, B256::with_last_byte(n as u8)));
+            execution_args_tx
+                .send(ExecutionArgs { block_number_to_block_id: block_ids.collect() })
+                .unwrap();
+            let storage = storage.clone();
+            let el = std::thread::spawn(move || {
+                while let Ok(event) = ext.event_rx.lock().unwrap().recv() {
+                    if let PipeExecLayerEvent::MakeCanonical(executed_block, tx) = event {
+                        storage.insert_canonical_block((*executed_block.recovered_block).clone());
+                        let _ = tx.send(());
+                    }
+                }
+            });
+            (api, el)
+        };
+        let transfer_block = |number: u64| OrderedBlock {
+            transactions: vec![transfer_tx(number - 1)],
+            senders: vec![sender],
+            ..empty_ordered_block(number)
+        };
+
+        let (api, el) = start(&genesis, PipeExecLayerConfig::default());
+        for number in 1..=2 {
+            api.push_ordered_block(transfer_block(number)).await.unwrap();
+            verify_block(&api, B256::with_last_byte(number as u8)).await;
+        }
+        wait_head(&api, B256::with_last_byte(2)).await;
+        stop_test_pipeline(api, el).await;
+
+        // The replayed blocks match the stored ones, so the pipeline goes on with the next block
+        let head = storage.get_canonical_block(2).unwrap();
+        let config = PipeExecLayerConfig::default().with_startup_replay_blocks(2);
+        let (api, el) = start(&head, config.clone());
+        api.push_ordered_block(transfer_block(3)).await.unwrap();
+        verify_block(&api, B256::with_last_byte(3)).await;
+        wait_head(&api, B256::with_last_byte(3)).await;
+        stop_test_pipeline(api, el).await;
+
+        // The transfer of block 2 is filtered out on top of a corrupted state of block 1, so the
+        // pipeline refuses to start
+        storage.insert_bundle_state(1, &BundleState::default());
+        let (api, el) = start(&head, config);
+        api.shutdown().await;
+        assert!(*api.stopped_rx.borrow());
+        assert_eq!(
+            api.pull_executed_block_hash(B256::with_last_byte(3)).await,
+            Err(PipeExecLayerError::Closed)
+        );
+        drop(api);
+        el.join().unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dump_block() {
+        let sender = Address::with_last_byte(1);
+        let storage = InMemoryStorage::new(funded_state(sender));
+        let genesis = RecoveredBlock::new_unhashed(
+            Block { header: Header::default(), body: BlockBody::default() },
+            vec![],
+        );
+        storage.insert_canonical_block(genesis.clone());
+        let (execution_args_tx, execution_args_rx) = oneshot::channel();
+        let (api, ext) = build_pipe_exec_layer(
+            EthExecutorProvider::ethereum(MAINNET.clone()),
+            EthEvmConfig::new(MAINNET.clone()),
+            MAINNET.clone(),
+            storage.clone(),
+            genesis.header().clone(),
+            genesis.hash(),
+            execution_args_rx,
+            PipeExecLayerConfig::default().with_execution_outcome_cache_capacity(1),
+        )
+        .unwrap();
+        execution_args_tx
+            .send(ExecutionArgs { block_number_to_block_id: BTreeMap::from([(0, B256::ZERO)]) })
+            .unwrap();
+        let el_storage = storage.clone();
+        let el = std::thread::spawn(move || {
+            while let Ok(event) = ext.event_rx.lock().unwrap().recv() {
+                if let PipeExecLayerEvent::MakeCanonical(executed_block, tx) = event {
+                    el_storage.insert_canonical_block((*executed_block.recovered_block).clone());
+                    let _ = tx.send(());
+                }
+            }
+        });
+        let transfer_block = |number: u64| OrderedBlock {
+            transactions: vec![transfer_tx(number - 1)],
+            senders: vec![sender],
+            ..empty_ordered_block(number)
+        };
+
+        let dir = std::env::temp_dir().join(format!("pipe-block-dump-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cached_path = dir.join("cached");
+        let executed_path = dir.join("executed");
+        for number in 1..=3 {
+            api.push_ordered_block(transfer_block(number)).await.unwrap();
+            verify_block(&api, B256::with_last_byte(number as u8)).await;
+            wait_head(&api, B256::with_last_byte(number as u8)).await;
+            // The outcome is cached right after the head moves
+            while api.execution_outcome(number).is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            if number == 2 {
+                api.dump_block(2, &cached_path).unwrap();
+            }
+        }
+        // The outcome of block 2 has been evicted from the cache by block 3, so the block is
+        // executed again
+        assert!(api.execution_outcome(2).is_none());
+        api.dump_block(2, &executed_path).unwrap();
+        assert!(matches!(
+            api.dump_block(4, &executed_path),
+            Err(BlockDumpError::BlockUnavailable(4))
+        ));
+        stop_test_pipeline(api, el).await;
+
+        let encoded = std::fs::read(&cached_path).unwrap();
+        assert_eq!(std::fs::read(&executed_path).unwrap(), encoded);
+        let dump = load_block_dump(&cached_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(dump.block, storage.get_canonical_block(2).unwrap());
+        assert_eq!(dump.execution_outcome.first_block, 2);
+        assert_eq!(dump.execution_outcome.receipts[0].len(), 1);
+        assert_eq!(dump.encode(), encoded);
+
+        let mut newer = encoded.clone();
+        newer[BLOCK_DUMP_MAGIC.len()..BLOCK_DUMP_MAGIC.len() + 2]
+            .copy_from_slice(&(BLOCK_DUMP_VERSION + 1).to_be_bytes());
+        assert!(matches!(
+            BlockDump::decode(&newer),
+            Err(BlockDumpError::UnsupportedVersion(version)) if version == BLOCK_DUMP_VERSION + 1
+        ));
+        assert!(matches!(
+            BlockDump::decode(&encoded[..encoded.len() - 1]),
+            Err(BlockDumpError::Malformed(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_first_block_after_start() {
+        /// Keeps the latest value of each gauge
+        #[derive(Clone, Default)]
+        struct GaugeSink(Arc<Mutex<HashMap<String, f64>>>);
+
+        impl MetricsSink for GaugeSink {
+            fn record_duration(&self, _key: &reth_metrics::metrics::Key, _value: f64) {}
+
+            fn increment_counter(&self, _key: &reth_metrics::metrics::Key, _value: u64) {}
+
+            fn set_gauge(&self, key: &reth_metrics::metrics::Key, value: f64) {
+                self.0.lock().unwrap().insert(key.name().to_string(), value);
+            }
+        }
+
+        // From the genesis, and after a restart at an arbitrary height
+        for latest_block_number in [0, 5] {
+            let latest_header = Header {
+                number: latest_block_number,
+                timestamp: latest_block_number,
+                ..Default::default()
+            };
+            let latest_block_hash = latest_header.hash_slow();
+            let sink = GaugeSink::default();
+            let (execution_args_tx, execution_args_rx) = oneshot::channel();
+            let (api, ext) = build_pipe_exec_layer(
+                CountingExecutorProvider::default(),
+                EthEvmConfig::new(MAINNET.clone()),
+                MAINNET.clone(),
+                MockStorage::new(CacheDB::new(EmptyDB::default())),
+                latest_header,
+                latest_block_hash,
+                execution_args_rx,
+                PipeExecLayerConfig::default().with_metrics_sink(sink.clone()),
+            )
+            .unwrap();
+            let latest_block_id = B256::with_last_byte(latest_block_number as u8);
+            execution_args_tx
+                .send(ExecutionArgs {
+                    block_number_to_block_id: BTreeMap::from([(
+                        latest_block_number,
+                        latest_block_id,
+                    )]),
+                })
+                .unwrap();
+            let el = std::thread::spawn(move || {
+                let mut headers = vec![];
+                while let Ok(event) = ext.event_rx.lock().unwrap().recv() {
+                    if let PipeExecLayerEvent::MakeCanonical(executed_block, tx) = event {
+                        headers.push(executed_block.recovered_block.clone_sealed_header());
+                        let _ = tx.send(());
+                    }
+                }
+                headers
+            });
+
+            // The block at the latest height is never processed again
+            let block_number = latest_block_number + 1;
+            let block_id = B256::with_last_byte(block_number as u8);
+            api.push_ordered_block(OrderedBlock {
+                number: latest_block_number,
+                parent_id: B256::with_last_byte(0xff),
+                id: latest_block_id,
+                ..empty_ordered_block(block_number)
+            })
+            .await
+            .unwrap();
+            api.push_ordered_block(empty_ordered_block(block_number)).await.unwrap();
+            let block_hash = verify_block(&api, block_id).await;
+            let head = wait_head(&api, block_id).await;
+            assert_eq!((head.block_number, head.block_hash), (block_number, block_hash));
+            let headers = stop_test_pipeline(api, el).await;
+            assert_eq!(headers.len(), 1);
+            // The header and the hash seeded into the barriers are the parent of the first block
+            assert_eq!(headers[0].number, block_number);
+            assert_eq!(headers[0].parent_hash, latest_block_hash);
+            assert_eq!(headers[0].hash(), block_hash);
+            // The seeded states have been taken, so only those of the first block are left
+            let gauges = sink.0.lock().unwrap();
+            for barrier in ["execute_block", "merklize", "seal", "make_canonical"] {
+                let key = format!("pipe_exec_layer.{barrier}_barrier_keys");
+                assert_eq!(gauges.get(&key), Some(&1.0), "{key}");
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_force_make_canonical() {
+        let (api, el) = spawn_test_pipeline();
+        api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+        let block_hash = api.pull_executed_block_hash(B256::with_last_byte(1)).await.unwrap();
+        assert_eq!(
+            api.force_make_canonical(1, block_hash),
+            Err(PipeExecLayerError::ForceCanonicalDisabled)
+        );
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+
+        let (api, _, el) = spawn_test_pipeline_with_config(
+            PipeExecLayerConfig::default().with_allow_force_canonical(true),
+        );
+
+        // Coordinator is gone after pulling the hash
+        api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+        let block_hash = api.pull_executed_block_hash(B256::with_last_byte(1)).await.unwrap();
+        assert_eq!(
+            api.force_make_canonical(1, B256::with_last_byte(9)),
+            Err(PipeExecLayerError::NotAwaitingVerification {
+                block_number: 1,
+                block_hash: B256::with_last_byte(9)
+            })
+        );
+        assert_eq!(
+            api.force_make_canonical(2, block_hash),
+            Err(PipeExecLayerError::NotAwaitingVerification { block_number: 2, block_hash })
+        );
+        api.force_make_canonical(1, block_hash).unwrap();
+        assert_eq!(wait_head(&api, B256::with_last_byte(1)).await.block_hash, block_hash);
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_senders_mismatch() {
+        let (api, el) = spawn_test_pipeline();
+
+        let malformed =
+            OrderedBlock { transactions: vec![transfer_tx(0)], ..empty_ordered_block(1) };
+        assert_eq!(
+            api.push_ordered_block(malformed).await,
+            Err(PipeExecLayerError::SendersMismatch {
+                block_id: B256::with_last_byte(1),
+                transactions: 1,
+                senders: 0,
+            })
+        );
+        let malformed = OrderedBlock {
+            withdrawals: Withdrawals::new(vec![withdrawal(1, 1), withdrawal(0, 1)]),
+            ..empty_ordered_block(1)
+        };
+        assert_eq!(
+            api.push_ordered_block(malformed).await,
+            Err(PipeExecLayerError::InvalidWithdrawal {
+                block_id: B256::with_last_byte(1),
+                position: 1,
+                reason: InvalidWithdrawalReason::NonMonotonicIndex { index: 0, previous: 1 },
+            })
+        );
+
+        // The well-formed block replacing it is executed as usual
+        api.push_ordered_block(empty_ordered_block(1)).await.unwrap();
+        verify_block(&api, B256::with_last_byte(1)).await;
+        assert_eq!(wait_head(&api, B256::with_last_byte(1)).await.block_number, 1);
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_zero_coinbase() {
+        let (api, _, el) = spawn_test_pipeline_with_config(
+            PipeExecLayerConfig::default().with_zero_coinbase(ZeroCoinbasePolicy::Reject),
+        );
+
+        assert_eq!(
+            api.push_ordered_block(empty_ordered_block(1)).await,
+            Err(PipeExecLayerError::ZeroCoinbase { block_id: B256::with_last_byte(1) })
+        );
+        let coinbase = Address::with_last_byte(0xcb);
+        api.push_ordered_block(OrderedBlock { coinbase, ..empty_ordered_block(1) }).await.unwrap();
+        verify_block(&api, B256::with_last_byte(1)).await;
+        assert_eq!(wait_head(&api, B256::with_last_byte(1)).await.block_number, 1);
+
+        assert!(stop_test_pipeline(api, el).await.is_empty());
+    }
+
+    #[test]
+    fn test_priority_fees_to_coinbase() {
+        let sender = Address::with_last_byte(1);
+        let coinbase = Address::with_last_byte(0xcb);
+        // Without block rewards, and the base fee is zero after a parent without one
+        let chain_spec = Arc::new(chain_spec_builder().paris_activated().build());
+        let core = Core::standalone(
+            EthExecutorProvider::ethereum(chain_spec.clone()),
+            EthEvmConfig::new(chain_spec.clone()),
+            chain_spec,
+            MockStorage::new(funded_state(sender)),
+            PipeExecLayerConfig::default(),
+        );
+        let (_, state) = core.storage.get_state_view(0).unwrap();
+        let result = core
+            .execute_ordered_block(
+                OrderedBlock {
+                    coinbase,
+                    transactions: vec![transfer_tx(0), transfer_tx(1)],
+                    senders: vec![sender; 2],
+                    ..empty_ordered_block(1)
+                },
+                &Header::default(),
+                state,
+            )
+            .unwrap();
+        assert_eq!(result.block.header.beneficiary, coinbase);
+        assert_eq!(result.block.header.base_fee_per_gas, Some(0));
+        let coinbase_account = result.execution_output.state.account(&coinbase).unwrap();
+        assert_eq!(coinbase_account.info.as_ref().unwrap().balance, U256::from(42_000));
+    }
+
+    #[test]
+    fn test_base_fee_override() {
+        let sender = Address::with_last_byte(1);
+        let coinbase = Address::with_last_byte(0xcb);
+        let mut state = CacheDB::new(EmptyDB::default());
+        // Exactly enough for a transaction at the gas price of 1
+        state.insert_account_info(
+            sender,
+            AccountInfo { balance: U256::from(21_000), ..Default::default() },
+        );
+        let chain_spec = Arc::new(chain_spec_builder().paris_activated().build());
+        let core = Core::standalone(
+            EthExecutorProvider::ethereum(chain_spec.clone()),
+            EthEvmConfig::new(chain_spec.clone()),
+            chain_spec,
+            MockStorage::new(state),
+            PipeExecLayerConfig::default().with_base_fee_override(1),
+        );
+        let (_, state) = core.storage.get_state_view(0).unwrap();
+        let result = core
+            .execute_ordered_block(
+                OrderedBlock {
+                    coinbase,
+                    transactions: vec![transfer_tx(0)],
+                    senders: vec![sender],
+                    ..empty_ordered_block(1)
+                },
+                &Header::default(),
+                state,
+            )
+            .unwrap();
+        assert!(result.discarded_txs.is_empty());
+        assert_eq!(result.block.header.base_fee_per_gas, Some(1));
+        // The whole gas price is the base fee, which is burnt instead of paid to the coinbase as
+        // with the derived base fee of zero
+        let balance = |address| {
+            result
+                .execution_output
+                .state
+                .account(&address)
+                .and_then(|account| account.info.as_ref().map(|info| info.balance))
+        };
+        assert_eq!(balance(sender), Some(U256::ZERO));
+        assert!(balance(coinbase).is_none_or(|balance| balance.is_zero()));
+    }
+
+    #[test]
+    fn test_fee_recipient_override() {
+        let sender = Address::with_last_byte(1);
+        let coinbase = Address::with_last_byte(0xcb);
+        let treasury = Address::with_last_byte(0xfe);
+        let mut state = CacheDB::new(EmptyDB::default());
+        state.insert_account_info(
+            sender,
+            AccountInfo { balance: U256::from(21_000), ..Default::default() },
+        );
+        let chain_spec = Arc::new(chain_spec_builder().paris_activated().build());
+        let core = Core::standalone(
+            EthExecutorProvider::ethereum(chain_spec.clone()),
+            EthEvmConfig::new(chain_spec.clone()),
+            chain_spec,
+            MockStorage::new(state),
+            PipeExecLayerConfig::default().with_fee_recipient_override(treasury),
+        );
+        let (_, state) = core.storage.get_state_view(0).unwrap();
+        let result = core
+            .execute_ordered_block(
+                OrderedBlock {
+                    coinbase,
+                    transactions: vec![transfer_tx(0)],
+                    senders: vec![sender],
+                    ..empty_ordered_block(1)
+                },
+                &Header::default(),
+                state,
+            )
+            .unwrap();
+        assert!(result.discarded_txs.is_empty());
+        // The header names the recipient actually paid, so that the block re-executes to the
+        // same state
+        assert_eq!(result.block.header.beneficiary, treasury);
+        // With the derived base fee of zero, the whole gas price is the priority fee
+        let balance = |address| {
+            result
+                .execution_output
+                .state
+                .account(&address)
+                .and_then(|account| account.info.as_ref().map(|info| info.balance))
+        };
+        assert_eq!(balance(treasury), Some(U256::from(21_000)));
+        assert!(balance(coinbase).is_none_or(|balance| balance.is_zero()));
+    }
+
+    fn chain_spec_builder() -> ChainSpecBuilder {
+        ChainSpecBuilder::default().chain(MAINNET.chain).genesis(MAINNET.genesis.clone())
+    }
+
+    #[test]
+    fn test_block_difficulty_and_nonce() {
+        let seal = |chain_spec: ChainSpec, config| {
+            let core = test_core_with_chain_spec(Arc::new(chain_spec), config);
+            let (_, state) = core.storage.get_state_view(0).unwrap();
+            let result = core
+                .execute_ordered_block(empty_ordered_block(1), &Header::default(), state)
+                .unwrap();
+            let mut block = result.block;
+            core.calculate_roots(&mut block, result.execution_output);
+            block.seal_slow()
+        };
+
+        let block =
+            seal(chain_spec_builder().paris_activated().build(), PipeExecLayerConfig::default());
+        assert_eq!(block.header().difficulty, U256::ZERO);
+        assert_eq!(block.header().nonce, BEACON_NONCE.into());
+
+        let config = PipeExecLayerConfig::default()
+            .with_block_difficulty(U256::from(131_072))
+            .with_block_nonce(0x42);
+        let block = seal(chain_spec_builder().build(), config);
+        assert_eq!(block.header().difficulty, U256::from(131_072));
+        assert_eq!(block.header().nonce, 0x42u64.into());
+    }
+
+    #[test]
+    fn test_empty_block_fast_path() {
+        let chain_spec = Arc::new(chain_spec_builder().paris_activated().build());
+        let core = test_core_with_chain_spec(chain_spec.clone(), PipeExecLayerConfig::default());
+        let (_, state) = core.storage.get_state_view(0).unwrap();
+        let result =
+            core.execute_ordered_block(empty_ordered_block(1), &Header::default(), state).unwrap();
+        assert_eq!(core.executor_provider.executed_blocks.load(Ordering::Relaxed), 0);
+
+        // The full path yields the same output, hence the same roots
+        let (_, state) = core.storage.get_state_view(0).unwrap();
+        let block = RecoveredBlock::new_unhashed(result.block, vec![]);
+        let output = EthExecutorProvider::ethereum(chain_spec)
+            .executor(parallel_database! { state })
+            .execute(&block)
+            .unwrap();
+        assert_eq!(output.state, result.execution_output.state);
+        assert_eq!(output.receipts, result.execution_output.receipts);
+        assert_eq!(output.gas_used, 0);
+
+        // System calls change the state since Cancun, so the block is executed as usual
+        let chain_spec = Arc::new(chain_spec_builder().cancun_activated().build());
+        let core = test_core_with_chain_spec(chain_spec, PipeExecLayerConfig::default());
+        let (_, state) = core.storage.get_state_view(0).unwrap();
+        core.execute_ordered_block(empty_ordered_block(1), &Header::default(), state).unwrap();
+        assert_eq!(core.executor_provider.executed_blocks.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_replay_failed_block() {
+        let chain_spec = Arc::new(chain_spec_builder().paris_activated().build());
+        let sender = Address::with_last_byte(1);
+        let state = funded_state(sender);
+        let block = RecoveredBlock::new_unhashed(
+            Block {
+                header: Header {
+                    number: 1,
+                    gas_limit: 30_000_000,
+                    base_fee_per_gas: Some(0),
+                    ..Default::default()
+                },
+                body: BlockBody {
+                    transactions: vec![transfer_tx(0), transfer_tx(1)],
+                    ..Default::default()
+                },
+            },
+            vec![sender; 2],
+        );
+        let expected = EthExecutorProvider::ethereum(chain_spec.clone())
+            .executor(parallel_database! { state.clone() })
+            .execute(&block)
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("pipe-replay-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = failed_block_dump_path(&dir, B256::with_last_byte(1), SystemTime::now());
+        write_failed_block(&path, &block).unwrap();
+        assert_eq!(load_failed_block(&path).unwrap(), block);
+
+        let output = replay_failed_block(&path, chain_spec, state).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(output.receipts.len(), 2);
+        assert_eq!(output.receipts, expected.receipts);
+        assert_eq!(output.state, expected.state);
+        assert_eq!(output.gas_used, 42_000);
+    }
+
+    #[test]
+    fn test_failed_block_dump_path() {
+        let dir = Path::new("/var/dump");
+        let block_id = B256::with_last_byte(1);
+        let first = failed_block_dump_path(dir, block_id, SystemTime::UNIX_EPOCH);
+        let second = failed_block_dump_path(
+            dir,
+            block_id,
+            SystemTime::UNIX_EPOCH + Duration::from_millis(1500),
+        );
+        assert_eq!(first, dir.join(format!("{block_id}-0.json")));
+        assert_eq!(second, dir.join(format!("{block_id}-1500.json")));
     }
 }
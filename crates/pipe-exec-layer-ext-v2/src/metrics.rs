@@ -1,7 +1,28 @@
+use crate::TxFilterReason;
+
+use alloy_primitives::{Address, B256, U256};
 use reth_metrics::{
-    metrics::{Counter, Histogram},
+    metrics::{
+        self, Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata,
+        Recorder, SharedString, Unit,
+    },
     Metrics,
 };
+use reth_trie::updates::TrieUpdates;
+use revm::{
+    db::{states::StorageSlot, AccountRevert, BundleAccount, BundleState, RevertToSlot},
+    primitives::Bytecode,
+};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+/// Time constant of the throughput moving averages
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(10);
 
 /// Metrics for the `PipeExecLayerMetrics`
 #[derive(Metrics)]
@@ -9,22 +30,489 @@ use reth_metrics::{
 pub(crate) struct PipeExecLayerMetrics {
     /// How long it took for blocks to be executed
     pub(crate) execute_duration: Histogram,
+    /// How long it took for the accounts touched by blocks to be prefetched
+    pub(crate) prefetch_duration: Histogram,
     /// How long it took for blocks to be merklized
     pub(crate) merklize_duration: Histogram,
+    /// How long it took for the state root to be calculated on the blocking thread pool, which
+    /// excludes the scheduling delay included in `merklize_duration`
+    pub(crate) state_root_duration: Histogram,
+    /// Number of trie nodes changed by blocks, as counted by `trie_updates_node_count`. It tracks
+    /// the write amplification of the state, since each changed node is a write to storage.
+    pub(crate) trie_updates_node_count: Histogram,
+    /// How long it took for the transactions root, receipts root and logs bloom to be calculated,
+    /// which overlaps with merklization
+    pub(crate) calculate_roots_duration: Histogram,
     /// How long it took for blocks to be sealed
     pub(crate) seal_duration: Histogram,
     /// How long it took for block hash to be verified
     pub(crate) verify_duration: Histogram,
     /// How long it took for blocks to be made canonical
     pub(crate) make_canonical_duration: Histogram,
+    /// How long blocks waited for the previous block to be executed
+    pub(crate) execute_wait_duration: Histogram,
+    /// How long blocks waited for the previous block to be merklized
+    pub(crate) merklize_wait_duration: Histogram,
+    /// How long blocks waited for the previous block to be sealed
+    pub(crate) seal_wait_duration: Histogram,
+    /// How long blocks waited for the previous block to be made canonical
+    pub(crate) make_canonical_wait_duration: Histogram,
+    /// Number of keys outstanding in the execute barrier, sampled each time a block is made
+    /// canonical. Bounded by the blocks in flight, so a steady growth reveals a leak.
+    pub(crate) execute_block_barrier_keys: Gauge,
+    /// Number of keys outstanding in the merklize barrier
+    pub(crate) merklize_barrier_keys: Gauge,
+    /// Number of keys outstanding in the seal barrier
+    pub(crate) seal_barrier_keys: Gauge,
+    /// Number of keys outstanding in the make canonical barrier
+    pub(crate) make_canonical_barrier_keys: Gauge,
+    /// Number of blocks being processed by the pipeline stages, at most `max_in_flight_blocks`
+    pub(crate) in_flight_blocks: Gauge,
+    /// Whether the pipeline is paused at the target block, 1 if paused and 0 otherwise
+    pub(crate) paused: Gauge,
+    /// Transactions committed per second, averaged over `THROUGHPUT_WINDOW`
+    pub(crate) transactions_per_second: Gauge,
+    /// Gas committed per second, averaged over `THROUGHPUT_WINDOW`
+    pub(crate) gas_per_second: Gauge,
     /// Total gas used
     pub(crate) total_gas_used: Counter,
+    /// Total number of bundle states of old canonical blocks pruned from storage
+    pub(crate) pruned_bundle_states: Counter,
+    /// Total number of ordered blocks received from Coordinator
+    pub(crate) received_blocks: Counter,
+    /// Total number of ordered blocks received with the zero address as coinbase, whose priority
+    /// fees are burnt, if `PipeExecLayerConfig::zero_coinbase` is `ZeroCoinbasePolicy::Warn`
+    pub(crate) zero_coinbase_blocks: Counter,
+    /// Number of ordered blocks waiting in the channel from Coordinator, sampled each time one is
+    /// dequeued. Staying near the channel capacity means execution is not keeping up.
+    pub(crate) ordered_block_queue_depth: Gauge,
+    /// Seconds between the timestamp of the latest ordered block and the wall clock when it's
+    /// dequeued. It stays small on a healthy chain, and keeps growing if the pipeline can't keep
+    /// up with block production. Negative if the block timestamp is ahead of the local clock,
+    /// e.g. due to clock skew.
+    pub(crate) block_timestamp_lag: Gauge,
+    /// Number of blocks discarded after execution before being made canonical, e.g. rejected by
+    /// Coordinator or rolled back by a reorg
+    pub(crate) wasted_blocks_total: Counter,
+    /// Gas used by the blocks discarded after execution. The wasted fraction of the executed gas
+    /// is `wasted_gas_total / (total_gas_used + wasted_gas_total)`, which is near zero unless
+    /// Coordinator often replaces the blocks it has ordered.
+    pub(crate) wasted_gas_total: Counter,
     /// Time difference between two adjacent ordered blocks received
     pub(crate) recv_block_time_diff: Histogram,
     /// Time difference between two adjacent blocks starting execute
     pub(crate) start_execute_time_diff: Histogram,
+    /// Number of blocks starting execution more than `PipeExecLayerConfig::slow_block_interval`
+    /// after the previous block, which arrived late or whose parent was slow to execute
+    pub(crate) slow_block_interval_total: Counter,
     /// Time difference between two adjacent blocks completing commit
     pub(crate) finish_commit_time_diff: Histogram,
     /// How long it took for transactions to be filtered
     pub(crate) filter_transaction_duration: Histogram,
+    /// Number of blocks whose transactions are presumed valid, skipping the filter
+    pub(crate) presumed_valid_blocks: Counter,
+    /// Number of canonical blocks replayed and verified on startup, see
+    /// `PipeExecLayerConfig::startup_replay_blocks`
+    pub(crate) startup_replayed_blocks: Counter,
+    /// Number of account reads served by the state view while executing a block, including the
+    /// repeated reads of the same account. Compared with the execution duration, tells whether
+    /// execution is bound by the state reads or by the EVM.
+    pub(crate) account_reads_per_block: Histogram,
+    /// Number of storage slot reads served by the state view while executing a block, including
+    /// the repeated reads of the same slot
+    pub(crate) storage_reads_per_block: Histogram,
+    /// Approximate bytes of the bundle state of an executed block, see `bundle_state_size`
+    pub(crate) bundle_state_bytes: Histogram,
+    /// Approximate bytes of the bundle states held by the blocks in flight, limited by
+    /// `PipeExecLayerConfig::max_in_flight_bundle_state_bytes`
+    pub(crate) in_flight_bundle_state_bytes: Gauge,
+    /// Number of presumed valid blocks which failed to execute and were executed again with the
+    /// transactions filtered
+    pub(crate) presumed_valid_fallbacks: Counter,
+    /// Total number of transactions discarded by the filter
+    pub(crate) filtered_transactions_total: Counter,
+    /// Number of transactions discarded by the filter in each block
+    pub(crate) filtered_transactions_per_block: Histogram,
+    /// Recorder of the labeled metrics registered on the fly, if a sink is configured
+    #[metric(skip)]
+    sink_recorder: Option<Arc<SinkRecorder>>,
+}
+
+impl PipeExecLayerMetrics {
+    /// Metrics reported to `sink`, or to the global recorder of `reth_metrics` if `None`.
+    pub(crate) fn new(sink: Option<&Arc<dyn MetricsSink>>) -> Self {
+        let Some(sink) = sink else { return Self::default() };
+        let recorder = Arc::new(SinkRecorder { sink: sink.clone() });
+        let mut metrics = metrics::with_local_recorder(recorder.as_ref(), Self::default);
+        metrics.sink_recorder = Some(recorder);
+        metrics
+    }
+
+    /// Record the transactions discarded by the filter in a block, along with the breakdown by
+    /// reason.
+    pub(crate) fn record_filtered_txs(&self, discarded_txs: &[(usize, TxFilterReason)]) {
+        self.filtered_transactions_total.increment(discarded_txs.len() as u64);
+        self.filtered_transactions_per_block.record(discarded_txs.len() as f64);
+        // The labeled counters are registered on the fly, so they go to the sink explicitly
+        match &self.sink_recorder {
+            Some(recorder) => metrics::with_local_recorder(recorder.as_ref(), || {
+                record_filter_reasons(discarded_txs)
+            }),
+            None => record_filter_reasons(discarded_txs),
+        }
+    }
+
+    /// Record the lag of the wall clock behind the timestamp (in seconds) of a dequeued block.
+    pub(crate) fn record_block_timestamp_lag(&self, timestamp: u64) {
+        self.block_timestamp_lag.set(block_timestamp_lag(timestamp, SystemTime::now()));
+    }
+}
+
+/// Count the transactions discarded by the filter by reason.
+fn record_filter_reasons(discarded_txs: &[(usize, TxFilterReason)]) {
+    for (_, reason) in discarded_txs {
+        metrics::counter!("pipe_exec_layer.filtered_transactions", "reason" => reason.as_str())
+            .increment(1);
+    }
+}
+
+/// Backend of the pipeline metrics, e.g. for exporting them to a system other than the global
+/// recorder of `reth_metrics`, which is the default.
+///
+/// The metrics are registered once when the pipeline is built, so each sample costs a single
+/// dynamic call into the sink, the same as with the default recorder. The sink is called on the
+/// pipeline stages, so it should return quickly. Metrics are identified by their `key`, whose name
+/// is prefixed with `pipe_exec_layer.`, and which carries labels for the breakdowns, e.g. the
+/// `reason` of `pipe_exec_layer.filtered_transactions`.
+pub trait MetricsSink: Send + Sync {
+    /// Record a sample of a histogram, which is a duration in seconds for the `*_duration` and
+    /// `*_time_diff` metrics.
+    fn record_duration(&self, key: &Key, value: f64);
+
+    /// Add `value` to a counter.
+    fn increment_counter(&self, key: &Key, value: u64);
+
+    /// Set a gauge to `value`. Gauges moved by a delta are tracked by the pipeline, so the sink
+    /// always receives the resulting value.
+    fn set_gauge(&self, key: &Key, value: f64);
+}
+
+impl std::fmt::Debug for dyn MetricsSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MetricsSink").finish_non_exhaustive()
+    }
+}
+
+/// Sink discarding all the metrics, e.g. for benchmarks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopMetricsSink;
+
+impl MetricsSink for NoopMetricsSink {
+    fn record_duration(&self, _key: &Key, _value: f64) {}
+
+    fn increment_counter(&self, _key: &Key, _value: u64) {}
+
+    fn set_gauge(&self, _key: &Key, _value: f64) {}
+}
+
+/// `Recorder` handing out metric handles which forward to a `MetricsSink`.
+#[derive(Debug)]
+struct SinkRecorder {
+    sink: Arc<dyn MetricsSink>,
+}
+
+impl SinkRecorder {
+    fn handle(&self, key: &Key) -> Arc<SinkHandle> {
+        Arc::new(SinkHandle {
+            sink: self.sink.clone(),
+            key: key.clone(),
+            gauge: AtomicU64::new(0f64.to_bits()),
+        })
+    }
+}
+
+impl Recorder for SinkRecorder {
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        Counter::from_arc(self.handle(key))
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        Gauge::from_arc(self.handle(key))
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        Histogram::from_arc(self.handle(key))
+    }
+}
+
+/// Handle of a metric registered on a `SinkRecorder`.
+#[derive(Debug)]
+struct SinkHandle {
+    sink: Arc<dyn MetricsSink>,
+    key: Key,
+    /// Current value of the gauge, as `f64` bits
+    gauge: AtomicU64,
+}
+
+impl SinkHandle {
+    /// Apply `f` to the gauge, and forward the resulting value to the sink.
+    fn update_gauge(&self, f: impl Fn(f64) -> f64) {
+        let mut value = 0.0;
+        let _ = self.gauge.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            value = f(f64::from_bits(bits));
+            Some(value.to_bits())
+        });
+        self.sink.set_gauge(&self.key, value);
+    }
+}
+
+impl CounterFn for SinkHandle {
+    fn increment(&self, value: u64) {
+        self.sink.increment_counter(&self.key, value);
+    }
+
+    fn absolute(&self, _value: u64) {}
+}
+
+impl GaugeFn for SinkHandle {
+    fn increment(&self, value: f64) {
+        self.update_gauge(|gauge| gauge + value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.update_gauge(|gauge| gauge - value);
+    }
+
+    fn set(&self, value: f64) {
+        self.update_gauge(|_| value);
+    }
+}
+
+impl HistogramFn for SinkHandle {
+    fn record(&self, value: f64) {
+        self.sink.record_duration(&self.key, value);
+    }
+}
+
+/// Number of trie nodes changed by the trie updates of a block, i.e. the updated and removed
+/// nodes of the account trie, plus the updated and removed nodes of each storage trie. A storage
+/// trie wiped as a whole by a selfdestruct adds no node of its own.
+pub(crate) fn trie_updates_node_count(trie_updates: &TrieUpdates) -> usize {
+    trie_updates.account_nodes_ref().len() +
+        trie_updates.removed_nodes_ref().len() +
+        trie_updates
+            .storage_tries_ref()
+            .values()
+            .map(|storage| storage.storage_nodes_ref().len() + storage.removed_nodes_ref().len())
+            .sum::<usize>()
+}
+
+/// Approximate bytes held by a bundle state, i.e. its accounts and storage slots along with their
+/// original values, its reverts, and the code of its contracts. The overhead of the hash maps is
+/// not counted.
+pub(crate) fn bundle_state_size(bundle_state: &BundleState) -> usize {
+    let accounts = bundle_state
+        .state
+        .values()
+        .map(|account| {
+            size_of::<(Address, BundleAccount)>() +
+                account.storage.len() * size_of::<(U256, StorageSlot)>()
+        })
+        .sum::<usize>();
+    let contracts = bundle_state
+        .contracts
+        .values()
+        .map(|code| size_of::<(B256, Bytecode)>() + code.original_bytes().len())
+        .sum::<usize>();
+    let reverts = bundle_state
+        .reverts
+        .iter()
+        .flatten()
+        .map(|(_, revert)| {
+            size_of::<(Address, AccountRevert)>() +
+                revert.storage.len() * size_of::<(U256, RevertToSlot)>()
+        })
+        .sum::<usize>();
+    accounts + contracts + reverts
+}
+
+/// Signed seconds from the block timestamp to `now`, negative if the block is in the future.
+fn block_timestamp_lag(timestamp: u64, now: SystemTime) -> f64 {
+    let block_time = SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp);
+    match now.duration_since(block_time) {
+        Ok(lag) => lag.as_secs_f64(),
+        Err(err) => -err.duration().as_secs_f64(),
+    }
+}
+
+/// Exponentially weighted moving average of a rate, whose weight decays with `THROUGHPUT_WINDOW`
+/// as the time constant, so that blocks committed at irregular intervals are weighted by their
+/// interval.
+#[derive(Debug, Default)]
+struct Ewma {
+    rate: Option<f64>,
+}
+
+impl Ewma {
+    /// Add `count` events happened over `elapsed`, returning the updated rate per second.
+    fn update(&mut self, count: f64, elapsed: Duration) -> f64 {
+        let elapsed = elapsed.as_secs_f64();
+        if elapsed <= 0.0 {
+            return self.rate.unwrap_or_default();
+        }
+        let instant_rate = count / elapsed;
+        let rate = match self.rate {
+            Some(rate) => {
+                let alpha = 1.0 - (-elapsed / THROUGHPUT_WINDOW.as_secs_f64()).exp();
+                rate + alpha * (instant_rate - rate)
+            }
+            None => instant_rate,
+        };
+        self.rate = Some(rate);
+        rate
+    }
+}
+
+/// Throughput of the committed blocks, which are committed one at a time in order.
+#[derive(Debug, Default)]
+pub(crate) struct Throughput {
+    transactions: Ewma,
+    gas: Ewma,
+}
+
+impl Throughput {
+    /// Add a block committed `elapsed` after the previous one, and set the throughput gauges.
+    pub(crate) fn record_block(
+        &mut self,
+        metrics: &PipeExecLayerMetrics,
+        tx_count: usize,
+        gas_used: u64,
+        elapsed: Duration,
+    ) {
+        metrics.transactions_per_second.set(self.transactions.update(tx_count as f64, elapsed));
+        metrics.gas_per_second.set(self.gas.update(gas_used as f64, elapsed));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ewma() {
+        let mut ewma = Ewma::default();
+        // The first sample is taken as is
+        assert_eq!(ewma.update(100.0, Duration::from_secs(1)), 100.0);
+        // An empty interval is ignored
+        assert_eq!(ewma.update(100.0, Duration::ZERO), 100.0);
+        // A sample over the whole window moves the rate by 1 - 1/e of the difference
+        let rate = ewma.update(2000.0, THROUGHPUT_WINDOW);
+        assert!((rate - (100.0 + (1.0 - (-1.0f64).exp()) * 100.0)).abs() < 1e-9);
+        // The rate converges to a steady throughput
+        for _ in 0..100 {
+            ewma.update(50.0, Duration::from_secs(1));
+        }
+        assert!((ewma.rate.unwrap() - 50.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_block_timestamp_lag() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_millis(100_500);
+        assert_eq!(block_timestamp_lag(98, now), 2.5);
+        // Clock skew
+        assert_eq!(block_timestamp_lag(102, now), -1.5);
+    }
+
+    #[test]
+    fn test_trie_updates_node_count() {
+        use reth_trie::{updates::StorageTrieUpdates, BranchNodeCompact, Nibbles};
+
+        let mut trie_updates = TrieUpdates::default();
+        assert_eq!(trie_updates_node_count(&trie_updates), 0);
+        trie_updates.account_nodes.insert(Nibbles::from_vec(vec![1]), BranchNodeCompact::default());
+        trie_updates.removed_nodes.insert(Nibbles::from_vec(vec![2]));
+        let mut storage =
+            StorageTrieUpdates::new([(Nibbles::from_vec(vec![3]), BranchNodeCompact::default())]);
+        storage.removed_nodes.insert(Nibbles::from_vec(vec![4]));
+        trie_updates.storage_tries.insert(B256::with_last_byte(1), storage);
+        trie_updates.storage_tries.insert(B256::with_last_byte(2), StorageTrieUpdates::deleted());
+        assert_eq!(trie_updates_node_count(&trie_updates), 4);
+    }
+
+    #[test]
+    fn test_bundle_state_size() {
+        use revm::primitives::{AccountInfo, HashMap};
+
+        assert_eq!(bundle_state_size(&BundleState::default()), 0);
+        let address = Address::with_last_byte(1);
+        let code = Bytecode::new_raw(vec![0x60, 0x00].into());
+        let bundle_state = BundleState::builder(1..=1)
+            .state_present_account_info(address, AccountInfo::default())
+            .state_storage(
+                address,
+                HashMap::from_iter([
+                    (U256::from(1), (U256::ZERO, U256::from(1))),
+                    (U256::from(2), (U256::ZERO, U256::from(2))),
+                ]),
+            )
+            .revert_account_info(1, address, Some(None))
+            .revert_storage(1, address, vec![(U256::from(1), U256::ZERO)])
+            .contract(code.hash_slow(), code)
+            .build();
+        assert_eq!(
+            bundle_state_size(&bundle_state),
+            size_of::<(Address, BundleAccount)>() +
+                2 * size_of::<(U256, StorageSlot)>() +
+                size_of::<(B256, Bytecode)>() +
+                2 +
+                size_of::<(Address, AccountRevert)>() +
+                size_of::<(U256, RevertToSlot)>()
+        );
+    }
+
+    #[test]
+    fn test_metrics_sink() {
+        #[derive(Default)]
+        struct RecordingSink(std::sync::Mutex<Vec<(String, f64)>>);
+
+        impl MetricsSink for RecordingSink {
+            fn record_duration(&self, key: &Key, value: f64) {
+                self.0.lock().unwrap().push((key.name().to_string(), value));
+            }
+
+            fn increment_counter(&self, key: &Key, value: u64) {
+                let labels =
+                    key.labels().map(|label| label.value().to_string()).collect::<Vec<_>>();
+                self.0.lock().unwrap().push((format!("{}{labels:?}", key.name()), value as f64));
+            }
+
+            fn set_gauge(&self, key: &Key, value: f64) {
+                self.0.lock().unwrap().push((key.name().to_string(), value));
+            }
+        }
+
+        let sink = Arc::new(RecordingSink::default());
+        let metrics = PipeExecLayerMetrics::new(Some(&(sink.clone() as Arc<dyn MetricsSink>)));
+        metrics.execute_duration.record(0.5);
+        metrics.in_flight_blocks.increment(2.0);
+        metrics.in_flight_blocks.decrement(1.0);
+        metrics.record_filtered_txs(&[(0, TxFilterReason::SenderNotFound)]);
+        assert_eq!(
+            *sink.0.lock().unwrap(),
+            [
+                ("pipe_exec_layer.execute_duration".to_string(), 0.5),
+                ("pipe_exec_layer.in_flight_blocks".to_string(), 2.0),
+                ("pipe_exec_layer.in_flight_blocks".to_string(), 1.0),
+                ("pipe_exec_layer.filtered_transactions_total[]".to_string(), 1.0),
+                ("pipe_exec_layer.filtered_transactions_per_block".to_string(), 1.0),
+                ("pipe_exec_layer.filtered_transactions[\"sender_not_found\"]".to_string(), 1.0),
+            ]
+        );
+    }
 }
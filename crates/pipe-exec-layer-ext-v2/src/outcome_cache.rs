@@ -0,0 +1,73 @@
+use reth_execution_types::ExecutionOutcome;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+/// Cache of the execution outcomes of the most recently committed blocks, in ascending order of
+/// block number. The oldest block is evicted first, which is the least recently used one for
+/// queries on the hot tail of the chain.
+#[derive(Debug)]
+pub(crate) struct ExecutionOutcomeCache {
+    capacity: usize,
+    outcomes: Mutex<VecDeque<(u64 /* block number */, Arc<ExecutionOutcome>)>>,
+}
+
+impl ExecutionOutcomeCache {
+    /// Nothing is cached if `capacity` is zero.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { capacity, outcomes: Mutex::new(VecDeque::with_capacity(capacity)) }
+    }
+
+    /// Record the execution outcome of the block, evicting the oldest one if the cache is full.
+    /// Blocks are committed in order, so `block_number` is always greater than the cached ones.
+    pub(crate) fn push(&self, block_number: u64, execution_outcome: Arc<ExecutionOutcome>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut outcomes = self.outcomes.lock().unwrap();
+        if outcomes.len() == self.capacity {
+            outcomes.pop_front();
+        }
+        outcomes.push_back((block_number, execution_outcome));
+    }
+
+    /// Discard the execution outcomes of the blocks above `block_number`, which have been rolled
+    /// back by a reorg.
+    pub(crate) fn rollback_to(&self, block_number: u64) {
+        self.outcomes.lock().unwrap().retain(|(number, _)| *number <= block_number);
+    }
+
+    /// Returns `None` if the block is not committed yet or has been evicted.
+    pub(crate) fn get(&self, block_number: u64) -> Option<Arc<ExecutionOutcome>> {
+        let outcomes = self.outcomes.lock().unwrap();
+        let idx = outcomes.binary_search_by_key(&block_number, |(number, _)| *number).ok()?;
+        Some(outcomes[idx].1.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_execution_outcome_cache() {
+        let outcome =
+            |number| Arc::new(ExecutionOutcome { first_block: number, ..Default::default() });
+        let cache = ExecutionOutcomeCache::new(2);
+        for number in 1..=3 {
+            cache.push(number, outcome(number));
+        }
+        assert_eq!(cache.get(1), None);
+        assert_eq!(cache.get(2), Some(outcome(2)));
+        assert_eq!(cache.get(3), Some(outcome(3)));
+
+        cache.rollback_to(2);
+        assert_eq!(cache.get(2), Some(outcome(2)));
+        assert_eq!(cache.get(3), None);
+
+        let disabled = ExecutionOutcomeCache::new(0);
+        disabled.push(1, outcome(1));
+        assert_eq!(disabled.get(1), None);
+    }
+}
@@ -0,0 +1,62 @@
+use alloy_consensus::Transaction;
+use alloy_primitives::Address;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use reth_ethereum_primitives::TransactionSigned;
+use reth_evm::ParallelDatabase;
+use revm::primitives::HashSet;
+
+use tracing::*;
+
+/// The accounts touched first by the transactions, i.e. the senders and the recipients. Contract
+/// creations have no recipient.
+pub(crate) fn prefetch_addresses(txs: &[TransactionSigned], senders: &[Address]) -> Vec<Address> {
+    let mut addresses: HashSet<Address> = senders.iter().copied().collect();
+    addresses.extend(txs.iter().filter_map(|tx| tx.to()));
+    addresses.into_iter().collect()
+}
+
+/// Read the accounts and their code from the state view in parallel, so that the storage pages
+/// are hot when the EVM touches them serially during execution. Errors are only logged, since the
+/// same reads are retried during execution.
+pub(crate) fn prefetch_state<DB: ParallelDatabase>(db: &DB, addresses: Vec<Address>) {
+    addresses.into_par_iter().for_each(|address| {
+        let info = match db.basic_ref(address) {
+            Ok(Some(info)) => info,
+            Ok(None) => return,
+            Err(err) => {
+                debug!(target: "prefetch_state", ?address, %err, "failed to prefetch account");
+                return;
+            }
+        };
+        if info.code.is_none() && !info.is_empty_code_hash() {
+            if let Err(err) = db.code_by_hash_ref(info.code_hash) {
+                debug!(target: "prefetch_state", ?address, %err, "failed to prefetch code");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloy_consensus::TxLegacy;
+    use alloy_primitives::{PrimitiveSignature as Signature, TxKind};
+    use reth_ethereum_primitives::Transaction as EthTransaction;
+
+    fn legacy_tx(to: TxKind) -> TransactionSigned {
+        TransactionSigned::new_unhashed(
+            EthTransaction::Legacy(TxLegacy { to, ..Default::default() }),
+            Signature::test_signature(),
+        )
+    }
+
+    #[test]
+    fn test_prefetch_addresses() {
+        let alice = Address::with_last_byte(1);
+        let bob = Address::with_last_byte(2);
+        let txs = vec![legacy_tx(TxKind::Call(bob)), legacy_tx(TxKind::Create)];
+        let mut addresses = prefetch_addresses(&txs, &[alice, alice]);
+        addresses.sort();
+        assert_eq!(addresses, vec![alice, bob]);
+    }
+}
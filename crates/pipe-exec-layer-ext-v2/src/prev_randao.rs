@@ -0,0 +1,62 @@
+use crate::OrderedBlock;
+use alloy_consensus::Header;
+use alloy_primitives::B256;
+
+/// Check of the `prev_randao` of the ordered blocks against the source it must match, e.g. a
+/// beacon chain or a VRF, before the blocks are executed.
+///
+/// The `prev_randao` is written into the `mix_hash` of the block header and read by contracts
+/// through the `PREVRANDAO` opcode, so it changes both the execution and the block hash. Like
+/// the execution itself, the check must be deterministic, otherwise the nodes of the chain would
+/// disagree on which blocks are executed.
+pub trait PrevRandaoValidator: Send + Sync {
+    /// Check the `prev_randao` of `ordered_block`, which is executed on top of `parent_header`.
+    /// Returns why the value is invalid otherwise.
+    fn validate_prev_randao(
+        &self,
+        ordered_block: &OrderedBlock,
+        parent_header: &Header,
+    ) -> Result<(), String>;
+}
+
+impl<F> PrevRandaoValidator for F
+where
+    F: Fn(&OrderedBlock, &Header) -> Result<(), String> + Send + Sync,
+{
+    fn validate_prev_randao(
+        &self,
+        ordered_block: &OrderedBlock,
+        parent_header: &Header,
+    ) -> Result<(), String> {
+        self(ordered_block, parent_header)
+    }
+}
+
+impl std::fmt::Debug for dyn PrevRandaoValidator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrevRandaoValidator").finish_non_exhaustive()
+    }
+}
+
+/// Accept any `prev_randao` delivered by Coordinator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AcceptAnyPrevRandao;
+
+impl PrevRandaoValidator for AcceptAnyPrevRandao {
+    fn validate_prev_randao(&self, _: &OrderedBlock, _: &Header) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// The `prev_randao` of an ordered block was rejected by the configured
+/// [`PrevRandaoValidator`], so the block was not executed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid prev_randao {prev_randao} of block {block_id}: {reason}")]
+pub struct InvalidPrevRandao {
+    /// Id of the ordered block
+    pub block_id: B256,
+    /// The rejected `prev_randao`
+    pub prev_randao: B256,
+    /// Why the validator rejected it
+    pub reason: String,
+}
@@ -0,0 +1,136 @@
+use crate::OrderedBlock;
+
+use std::collections::BTreeMap;
+
+use tracing::*;
+
+/// Buffer of the ordered blocks received ahead of their turn, so that out-of-order delivery from
+/// Coordinator heals by itself once the gap is filled.
+#[derive(Debug)]
+pub(crate) struct ReorderBuffer {
+    capacity: usize,
+    pending: BTreeMap<u64 /* block number */, OrderedBlock>,
+}
+
+impl ReorderBuffer {
+    /// Blocks ahead of their turn are discarded if `capacity` is zero.
+    pub(crate) const fn new(capacity: usize) -> Self {
+        Self { capacity, pending: BTreeMap::new() }
+    }
+
+    /// Push a received block. Returns the blocks ready to be executed in order, i.e. the
+    /// contiguous blocks starting from `latest_block_number + 1`.
+    ///
+    /// Blocks already accepted (resent by Coordinator) are discarded, and so are the blocks ahead
+    /// of their turn if the buffer is full.
+    pub(crate) fn push(
+        &mut self,
+        latest_block_number: u64,
+        ordered_block: OrderedBlock,
+    ) -> Vec<OrderedBlock> {
+        let next_block_number = latest_block_number + 1;
+        if ordered_block.number < next_block_number {
+            warn!(target: "PipeExecService.run",
+                id=?ordered_block.id,
+                number=?ordered_block.number,
+                latest_block_number=?latest_block_number,
+                "discard ordered block which has been accepted"
+            );
+            return vec![];
+        }
+        if ordered_block.number > next_block_number {
+            if self.pending.contains_key(&ordered_block.number) {
+                warn!(target: "PipeExecService.run",
+                    id=?ordered_block.id,
+                    number=?ordered_block.number,
+                    "discard ordered block which has been buffered"
+                );
+            } else if self.pending.len() >= self.capacity {
+                error!(target: "PipeExecService.run",
+                    id=?ordered_block.id,
+                    number=?ordered_block.number,
+                    latest_block_number=?latest_block_number,
+                    capacity=?self.capacity,
+                    "reorder buffer is full, discard ordered block ahead of its turn"
+                );
+            } else {
+                debug!(target: "PipeExecService.run",
+                    id=?ordered_block.id,
+                    number=?ordered_block.number,
+                    latest_block_number=?latest_block_number,
+                    "buffer ordered block ahead of its turn"
+                );
+                self.pending.insert(ordered_block.number, ordered_block);
+            }
+            return vec![];
+        }
+
+        let mut ready = vec![ordered_block];
+        while let Some(ordered_block) =
+            self.pending.remove(&(next_block_number + ready.len() as u64))
+        {
+            ready.push(ordered_block);
+        }
+        ready
+    }
+
+    /// Discard all buffered blocks.
+    pub(crate) fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloy_primitives::{Address, B256};
+
+    fn ordered_block(number: u64) -> OrderedBlock {
+        OrderedBlock {
+            parent_id: B256::with_last_byte(number as u8 - 1),
+            id: B256::with_last_byte(number as u8),
+            number,
+            timestamp: number,
+            coinbase: Address::ZERO,
+            prev_randao: B256::ZERO,
+            withdrawals: Default::default(),
+            transactions: vec![],
+            senders: vec![],
+            blob_sidecars: None,
+            presumed_valid: false,
+        }
+    }
+
+    fn numbers(blocks: Vec<OrderedBlock>) -> Vec<u64> {
+        blocks.into_iter().map(|block| block.number).collect()
+    }
+
+    #[test]
+    fn test_duplicate_delivery() {
+        let mut buffer = ReorderBuffer::new(4);
+        assert_eq!(numbers(buffer.push(0, ordered_block(1))), vec![1]);
+        // Resent block is discarded
+        assert!(buffer.push(1, ordered_block(1)).is_empty());
+        // Resent block ahead of its turn is buffered only once
+        assert!(buffer.push(1, ordered_block(3)).is_empty());
+        assert!(buffer.push(1, ordered_block(3)).is_empty());
+        assert_eq!(numbers(buffer.push(1, ordered_block(2))), vec![2, 3]);
+        assert!(buffer.pending.is_empty());
+    }
+
+    #[test]
+    fn test_gapped_delivery() {
+        let mut buffer = ReorderBuffer::new(2);
+        assert!(buffer.push(0, ordered_block(3)).is_empty());
+        assert!(buffer.push(0, ordered_block(2)).is_empty());
+        // The buffer is full
+        assert!(buffer.push(0, ordered_block(5)).is_empty());
+        assert_eq!(numbers(buffer.push(0, ordered_block(1))), vec![1, 2, 3]);
+        assert!(buffer.push(3, ordered_block(5)).is_empty());
+        assert_eq!(numbers(buffer.push(3, ordered_block(4))), vec![4, 5]);
+
+        buffer.push(5, ordered_block(7));
+        buffer.clear();
+        assert_eq!(numbers(buffer.push(5, ordered_block(6))), vec![6]);
+    }
+}
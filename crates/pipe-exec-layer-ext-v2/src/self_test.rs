@@ -0,0 +1,241 @@
+//! Self-test executing an embedded fixture block, to check a deployment (e.g. its build of revm
+//! and grevm) before it joins the network.
+
+use crate::{Core, OrderedBlock, PipeExecLayerConfig};
+use alloy_consensus::{constants::EMPTY_WITHDRAWALS, Header, TxEip1559};
+use alloy_eips::{
+    eip4788::{BEACON_ROOTS_ADDRESS, BEACON_ROOTS_CODE},
+    eip4895::{Withdrawal, Withdrawals},
+};
+use alloy_primitives::{b256, keccak256, Address, PrimitiveSignature, TxKind, B256, U256};
+use gravity_storage::{GravityStorage, GravityStorageError};
+use reth_chainspec::ChainSpecBuilder;
+use reth_ethereum_primitives::{Transaction, TransactionSigned};
+use reth_evm_ethereum::{execute::EthExecutorProvider, EthEvmConfig};
+use reth_primitives_traits::Account;
+use reth_trie::{
+    root::{state_root_unhashed, storage_root_unhashed},
+    updates::TrieUpdates,
+    HashedPostState,
+};
+use revm::{
+    db::{AccountState, BundleState, CacheDB, DbAccount, EmptyDB},
+    primitives::{AccountInfo, Bytecode},
+};
+use std::sync::Arc;
+
+/// Hash of the fixture block, produced by a known-good build
+const FIXTURE_BLOCK_HASH: B256 =
+    b256!("2e54183bcc39dd4c22aa284b9ad456ea037d05f7ae530069b8eae4ed852571a6");
+
+/// Timestamp of the parent of the fixture block
+const FIXTURE_PARENT_TIMESTAMP: u64 = 1_700_000_000;
+
+/// Execute the embedded fixture block on a Cancun chain and check its hash against the one
+/// produced by a known-good build. The block carries an EIP-1559 transfer and a withdrawal, and
+/// triggers the EIP-4788 beacon roots contract call, so that the Shanghai and Cancun paths of
+/// execution and root calculation are covered. Returns the reason of the failure if the block
+/// fails to execute or its hash differs.
+pub fn self_test() -> Result<(), String> {
+    let chain_spec = Arc::new(ChainSpecBuilder::mainnet().cancun_activated().build());
+    let core = Core::standalone(
+        EthExecutorProvider::ethereum(chain_spec.clone()),
+        EthEvmConfig::new(chain_spec.clone()),
+        chain_spec,
+        FixtureStorage,
+        PipeExecLayerConfig::default(),
+    );
+
+    let parent_header = fixture_parent_header();
+    let parent_state = fixture_parent_state();
+    let result = core
+        .execute_ordered_block(fixture_block(), &parent_header, parent_state.clone())
+        .map_err(|err| format!("fixture block failed to execute: {err}"))?;
+    if !result.discarded_txs.is_empty() {
+        return Err(format!("fixture transactions discarded: {:?}", result.discarded_txs));
+    }
+
+    let mut block = result.block;
+    let execution_outcome = core.calculate_roots(&mut block, result.execution_output);
+    let mut state = parent_state;
+    apply_bundle_state(&mut state, &execution_outcome.bundle);
+    block.header.state_root = calculate_state_root(&state);
+    block.header.parent_hash = parent_header.hash_slow();
+
+    let block_hash = block.header.hash_slow();
+    if block_hash != FIXTURE_BLOCK_HASH {
+        return Err(format!(
+            "fixture block hash mismatch: expected {FIXTURE_BLOCK_HASH}, got {block_hash}, \
+             header {:?}",
+            block.header
+        ));
+    }
+    Ok(())
+}
+
+/// Header of the parent of the fixture block, whose gas usage keeps the base fee unchanged
+fn fixture_parent_header() -> Header {
+    Header {
+        number: 0,
+        timestamp: FIXTURE_PARENT_TIMESTAMP,
+        gas_limit: 30_000_000,
+        gas_used: 15_000_000,
+        base_fee_per_gas: Some(7),
+        withdrawals_root: Some(EMPTY_WITHDRAWALS),
+        blob_gas_used: Some(0),
+        excess_blob_gas: Some(0),
+        parent_beacon_block_root: Some(B256::ZERO),
+        ..Default::default()
+    }
+}
+
+/// State of the parent of the fixture block, with the funded sender of the transfer and the
+/// beacon roots contract
+fn fixture_parent_state() -> CacheDB<EmptyDB> {
+    let mut state = CacheDB::new(EmptyDB::default());
+    state.insert_account_info(
+        Address::repeat_byte(0x01),
+        AccountInfo { balance: U256::from(10).pow(U256::from(18)), ..Default::default() },
+    );
+    state.insert_account_info(
+        BEACON_ROOTS_ADDRESS,
+        AccountInfo {
+            nonce: 1,
+            code_hash: keccak256(&BEACON_ROOTS_CODE),
+            code: Some(Bytecode::new_raw(BEACON_ROOTS_CODE.clone())),
+            ..Default::default()
+        },
+    );
+    state
+}
+
+fn fixture_block() -> OrderedBlock {
+    let transfer = TransactionSigned::new_unhashed(
+        Transaction::Eip1559(TxEip1559 {
+            chain_id: 1,
+            nonce: 0,
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 2,
+            to: TxKind::Call(Address::repeat_byte(0x02)),
+            value: U256::from(1_000),
+            ..Default::default()
+        }),
+        PrimitiveSignature::test_signature(),
+    );
+    OrderedBlock {
+        parent_id: B256::repeat_byte(0x11),
+        id: B256::repeat_byte(0x22),
+        number: 1,
+        timestamp: FIXTURE_PARENT_TIMESTAMP + 12,
+        coinbase: Address::repeat_byte(0xcb),
+        prev_randao: B256::repeat_byte(0x33),
+        withdrawals: Withdrawals::new(vec![Withdrawal {
+            index: 0,
+            validator_index: 1,
+            address: Address::repeat_byte(0x03),
+            amount: 2,
+        }]),
+        transactions: vec![transfer],
+        senders: vec![Address::repeat_byte(0x01)],
+        blob_sidecars: None,
+        presumed_valid: false,
+    }
+}
+
+/// Calculate the state root of the whole state.
+pub(crate) fn calculate_state_root(state: &CacheDB<EmptyDB>) -> B256 {
+    state_root_unhashed(
+        state
+            .accounts
+            .iter()
+            .filter(|(_, account)| account.account_state != AccountState::NotExisting)
+            .map(|(address, account)| {
+                let storage_root = storage_root_unhashed(
+                    account
+                        .storage
+                        .iter()
+                        .filter(|(_, value)| !value.is_zero())
+                        .map(|(slot, value)| (B256::new(slot.to_be_bytes()), *value)),
+                );
+                (*address, Account::from(&account.info).into_trie_account(storage_root))
+            }),
+    )
+}
+
+/// Apply the net changes of a block to the state.
+pub(crate) fn apply_bundle_state(state: &mut CacheDB<EmptyDB>, bundle_state: &BundleState) {
+    for (address, bundle_account) in bundle_state.state() {
+        let Some(mut info) = bundle_account.info.clone() else {
+            state.accounts.insert(*address, DbAccount::new_not_existing());
+            continue;
+        };
+        if info.code.is_none() {
+            info.code = bundle_state.contracts.get(&info.code_hash).cloned();
+        }
+        state.insert_account_info(*address, info);
+        if bundle_account.was_destroyed() {
+            state.replace_account_storage(*address, Default::default()).unwrap();
+        }
+        for (slot, value) in &bundle_account.storage {
+            state.insert_account_storage(*address, *slot, value.present_value).unwrap();
+        }
+    }
+}
+
+/// Storage of the self-test core, which is never read since the parent state is passed to
+/// `execute_ordered_block` directly
+#[derive(Debug)]
+struct FixtureStorage;
+
+impl GravityStorage for FixtureStorage {
+    type StateView = CacheDB<EmptyDB>;
+
+    fn get_state_view(
+        &self,
+        block_number: u64,
+    ) -> Result<(B256, Self::StateView), GravityStorageError> {
+        Err(GravityStorageError::TooNew(block_number))
+    }
+
+    fn get_block_id(&self, _block_number: u64) -> Option<B256> {
+        None
+    }
+
+    fn insert_block_id(&self, _block_number: u64, _block_id: B256) {}
+
+    fn insert_bundle_state(&self, _block_number: u64, _bundle_state: &BundleState) {}
+
+    fn update_canonical(&self, _block_number: u64, _block_hash: B256) {}
+
+    fn rollback_to(&self, _block_number: u64) -> Result<(), GravityStorageError> {
+        Ok(())
+    }
+
+    fn unwind_canonical(
+        &self,
+        _block_number: u64,
+        _block_hash: B256,
+    ) -> Result<(), GravityStorageError> {
+        Ok(())
+    }
+
+    fn state_root_with_updates(
+        &self,
+        block_number: u64,
+    ) -> Result<(B256, Arc<HashedPostState>, Arc<TrieUpdates>), GravityStorageError> {
+        Err(GravityStorageError::TooNew(block_number))
+    }
+
+    fn state_root(&self, block_number: u64) -> Result<B256, GravityStorageError> {
+        Err(GravityStorageError::TooNew(block_number))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_self_test() {
+        assert_eq!(super::self_test(), Ok(()));
+    }
+}
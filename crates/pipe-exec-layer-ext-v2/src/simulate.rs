@@ -0,0 +1,216 @@
+use crate::{
+    canonical_ordered_block, BlockDump, BlockDumpError, Core, ExecuteOrderedBlockResult,
+    OrderedBlock, TxFilterReason,
+};
+
+use alloy_consensus::Header;
+use alloy_primitives::B256;
+use gravity_storage::{GravityStorage, GravityStorageError};
+use reth_ethereum_primitives::{Block, Receipt};
+use reth_evm::{
+    execute::{BlockExecutionError, BlockExecutorProvider},
+    ConfigureEvmEnv,
+};
+use reth_execution_types::ExecutionOutcome;
+use reth_primitives::EthPrimitives;
+use revm::db::BundleState;
+
+/// The result of simulating an ordered block.
+#[derive(Debug)]
+pub struct SimulationResult {
+    /// Header of the simulated block. The state root and the parent hash are not computed, since
+    /// they require merklizing against the live trie and sealing the parent block.
+    pub header: Header,
+    /// Gas used by the block
+    pub gas_used: u64,
+    /// Receipts of the transactions included in the block
+    pub receipts: Vec<Receipt>,
+    /// State changes made by the block
+    pub state: BundleState,
+    /// Transactions discarded before execution, with their index in the ordered block
+    pub discarded_txs: Vec<(usize, TxFilterReason)>,
+}
+
+/// The hash which an ordered block would be sealed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComputedBlockHash {
+    /// Hash of the sealed block
+    pub block_hash: B256,
+    /// Gas used by the block
+    pub gas_used: u64,
+}
+
+/// Errors of simulating an ordered block.
+#[derive(Debug, thiserror::Error)]
+pub enum SimulationError {
+    /// The block doesn't follow the latest executed block
+    #[error("block {number} doesn't follow the latest executed block {latest_executed}")]
+    NotOnLatestExecutedBlock {
+        /// Number of the simulated block
+        number: u64,
+        /// Number of the latest executed block
+        latest_executed: u64,
+    },
+    /// The parent id of the block doesn't match the latest executed block
+    #[error("parent id mismatch, expected {expected}, got {got}")]
+    ParentIdMismatch {
+        /// Id of the latest executed block
+        expected: B256,
+        /// Parent id of the simulated block
+        got: B256,
+    },
+    /// The latest executed block is not canonical yet, so the state root can't be calculated
+    /// against its state
+    #[error("block {parent} is not canonical yet, the canonical head is {canonical}")]
+    ParentNotCanonical {
+        /// Number of the latest executed block
+        parent: u64,
+        /// Number of the canonical head
+        canonical: u64,
+    },
+    /// The state view of the parent block is unavailable
+    #[error("failed to get state view: {0}")]
+    StateView(#[from] GravityStorageError),
+    /// The block failed to execute
+    #[error("failed to execute block: {0}")]
+    ExecutionFailed(#[from] BlockExecutionError),
+}
+
+/// Storage-agnostic access to `Core` for `PipeExecLayerApi`.
+pub(crate) trait Simulator: Send + Sync {
+    /// Execute the ordered block on top of the latest executed block without touching the
+    /// pipeline barriers or the storage.
+    fn simulate_ordered_block(
+        &self,
+        ordered_block: OrderedBlock,
+    ) -> Result<SimulationResult, SimulationError>;
+
+    /// Execute and seal the ordered block on top of the latest executed block, which must be
+    /// canonical, without touching the pipeline barriers or the storage.
+    fn compute_block_hash(
+        &self,
+        ordered_block: OrderedBlock,
+    ) -> Result<ComputedBlockHash, SimulationError>;
+
+    /// Collect the canonical block along with its execution outcome, executing the block again
+    /// if its outcome is not cached anymore.
+    fn block_dump(&self, block_number: u64) -> Result<BlockDump, BlockDumpError>;
+}
+
+impl std::fmt::Debug for dyn Simulator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Simulator").finish_non_exhaustive()
+    }
+}
+
+impl<Storage, ExecutorProvider, EvmConfig> Simulator for Core<Storage, ExecutorProvider, EvmConfig>
+where
+    Storage: GravityStorage,
+    ExecutorProvider: BlockExecutorProvider<Primitives = EthPrimitives>,
+    EvmConfig: ConfigureEvmEnv<Header = Header>,
+{
+    fn simulate_ordered_block(
+        &self,
+        ordered_block: OrderedBlock,
+    ) -> Result<SimulationResult, SimulationError> {
+        let (_, block, mut execution_outcome, discarded_txs) =
+            self.execute_on_latest_executed(ordered_block)?;
+        Ok(SimulationResult {
+            gas_used: block.header.gas_used,
+            header: block.header,
+            receipts: execution_outcome.receipts.pop().unwrap_or_default(),
+            state: execution_outcome.bundle,
+            discarded_txs,
+        })
+    }
+
+    fn compute_block_hash(
+        &self,
+        ordered_block: OrderedBlock,
+    ) -> Result<ComputedBlockHash, SimulationError> {
+        // Read before the latest executed block, so that a block made canonical in between is
+        // reported as not canonical rather than sealed on a stale parent hash
+        let head = self.head.get();
+        let (parent_header, mut block, execution_outcome, _) =
+            self.execute_on_latest_executed(ordered_block)?;
+        if head.block_number != parent_header.number {
+            return Err(SimulationError::ParentNotCanonical {
+                parent: parent_header.number,
+                canonical: head.block_number,
+            });
+        }
+        block.header.state_root =
+            self.storage.historical_state_root(parent_header.number, &execution_outcome.bundle)?;
+        block.header.parent_hash = head.block_hash;
+        Ok(ComputedBlockHash {
+            block_hash: block.header.hash_slow(),
+            gas_used: block.header.gas_used,
+        })
+    }
+
+    fn block_dump(&self, block_number: u64) -> Result<BlockDump, BlockDumpError> {
+        let canonical_block = |block_number| {
+            self.storage
+                .get_canonical_block(block_number)
+                .ok_or(BlockDumpError::BlockUnavailable(block_number))
+        };
+        let block = canonical_block(block_number)?;
+        if let Some(execution_outcome) = self.execution_outcomes.get(block_number) {
+            return Ok(BlockDump { block, execution_outcome: (*execution_outcome).clone() });
+        }
+        let parent_number =
+            block_number.checked_sub(1).ok_or(BlockDumpError::BlockUnavailable(block_number))?;
+        let parent = canonical_block(parent_number)?;
+        // The ids only label the logs of the execution
+        let block_id = |block_number| self.storage.get_block_id(block_number).unwrap_or_default();
+        let ordered_block =
+            canonical_ordered_block(&block, block_id(parent_number), block_id(block_number), true);
+        let state = self.storage.get_historical_state_view(parent_number)?;
+        let ExecuteOrderedBlockResult { block: mut executed, execution_output, .. } =
+            self.install(|| self.execute_ordered_block(ordered_block, parent.header(), state))?;
+        let execution_outcome = self.calculate_roots(&mut executed, execution_output);
+        Ok(BlockDump { block, execution_outcome })
+    }
+}
+
+impl<Storage, ExecutorProvider, EvmConfig> Core<Storage, ExecutorProvider, EvmConfig>
+where
+    Storage: GravityStorage,
+    ExecutorProvider: BlockExecutorProvider<Primitives = EthPrimitives>,
+    EvmConfig: ConfigureEvmEnv<Header = Header>,
+{
+    /// Execute the ordered block on top of the latest executed block and calculate its roots
+    /// other than the state root. Returns the parent header along with the block, its execution
+    /// outcome and the discarded transactions.
+    fn execute_on_latest_executed(
+        &self,
+        ordered_block: OrderedBlock,
+    ) -> Result<(Header, Block, ExecutionOutcome, Vec<(usize, TxFilterReason)>), SimulationError>
+    {
+        let parent_header = self.latest_executed_header.lock().unwrap().clone();
+        if ordered_block.number != parent_header.number + 1 {
+            return Err(SimulationError::NotOnLatestExecutedBlock {
+                number: ordered_block.number,
+                latest_executed: parent_header.number,
+            });
+        }
+        let state = match self
+            .storage
+            .get_state_view_by_id(parent_header.number, ordered_block.parent_id)
+        {
+            Ok(state) => state,
+            Err(GravityStorageError::BlockIdMismatch { block_id, stored_block_id, .. }) => {
+                return Err(SimulationError::ParentIdMismatch {
+                    expected: stored_block_id,
+                    got: block_id,
+                });
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let ExecuteOrderedBlockResult { mut block, execution_output, discarded_txs, .. } =
+            self.install(|| self.execute_ordered_block(ordered_block, &parent_header, state))?;
+        let execution_outcome = self.calculate_roots(&mut block, execution_output);
+        Ok((parent_header, block, execution_outcome, discarded_txs))
+    }
+}
@@ -0,0 +1,102 @@
+use alloy_primitives::{Address, U256};
+use reth_primitives_traits::Account;
+use revm::db::BundleState;
+
+/// Net change of an account in a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountDiff {
+    /// Address of the account
+    pub address: Address,
+    /// The account at the end of the block, or `None` if it doesn't exist anymore
+    pub account: Option<Account>,
+    /// Whether the storage of the account was wiped by selfdestruct before the storage changes
+    /// of the block were applied
+    pub storage_wiped: bool,
+}
+
+/// Net change of a storage slot in a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageDiff {
+    /// Address of the account owning the slot
+    pub address: Address,
+    /// Key of the slot
+    pub slot: U256,
+    /// Value of the slot at the start of the block
+    pub previous_value: U256,
+    /// Value of the slot at the end of the block
+    pub value: U256,
+}
+
+/// Collect the net changes of the accounts and storage slots from the bundle state of a block,
+/// skipping the ones touched but left unchanged. Both are sorted by address, then by slot.
+/// The storage changes include the writes of the system calls around the transactions, e.g. the
+/// EIP-4788 beacon roots contract call.
+pub(crate) fn state_diff(bundle_state: &BundleState) -> (Vec<AccountDiff>, Vec<StorageDiff>) {
+    let mut accounts = Vec::new();
+    let mut storage = Vec::new();
+    for (address, bundle_account) in bundle_state.state() {
+        let storage_wiped = bundle_account.was_destroyed();
+        if bundle_account.is_info_changed() || storage_wiped {
+            accounts.push(AccountDiff {
+                address: *address,
+                account: bundle_account.info.as_ref().map(Account::from),
+                storage_wiped,
+            });
+        }
+        storage.extend(bundle_account.storage.iter().filter(|(_, slot)| slot.is_changed()).map(
+            |(key, slot)| StorageDiff {
+                address: *address,
+                slot: *key,
+                previous_value: slot.previous_or_original_value,
+                value: slot.present_value,
+            },
+        ));
+    }
+    accounts.sort_unstable_by_key(|diff| diff.address);
+    storage.sort_unstable_by_key(|diff| (diff.address, diff.slot));
+    (accounts, storage)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use revm::primitives::{AccountInfo, HashMap};
+
+    #[test]
+    fn test_state_diff() {
+        let created = Address::with_last_byte(1);
+        let untouched = Address::with_last_byte(2);
+        let info = AccountInfo { nonce: 1, balance: U256::from(10), ..Default::default() };
+        let bundle_state = BundleState::builder(1..=1)
+            .state_present_account_info(created, info.clone())
+            .state_storage(
+                created,
+                HashMap::from_iter([
+                    (U256::from(1), (U256::ZERO, U256::from(10))),
+                    (U256::from(2), (U256::from(5), U256::from(5))),
+                ]),
+            )
+            .state_original_account_info(untouched, info.clone())
+            .state_present_account_info(untouched, info.clone())
+            .build();
+
+        let (accounts, storage) = state_diff(&bundle_state);
+        assert_eq!(
+            accounts,
+            vec![AccountDiff {
+                address: created,
+                account: Some(Account::from(&info)),
+                storage_wiped: false
+            }]
+        );
+        assert_eq!(
+            storage,
+            vec![StorageDiff {
+                address: created,
+                slot: U256::from(1),
+                previous_value: U256::ZERO,
+                value: U256::from(10)
+            }]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use revm::{
+    primitives::{AccountInfo, Address, Bytecode, B256, U256},
+    DatabaseRef,
+};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// Number of reads served by a state view during the execution of a block.
+#[derive(Debug, Default)]
+pub(crate) struct StateReads {
+    accounts: AtomicU64,
+    storage_slots: AtomicU64,
+}
+
+impl StateReads {
+    /// Number of account reads, including the repeated reads of the same account
+    pub(crate) fn accounts(&self) -> u64 {
+        self.accounts.load(Ordering::Relaxed)
+    }
+
+    /// Number of storage slot reads, including the repeated reads of the same slot
+    pub(crate) fn storage_slots(&self) -> u64 {
+        self.storage_slots.load(Ordering::Relaxed)
+    }
+}
+
+/// State view counting the reads served to the executor, which owns the view while the counts
+/// are shared with the pipeline. Relaxed atomic increments keep the overhead negligible next to
+/// the reads themselves, even when Grevm reads from many threads at once.
+#[derive(Debug)]
+pub(crate) struct CountingStateView<DB> {
+    inner: DB,
+    reads: Arc<StateReads>,
+}
+
+impl<DB> CountingStateView<DB> {
+    pub(crate) const fn new(inner: DB, reads: Arc<StateReads>) -> Self {
+        Self { inner, reads }
+    }
+}
+
+impl<DB: DatabaseRef> DatabaseRef for CountingStateView<DB> {
+    type Error = DB::Error;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.reads.accounts.fetch_add(1, Ordering::Relaxed);
+        self.inner.basic_ref(address)
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.inner.code_by_hash_ref(code_hash)
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.reads.storage_slots.fetch_add(1, Ordering::Relaxed);
+        self.inner.storage_ref(address, index)
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        self.inner.block_hash_ref(number)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use revm::db::{CacheDB, EmptyDB};
+
+    #[test]
+    fn test_counting_state_view() {
+        let address = Address::with_last_byte(1);
+        let mut state = CacheDB::new(EmptyDB::default());
+        state.insert_account_info(address, AccountInfo::default());
+        let reads = Arc::new(StateReads::default());
+        let view = CountingStateView::new(state, reads.clone());
+
+        view.basic_ref(address).unwrap();
+        view.basic_ref(Address::with_last_byte(2)).unwrap();
+        view.storage_ref(address, U256::ZERO).unwrap();
+        view.block_hash_ref(0).unwrap();
+        assert_eq!(reads.accounts(), 2);
+        assert_eq!(reads.storage_slots(), 1);
+    }
+}
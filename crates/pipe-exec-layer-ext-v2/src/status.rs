@@ -0,0 +1,86 @@
+use alloy_primitives::B256;
+use std::{collections::BTreeMap, sync::Mutex};
+
+/// Progress of an ordered block through the pipeline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlockStatus {
+    /// The block is not known to the pipeline, e.g. it's not executed yet, has been discarded,
+    /// or has been made canonical long enough ago to be evicted
+    #[default]
+    Unknown,
+    /// The block has been executed, and its hash is waiting for verification by Coordinator
+    Executed,
+    /// The block hash has been verified, and the block is waiting to be made canonical
+    Verified,
+    /// The block has been made canonical
+    Canonical,
+}
+
+/// Status of the blocks going through the pipeline, keyed by block number since at most one
+/// block of each number is in the pipeline at a time. The blocks in flight are always tracked,
+/// while only the most recent `capacity` canonical blocks are kept.
+#[derive(Debug)]
+pub(crate) struct BlockStatusMap {
+    capacity: usize,
+    statuses: Mutex<BTreeMap<u64 /* block number */, (B256 /* block id */, BlockStatus)>>,
+}
+
+impl BlockStatusMap {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { capacity, statuses: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Record the status of the block, evicting the oldest canonical blocks beyond the capacity.
+    pub(crate) fn set(&self, block_number: u64, block_id: B256, status: BlockStatus) {
+        let mut statuses = self.statuses.lock().unwrap();
+        statuses.insert(block_number, (block_id, status));
+        let canonical =
+            statuses.values().filter(|(_, status)| *status == BlockStatus::Canonical).count();
+        // Blocks are made canonical in order, so the canonical blocks come first
+        for _ in self.capacity..canonical {
+            statuses.pop_first();
+        }
+    }
+
+    /// Status of the block, looked up from the most recent one.
+    pub(crate) fn get(&self, block_id: B256) -> BlockStatus {
+        let statuses = self.statuses.lock().unwrap();
+        statuses
+            .values()
+            .rev()
+            .find(|(id, _)| *id == block_id)
+            .map(|(_, status)| *status)
+            .unwrap_or_default()
+    }
+
+    /// Forget the blocks above `block_number`, which have been rolled back.
+    pub(crate) fn rollback_to(&self, block_number: u64) {
+        self.statuses.lock().unwrap().retain(|number, _| *number <= block_number);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_block_status_map() {
+        let id = B256::with_last_byte;
+        let statuses = BlockStatusMap::new(1);
+        statuses.set(1, id(1), BlockStatus::Canonical);
+        statuses.set(2, id(2), BlockStatus::Verified);
+        statuses.set(3, id(3), BlockStatus::Executed);
+        assert_eq!(statuses.get(id(1)), BlockStatus::Canonical);
+        assert_eq!(statuses.get(id(2)), BlockStatus::Verified);
+
+        // The oldest canonical block is evicted, while the blocks in flight are kept
+        statuses.set(2, id(2), BlockStatus::Canonical);
+        assert_eq!(statuses.get(id(1)), BlockStatus::Unknown);
+        assert_eq!(statuses.get(id(2)), BlockStatus::Canonical);
+        assert_eq!(statuses.get(id(3)), BlockStatus::Executed);
+
+        statuses.rollback_to(2);
+        assert_eq!(statuses.get(id(3)), BlockStatus::Unknown);
+        assert_eq!(statuses.get(id(4)), BlockStatus::Unknown);
+    }
+}
@@ -0,0 +1,201 @@
+//! Test utilities for running the pipeline end to end without a database.
+
+use crate::{
+    build_pipe_exec_layer,
+    self_test::{apply_bundle_state, calculate_state_root},
+    ExecutionArgs, PipeExecLayerApi, PipeExecLayerConfig, PipeExecLayerConfigError,
+    PipeExecLayerExt,
+};
+use alloy_consensus::Header;
+use alloy_primitives::B256;
+use gravity_storage::{GravityStorage, GravityStorageError};
+use reth_chainspec::ChainSpec;
+use reth_ethereum_primitives::Block;
+use reth_evm_ethereum::{execute::EthExecutorProvider, EthEvmConfig};
+use reth_primitives::EthPrimitives;
+use reth_primitives_traits::RecoveredBlock;
+use reth_trie::{updates::TrieUpdates, HashedPostState};
+use revm::db::{BundleState, CacheDB, EmptyDB};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+/// Launch a pipeline executing the ordered blocks with `EthExecutorProvider` on top of `storage`,
+/// and return the api for Coordinator along with the extension for EL. Unlike
+/// `new_pipe_exec_layer_api_with_config`, the extension is not registered globally, so that any
+/// number of pipelines can run in the same process, e.g. one per benchmark iteration.
+/// Returns an error if the config is invalid.
+pub fn new_test_pipe_exec_layer<Storage: GravityStorage>(
+    chain_spec: Arc<ChainSpec>,
+    storage: Storage,
+    latest_block_header: Header,
+    latest_block_hash: B256,
+    execution_args_rx: oneshot::Receiver<ExecutionArgs>,
+    config: PipeExecLayerConfig,
+) -> Result<(PipeExecLayerApi, PipeExecLayerExt<EthPrimitives>), PipeExecLayerConfigError> {
+    build_pipe_exec_layer(
+        EthExecutorProvider::ethereum(chain_spec.clone()),
+        EthEvmConfig::new(chain_spec.clone()),
+        chain_spec,
+        storage,
+        latest_block_header,
+        latest_block_hash,
+        execution_args_rx,
+        config,
+    )
+}
+
+/// In-memory `GravityStorage`, which keeps the block ids and the bundle states of the executed
+/// blocks, and serves the state of a block by applying the bundle states up to it on top of the
+/// base state, i.e. the state of the block the pipeline starts from. The state root is calculated
+/// from scratch over the whole state, without trie updates. Pruned bundle states are merged into
+/// the base state. Clones share the same storage, so a test can inspect the storage handed to the
+/// pipeline. The canonical blocks inserted by the test are served for the replay on startup,
+/// along with the historical states of the blocks whose bundle states haven't been pruned.
+#[derive(Debug, Clone)]
+pub struct InMemoryStorage {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    base_state: CacheDB<EmptyDB>,
+    block_ids: HashMap<u64, B256>,
+    bundle_states: BTreeMap<u64, BundleState>,
+    canonical_hashes: BTreeMap<u64, B256>,
+    canonical_blocks: BTreeMap<u64, RecoveredBlock<Block>>,
+}
+
+impl InMemoryStorage {
+    /// Create a storage whose blocks are executed on top of `base_state`.
+    pub fn new(base_state: CacheDB<EmptyDB>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                base_state,
+                block_ids: HashMap::new(),
+                bundle_states: BTreeMap::new(),
+                canonical_hashes: BTreeMap::new(),
+                canonical_blocks: BTreeMap::new(),
+            })),
+        }
+    }
+
+    /// Hash of the canonical block at `block_number`, if made canonical through the storage.
+    pub fn canonical_hash(&self, block_number: u64) -> Option<B256> {
+        self.inner.lock().unwrap().canonical_hashes.get(&block_number).copied()
+    }
+
+    /// The latest block number made canonical through the storage.
+    pub fn canonical_block_number(&self) -> Option<u64> {
+        self.inner.lock().unwrap().canonical_hashes.last_key_value().map(|(number, _)| *number)
+    }
+
+    /// Persist the canonical block, as EL does once the block is made canonical.
+    pub fn insert_canonical_block(&self, block: RecoveredBlock<Block>) {
+        self.inner.lock().unwrap().canonical_blocks.insert(block.header().number, block);
+    }
+
+    /// Bundle state of the executed block at `block_number`.
+    pub fn bundle_state(&self, block_number: u64) -> Option<BundleState> {
+        self.inner.lock().unwrap().bundle_states.get(&block_number).cloned()
+    }
+}
+
+impl GravityStorage for InMemoryStorage {
+    type StateView = CacheDB<EmptyDB>;
+
+    fn get_state_view(
+        &self,
+        block_number: u64,
+    ) -> Result<(B256, Self::StateView), GravityStorageError> {
+        let inner = self.inner.lock().unwrap();
+        let block_id = inner
+            .block_ids
+            .get(&block_number)
+            .copied()
+            .ok_or(GravityStorageError::TooNew(block_number))?;
+        let mut state = inner.base_state.clone();
+        for bundle_state in inner.bundle_states.range(..=block_number).map(|(_, state)| state) {
+            apply_bundle_state(&mut state, bundle_state);
+        }
+        Ok((block_id, state))
+    }
+
+    fn get_block_id(&self, block_number: u64) -> Option<B256> {
+        self.inner.lock().unwrap().block_ids.get(&block_number).copied()
+    }
+
+    fn insert_block_id(&self, block_number: u64, block_id: B256) {
+        self.inner.lock().unwrap().block_ids.insert(block_number, block_id);
+    }
+
+    fn insert_bundle_state(&self, block_number: u64, bundle_state: &BundleState) {
+        self.inner.lock().unwrap().bundle_states.insert(block_number, bundle_state.clone());
+    }
+
+    fn update_canonical(&self, block_number: u64, block_hash: B256) {
+        self.inner.lock().unwrap().canonical_hashes.insert(block_number, block_hash);
+    }
+
+    fn prune_bundle_states(&self, below: u64) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let retained = inner.bundle_states.split_off(&below);
+        let pruned = std::mem::replace(&mut inner.bundle_states, retained);
+        for bundle_state in pruned.values() {
+            apply_bundle_state(&mut inner.base_state, bundle_state);
+        }
+        pruned.len()
+    }
+
+    fn rollback_to(&self, block_number: u64) -> Result<(), GravityStorageError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.block_ids.retain(|number, _| *number <= block_number);
+        inner.bundle_states.retain(|number, _| *number <= block_number);
+        Ok(())
+    }
+
+    fn unwind_canonical(
+        &self,
+        block_number: u64,
+        _block_hash: B256,
+    ) -> Result<(), GravityStorageError> {
+        self.rollback_to(block_number)?;
+        self.inner.lock().unwrap().canonical_hashes.retain(|number, _| *number <= block_number);
+        Ok(())
+    }
+
+    fn get_canonical_block(&self, block_number: u64) -> Option<RecoveredBlock<Block>> {
+        self.inner.lock().unwrap().canonical_blocks.get(&block_number).cloned()
+    }
+
+    fn get_historical_state_view(
+        &self,
+        block_number: u64,
+    ) -> Result<Self::StateView, GravityStorageError> {
+        Ok(self.get_state_view(block_number)?.1)
+    }
+
+    fn historical_state_root(
+        &self,
+        block_number: u64,
+        bundle_state: &BundleState,
+    ) -> Result<B256, GravityStorageError> {
+        let mut state = self.get_historical_state_view(block_number)?;
+        apply_bundle_state(&mut state, bundle_state);
+        Ok(calculate_state_root(&state))
+    }
+
+    fn state_root_with_updates(
+        &self,
+        block_number: u64,
+    ) -> Result<(B256, Arc<HashedPostState>, Arc<TrieUpdates>), GravityStorageError> {
+        Ok((self.state_root(block_number)?, Default::default(), Default::default()))
+    }
+
+    fn state_root(&self, block_number: u64) -> Result<B256, GravityStorageError> {
+        let (_, state) = self.get_state_view(block_number)?;
+        Ok(calculate_state_root(&state))
+    }
+}
@@ -0,0 +1,85 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+/// Latencies of the pipeline stages of a block, measured the same way as the duration histograms
+/// of `PipeExecLayerMetrics`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StageTimings {
+    /// How long it took for the block to be executed
+    pub execute: Duration,
+    /// How long it took for the block to be merklized, since the execution started
+    pub merklize: Duration,
+    /// How long it took for the block to be sealed
+    pub seal: Duration,
+    /// How long it took for the block hash to be verified by Coordinator, or by the pipeline
+    /// itself in single-node mode
+    pub verify: Duration,
+    /// How long it took for the block to be made canonical, since the verification started
+    pub make_canonical: Duration,
+}
+
+/// Ring buffer of the stage timings of the most recently committed blocks, in ascending order of
+/// block number.
+#[derive(Debug)]
+pub(crate) struct StageTimingsBuffer {
+    capacity: usize,
+    timings: Mutex<VecDeque<(u64 /* block number */, StageTimings)>>,
+}
+
+impl StageTimingsBuffer {
+    /// Nothing is recorded if `capacity` is zero.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { capacity, timings: Mutex::new(VecDeque::with_capacity(capacity)) }
+    }
+
+    /// Record the timings of the block, evicting the oldest one if the buffer is full.
+    /// Blocks are committed in order, so `block_number` is always greater than the recorded ones.
+    pub(crate) fn push(&self, block_number: u64, stage_timings: StageTimings) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut timings = self.timings.lock().unwrap();
+        if timings.len() == self.capacity {
+            timings.pop_front();
+        }
+        timings.push_back((block_number, stage_timings));
+    }
+
+    /// Discard the timings of the blocks above `block_number`, which have been rolled back by a
+    /// reorg, so that the recorded blocks stay in order.
+    pub(crate) fn rollback_to(&self, block_number: u64) {
+        self.timings.lock().unwrap().retain(|(number, _)| *number <= block_number);
+    }
+
+    /// Returns `None` if the block is not committed yet or has been evicted.
+    pub(crate) fn get(&self, block_number: u64) -> Option<StageTimings> {
+        let timings = self.timings.lock().unwrap();
+        let idx = timings.binary_search_by_key(&block_number, |(number, _)| *number).ok()?;
+        Some(timings[idx].1)
+    }
+
+    pub(crate) fn recent(&self) -> Vec<(u64, StageTimings)> {
+        self.timings.lock().unwrap().iter().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stage_timings_buffer() {
+        let buffer = StageTimingsBuffer::new(2);
+        let timings =
+            |ms| StageTimings { execute: Duration::from_millis(ms), ..Default::default() };
+        buffer.push(1, timings(1));
+        buffer.push(2, timings(2));
+        buffer.push(3, timings(3));
+        assert_eq!(buffer.get(1), None);
+        assert_eq!(buffer.get(3), Some(timings(3)));
+        assert_eq!(buffer.recent(), vec![(2, timings(2)), (3, timings(3))]);
+
+        let disabled = StageTimingsBuffer::new(0);
+        disabled.push(1, timings(1));
+        assert!(disabled.recent().is_empty());
+    }
+}
@@ -0,0 +1,64 @@
+use alloy_primitives::TxHash;
+use reth_ethereum_primitives::{Receipt, TransactionSigned};
+
+/// Outcome of a transaction executed by the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxTrace {
+    /// Hash of the transaction
+    pub tx_hash: TxHash,
+    /// Gas used by the transaction
+    pub gas_used: u64,
+    /// Whether the transaction succeeded
+    pub success: bool,
+    /// Number of logs emitted by the transaction
+    pub logs_count: usize,
+}
+
+/// Sink of the outcomes of the transactions executed by the pipeline, e.g. for debugging
+/// consensus divergence between nodes.
+///
+/// The outcomes are taken from the receipts once a block is executed, so no inspector is attached
+/// to the EVM, and nothing is collected without a sink. The sink is called on the execution stage
+/// before the next block can be executed, so it should return quickly. A block executed again
+/// after a rollback is traced again.
+pub trait TxTraceSink: Send + Sync {
+    /// Record the outcome of a transaction of the block `block_number`, in the order of execution.
+    fn on_tx(&self, block_number: u64, trace: TxTrace);
+}
+
+impl<F> TxTraceSink for F
+where
+    F: Fn(u64, TxTrace) + Send + Sync,
+{
+    fn on_tx(&self, block_number: u64, trace: TxTrace) {
+        self(block_number, trace)
+    }
+}
+
+impl std::fmt::Debug for dyn TxTraceSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TxTraceSink").finish_non_exhaustive()
+    }
+}
+
+/// Feed the outcomes of the executed transactions of a block to the sink.
+pub(crate) fn trace_txs(
+    sink: &dyn TxTraceSink,
+    block_number: u64,
+    txs: &[TransactionSigned],
+    receipts: &[Receipt],
+) {
+    let mut prev_cumulative_gas_used = 0;
+    for (tx, receipt) in txs.iter().zip(receipts) {
+        sink.on_tx(
+            block_number,
+            TxTrace {
+                tx_hash: *tx.hash(),
+                gas_used: receipt.cumulative_gas_used - prev_cumulative_gas_used,
+                success: receipt.success,
+                logs_count: receipt.logs.len(),
+            },
+        );
+        prev_cumulative_gas_used = receipt.cumulative_gas_used;
+    }
+}